@@ -1,22 +1,48 @@
 import * as store from "svelte/store"
 
-export interface Stat {
+export interface Template {
     name: string;
-    type: string;
+    parent: string | null;
 }
 
-export interface CharacterStat {
+export interface Stat {
     name: string;
+    template?: string;
     type: string;
+    options?: string[];
+    min?: number;
+    max?: number;
+    formula?: string;
+    default?: number | string;
+    label?: string;
+    description?: string;
+    order?: number;
+    category?: string;
+    visibility?: "public" | "gm";
+}
+
+export interface CharacterStat extends Stat {
     value: boolean | number | string;
 }
 
 export interface Character {
     name: string;
+    owner: string | null;
+    template: string;
     stats: CharacterStat[];
+    inventory: InventoryItem[];
+}
+
+export interface InventoryItem {
+    id: number;
+    weapon: Weapon | null;
+    nickname: string | null;
+    equipped: boolean;
+    notes: string;
 }
 
 export interface Part {
+    id: number;
     name: string;
     details: string;
     type: string;
@@ -37,18 +63,70 @@ interface Weapon {
     range: string;
     damage: string;
     details: string[];
+    seed?: number;
 }
 
 interface State {
+    templates: Template[];
     stats: Stat[];
     characters: Character[];
     parts: Part[];
     weapon?: Weapon;
+    revision: number;
 }
 
+export interface Delta {
+    templates: Template[];
+    stats: Stat[];
+    characters: Character[];
+    parts: Part[];
+    weapon?: Weapon;
+    removed: {
+        templates: string[];
+        stats: string[];
+        characters: string[];
+        parts: number[];
+    };
+    revision: number;
+}
+
+function merge<T, K>(items: T[], changed: T[], removed: K[], key: (item: T) => K): T[] {
+    let kept = items.filter(item => !removed.includes(key(item)))
+    for (const item of changed) {
+        let index = kept.findIndex(old => key(old) == key(item))
+        index == -1 ? kept.push(item) : kept[index] = item
+    }
+    return kept
+}
+
+export function applyDelta(current: State, delta: Delta): State {
+    return {
+        templates: merge(current.templates, delta.templates, delta.removed.templates, template => template.name),
+        stats: merge(current.stats, delta.stats, delta.removed.stats, stat => stat.name),
+        characters: merge(current.characters, delta.characters, delta.removed.characters, character => character.name),
+        parts: merge(current.parts, delta.parts, delta.removed.parts, part => part.id),
+        weapon: delta.weapon ?? current.weapon,
+        revision: delta.revision,
+    }
+}
+
+// the campaign is picked with `?campaign=` and falls back to the default one
+const campaign = new URLSearchParams(location.search).get("campaign") ?? "default"
+
+export const api = `/api/campaigns/${encodeURIComponent(campaign)}`
+
 export const state: store.Writable<State> = store.writable({
+    templates: [],
     stats: [],
     characters: [],
     parts: [],
     weapon: null,
+    revision: 0,
 });
+
+export interface ApiError {
+    error: {
+        code: "missing_parameter" | "invalid_value" | "not_found" | "unauthorized" | "forbidden" | "method_not_allowed" | "conflict" | "unprocessable" | "internal";
+        message: string;
+    };
+}
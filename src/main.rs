@@ -1,16 +1,35 @@
-use std::{fs::File, path::PathBuf, time::Duration};
+use std::{
+    cell::Cell,
+    collections::{BTreeMap, HashMap, HashSet},
+    fs::File,
+    io::Read as _,
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
 
 use anyhow::{Context as _, Result};
 use oxhttp::{
-    model::{Request, Response, ResponseBuilder, Status},
+    model::{HeaderName, Method, Request, Response, ResponseBuilder, Status},
     Server,
 };
 use path_tree::PathTree;
-use rusqlite::{params, Connection, Transaction};
-
-use crate::models::{
-    Character, CharacterStat, Curve, Pairs, Params, Part, PartType, Rarity, Stat, State, Weapon,
-    WeaponCurve, Company,
+use rand::SeedableRng as _;
+use rand_chacha::ChaCha8Rng;
+use rusqlite::{params, types::Type, Connection, OptionalExtension as _, Transaction};
+
+use crate::{
+    error::{ApiError, OrConflict as _},
+    events::Events,
+    models::{
+        Action, Campaign, Change, Character, CharacterSnapshot, CharacterStat, Company, Curve,
+        Delta, Dice, DiceStat, DropTable, Entity, Formula, HistoryEntry, HistoryFilter,
+        HistoryStatus, Id, InventoryItem, ItemSnapshot, Pairs, Params, Part, PartType, Payload,
+        Rarity, RarityWeight, Removed, Role, Roll, RollEntry, SavedWeapon, Stat, StatMeta,
+        StatSnapshot, StatType, StatValue, State, Template, User, Visibility, Weapon, WeaponCurve,
+        WeaponDisplay, WeaponError, WeaponFilter, WeaponType, WeaponWeight,
+    },
+    pool::{Pool, PooledConnection},
 };
 
 static FAVICON_ANDROID_192: &[u8] = include_bytes!("../frontend/dist/android-chrome-192x192.png");
@@ -22,29 +41,66 @@ static FAVICON: &[u8] = include_bytes!("../frontend/dist/favicon.ico");
 static WEB_MANIFEST: &[u8] = include_bytes!("../frontend/dist/site.webmanifest");
 
 static INDEX: &str = include_str!("../frontend/dist/index.html");
+static LOGIN: &str = include_str!("../frontend/dist/login.html");
 static CSS: &str = include_str!("../frontend/dist/assets/index.css");
 static JS: &str = include_str!("../frontend/dist/assets/index.js");
 
 static LEVEL_MAX: u8 = 32;
 
-static DATABASE_INIT_SQL: &str = "
-    CREATE TABLE IF NOT EXISTS characters (name TEXT NOT NULL);
-    CREATE TABLE IF NOT EXISTS template (key TEXT NOT NULL, type TEXT NOT NULL);
-    CREATE TABLE IF NOT EXISTS stats (character TEXT NOT NULL, key TEXT NOT NULL, value INTEGER NOT NULL);
-    CREATE TABLE IF NOT EXISTS weapon_curves (name TEXT NOT NULL, type TEXT NOT NULL, a REAL NOT NULL, b REAL NOT NULL, c REAL NOT NULL, d REAL NOT NULL);
-    CREATE TABLE IF NOT EXISTS weapon_parts (name TEXT NOT NULL, details TEXT NOT NULL, type TEXT NOT NULL, rarity TEXT NOT NULL, company TEXT NOT NULL);
-";
-static WEAPON_PARTS_SQL: &str = "
-    INSERT INTO weapon_curves VALUES
+/// Part ids a campaign can hand out, weapon ids store a byte per part.
+static PART_LIMIT: usize = 256;
+
+/// Template every campaign starts with, used when no other one is given.
+static BASE_TEMPLATE: &str = "base";
+
+/// Seed curves for the campaign `?1`.
+static WEAPON_CURVES_SQL: &str = "
+    INSERT OR IGNORE INTO weapon_curves (campaign, name, type, a, b, c, d) SELECT ?1, * FROM (VALUES
         ('assault rifle', 'cubic', 0.25, 1, 0.25, 1),
         ('grenade launcher', 'cubic', 0.25, 1, 0.25, 1),
         ('pistol', 'cubic', 0.25, 1, 0.25, 1),
         ('rocket launcher', 'cubic', 0.25, 1, 0.25, 1),
         ('shotgun', 'cubic', 0.25, 1, 0.25, 1),
         ('sniper rifle', 'cubic', 0.25, 1, 0.25, 1),
-        ('submachine gun', 'cubic', 0.25, 1, 0.25, 1);
+        ('submachine gun', 'cubic', 0.25, 1, 0.25, 1)
+    )
+";
+
+/// Seed weapon type weights for the campaign `?1`, rockets only come up from
+/// level 20 on.
+static WEAPON_WEIGHTS_SQL: &str = "
+    INSERT OR IGNORE INTO weapon_weights (campaign, type, level, weight) SELECT ?1, * FROM (VALUES
+        ('assault rifle', 0, 2),
+        ('grenade launcher', 0, 1),
+        ('pistol', 0, 2),
+        ('rocket launcher', 20, 1),
+        ('shotgun', 0, 2),
+        ('sniper rifle', 0, 1),
+        ('submachine gun', 0, 2)
+    )
+";
+
+/// Seed rarity weights for the campaign `?1`, rarer parts unlock at higher
+/// levels and common ones get less likely.
+static RARITY_WEIGHTS_SQL: &str = "
+    INSERT OR IGNORE INTO rarity_weights (campaign, rarity, level, weight) SELECT ?1, * FROM (VALUES
+        ('common', 0, 6),
+        ('common', 20, 3),
+        ('uncommon', 0, 4),
+        ('rare', 8, 2),
+        ('rare', 20, 3),
+        ('epic', 12, 1),
+        ('epic', 20, 2),
+        ('legendary', 20, 1),
+        ('unique', 20, 1)
+    )
+";
 
-    INSERT INTO weapon_parts VALUES
+/// Seed parts for the campaign `?1`, parts it already has are skipped so they
+/// don't use up part ids.
+static WEAPON_PARTS_SQL: &str = "
+    INSERT INTO weapon_parts (campaign, id, name, details, type, rarity, company)
+    SELECT ?1, (SELECT part_seq FROM campaigns WHERE id = ?1) + ROW_NUMBER() OVER (), * FROM (VALUES
         -- technological
         ('lightweight', '', 'barrel', 'common', 'arksys'),
         ('hybrid', '', 'barrel', 'uncommon', 'arksys'),
@@ -104,27 +160,75 @@ static WEAPON_PARTS_SQL: &str = "
         ('placer', 'lore: in one, out the other', 'body', 'uncommon', 'west_field'),
         ('high-wall', '', 'body', 'rare', 'west_field'),
         ('<unnamed>', '', 'magazine', 'common', 'west_field'),
-        ('<unnamed>', '', 'stock', 'common', 'west_field');
+        ('<unnamed>', '', 'stock', 'common', 'west_field')
+    ) seed
+    WHERE NOT EXISTS (
+        SELECT 1 FROM weapon_parts p
+        WHERE p.campaign = ?1 AND p.name = seed.column1 AND p.type = seed.column3 AND p.company = seed.column5
+    )
 ";
 
+#[derive(Clone, Copy)]
 enum Route {
     Index,
     Css,
     Js,
+    LoginPage,
+
+    Login,
+    Logout,
+    Me,
+    UserList,
+    UserNew,
+    UserRemove,
+
+    CampaignList,
+    CampaignNew,
+    CampaignRemove,
+    CampaignClone,
 
     State,
+    Events,
     CharNew,
     CharRemove,
+    CharOwner,
+    CharRename,
+    InventoryGive,
+    InventoryDrop,
+    InventoryEdit,
+    InventoryEquip,
+    InventoryTransfer,
     CharStatIncrement,
     CharStatDecrement,
     CharStatToggle,
+    CharStatSet,
     StatNew,
     StatRemove,
+    StatRename,
+    TemplateNew,
+    TemplateRemove,
+    History,
+    Undo,
+    Redo,
+    Roll,
+    RollList,
     WeaponBuild,
     WeaponGenerate,
+    WeaponGenerateBatch,
+    WeaponWeightList,
+    WeaponWeightSet,
+    WeaponWeightRemove,
+    RarityWeightList,
+    RarityWeightSet,
+    RarityWeightRemove,
     WeaponPartInit,
     WeaponPartNew,
     WeaponPartRemove,
+    WeaponPartRename,
+    WeaponSave,
+    WeaponList,
+    WeaponTag,
+    WeaponRemove,
 
     FaviconAndroid192,
     FaviconAndroid512,
@@ -135,82 +239,480 @@ enum Route {
     WebManifest,
 }
 
-static DB: &str = "lumen.db";
+/// Who may use a route, checked in `handle` before dispatching.
+#[derive(Clone, Copy)]
+enum Access {
+    Public,
+    /// Any user that is logged in.
+    User,
+    /// The GM, or the player the `:name` character is assigned to.
+    Owner,
+    Gm,
+}
+
+impl Route {
+    fn access(self) -> Access {
+        match self {
+            Route::Index
+            | Route::Css
+            | Route::Js
+            | Route::LoginPage
+            | Route::Login
+            | Route::Logout
+            | Route::FaviconAndroid192
+            | Route::FaviconAndroid512
+            | Route::FaviconApple
+            | Route::Favicon16
+            | Route::Favicon32
+            | Route::Favicon
+            | Route::WebManifest => Access::Public,
+
+            Route::Me
+            | Route::CampaignList
+            | Route::State
+            | Route::Events
+            | Route::History
+            | Route::Roll
+            | Route::RollList
+            | Route::WeaponGenerateBatch
+            | Route::WeaponWeightList
+            | Route::RarityWeightList
+            | Route::WeaponList => Access::User,
+
+            Route::CharStatIncrement
+            | Route::CharStatDecrement
+            | Route::CharStatToggle
+            | Route::CharStatSet
+            | Route::InventoryEdit
+            | Route::InventoryEquip => Access::Owner,
+
+            Route::UserList
+            | Route::UserNew
+            | Route::UserRemove
+            | Route::CampaignNew
+            | Route::CampaignRemove
+            | Route::CampaignClone
+            | Route::CharNew
+            | Route::CharRemove
+            | Route::CharOwner
+            | Route::CharRename
+            | Route::InventoryGive
+            | Route::InventoryDrop
+            | Route::InventoryTransfer
+            | Route::StatNew
+            | Route::StatRemove
+            | Route::StatRename
+            | Route::TemplateNew
+            | Route::TemplateRemove
+            | Route::Undo
+            | Route::Redo
+            | Route::WeaponBuild
+            | Route::WeaponGenerate
+            | Route::WeaponPartInit
+            | Route::WeaponPartNew
+            | Route::WeaponPartRemove
+            | Route::WeaponPartRename
+            | Route::WeaponWeightSet
+            | Route::WeaponWeightRemove
+            | Route::RarityWeightSet
+            | Route::RarityWeightRemove
+            | Route::WeaponSave
+            | Route::WeaponTag
+            | Route::WeaponRemove => Access::Gm,
+        }
+    }
+}
+
+static USAGE: &str = "\
+usage: lumen [options] [command]
+
+commands:
+    add-gm <name>        add a gm account, the password is read from stdin
+
+options:
+    --bind <address>     address to listen on [env: LUMEN_BIND] [default: localhost]
+    --port <port>        port to listen on [env: LUMEN_PORT] [default: 8080]
+    --db <path>          path to the sqlite database [env: LUMEN_DB] [default: lumen.db]
+    --timeout <seconds>  client read/write timeout [env: LUMEN_TIMEOUT] [default: 10]
+    -h, --help           print this message
+";
+
+struct Config {
+    bind: String,
+    port: u16,
+    db: PathBuf,
+    timeout: Duration,
+    command: Command,
+}
+
+enum Command {
+    Serve,
+    AddGm(String),
+}
+
+impl Config {
+    fn parse() -> Result<Option<Self>> {
+        let mut config = Config {
+            bind: "localhost".to_string(),
+            port: 8080,
+            db: PathBuf::from("lumen.db"),
+            timeout: Duration::from_secs(10),
+            command: Command::Serve,
+        };
+
+        if let Ok(bind) = std::env::var("LUMEN_BIND") {
+            config.set("bind", &bind)?;
+        }
+        if let Ok(port) = std::env::var("LUMEN_PORT") {
+            config.set("port", &port)?;
+        }
+        if let Ok(db) = std::env::var("LUMEN_DB") {
+            config.set("db", &db)?;
+        }
+        if let Ok(timeout) = std::env::var("LUMEN_TIMEOUT") {
+            config.set("timeout", &timeout)?;
+        }
+
+        let mut positional = Vec::new();
+
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            if arg == "-h" || arg == "--help" {
+                return Ok(None);
+            }
+
+            let Some(flag) = arg.strip_prefix("--") else {
+                positional.push(arg);
+                continue;
+            };
+
+            match flag.split_once('=') {
+                Some((key, value)) => config.set(key, value)?,
+                None => {
+                    let value = args
+                        .next()
+                        .with_context(|| format!("missing value for `--{}`", flag))?;
+
+                    config.set(flag, &value)?;
+                }
+            }
+        }
+
+        let mut positional = positional.into_iter();
+        match (positional.next(), positional.next(), positional.next()) {
+            (None, _, _) => {}
+            (Some(command), Some(name), None) if command == "add-gm" => {
+                config.command = Command::AddGm(name)
+            }
+            (Some(command), _, _) => anyhow::bail!("unexpected command `{}`", command),
+        }
+
+        Ok(Some(config))
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        match key {
+            "bind" => self.bind = value.to_string(),
+            "port" => {
+                self.port = value
+                    .parse()
+                    .with_context(|| format!("invalid port `{}`", value))?
+            }
+            "db" => self.db = PathBuf::from(value),
+            "timeout" => {
+                self.timeout = value
+                    .parse()
+                    .map(Duration::from_secs)
+                    .with_context(|| format!("invalid timeout `{}`", value))?
+            }
+            _ => anyhow::bail!("unknown option `--{}`", key),
+        }
+
+        Ok(())
+    }
+}
+
+/// State shared by every request handler.
+pub struct Context {
+    pub pool: Arc<Pool>,
+    pub events: Arc<Events>,
+}
+
+static POOL_SIZE: usize = 8;
 
 #[rustfmt::skip]
 fn main() -> anyhow::Result<()> {
-    if !PathBuf::from(DB).exists() {
-        File::create(PathBuf::from(DB))?;
+    let Some(config) = Config::parse()? else {
+        print!("{}", USAGE);
+
+        return Ok(());
+    };
+
+    if !config.db.exists() {
+        File::create(&config.db)?;
     }
 
     {
-        let conn = Connection::open(DB)?;
-
-        conn.execute_batch(DATABASE_INIT_SQL)?;
-    }
-
-    let mut router: PathTree<Route> = PathTree::new();
-
-    router.insert("/", Route::Index);
-    router.insert("/assets/index.css", Route::Css);
-    router.insert("/assets/index.js", Route::Js);
-
-    router.insert("/api/state", Route::State);
-    router.insert("/api/character/new", Route::CharNew);
-    router.insert("/api/character/remove/:name", Route::CharRemove);
-    router.insert("/api/character/increment/:name/:stat", Route::CharStatIncrement);
-    router.insert("/api/character/decrement/:name/:stat", Route::CharStatDecrement);
-    router.insert("/api/character/toggle/:name/:stat", Route::CharStatToggle);
-    router.insert("/api/stat/new", Route::StatNew);
-    router.insert("/api/stat/remove/:name", Route::StatRemove);
-    router.insert("/api/weapon/build", Route::WeaponBuild);
-    router.insert("/api/weapon/generate", Route::WeaponGenerate);
-    router.insert("/api/weapon/part/init", Route::WeaponPartInit);
-    router.insert("/api/weapon/part/new", Route::WeaponPartNew);
-    router.insert("/api/weapon/part/remove/:name", Route::WeaponPartRemove);
-
-    router.insert("/android-chrome-192x192.png", Route::FaviconAndroid192);
-    router.insert("/android-chrome-512x512.png", Route::FaviconAndroid512);
-    router.insert("/apple-touch-icon.png", Route::FaviconApple);
-    router.insert("/favicon-16x16.png", Route::Favicon16);
-    router.insert("/favicon-32x32.png", Route::Favicon32);
-    router.insert("/favicon.ico", Route::Favicon);
-    router.insert("/site.webmanifest", Route::WebManifest);
+        let mut conn = Connection::open(&config.db)?;
+
+        migrations::run(&mut conn)?;
+
+        if let Command::AddGm(name) = &config.command {
+            eprint!("password for `{}`: ", name);
+
+            let mut password = String::new();
+            std::io::stdin().read_line(&mut password)?;
+            let password = password.trim_end_matches(['\r', '\n']);
+
+            anyhow::ensure!(!password.is_empty(), "the password can't be empty");
+
+            Db::add_user(&conn, name.as_str(), password, Role::Gm)?;
+            eprintln!("added gm `{}`", name);
+
+            return Ok(());
+        }
+    }
+
+    let router = router()?;
+
+    let address = (config.bind.clone(), config.port);
+    let timeout = config.timeout;
+
+    let ctx = Context {
+        pool: Arc::new(Pool::new(&config.db, POOL_SIZE)),
+        events: Arc::new(Events::new()),
+    };
 
     let mut server = Server::new(move |request| {
-        handle(&router, request)
+        handle(&router, &ctx, request)
     });
 
-    server.set_global_timeout(Duration::from_secs(10));
+    server.set_global_timeout(timeout);
 
-    server.listen(("localhost", 8080))?;
+    server.listen(address)?;
 
     Ok(())
 }
 
-fn handle(router: &PathTree<Route>, request: &mut Request) -> Response {
-    if let Some((route, path)) = router.find(request.url().path()) {
+/// Every route the server answers, by path and method.
+#[rustfmt::skip]
+fn router() -> anyhow::Result<PathTree<Vec<(Method, Route)>>> {
+    let patch = "PATCH".parse::<Method>()?;
+
+    let mut router = Router::new();
+
+    router.insert(Method::GET, "/", Route::Index);
+    router.insert(Method::GET, "/assets/index.css", Route::Css);
+    router.insert(Method::GET, "/assets/index.js", Route::Js);
+
+    router.insert(Method::GET, "/login", Route::LoginPage);
+
+    router.insert(Method::POST, "/api/login", Route::Login);
+    router.insert(Method::POST, "/api/logout", Route::Logout);
+    router.insert(Method::GET, "/api/me", Route::Me);
+    router.insert(Method::GET, "/api/users", Route::UserList);
+    router.insert(Method::POST, "/api/users", Route::UserNew);
+    router.insert(Method::DELETE, "/api/users/:name", Route::UserRemove);
+
+    router.insert(Method::GET, "/api/campaigns", Route::CampaignList);
+    router.insert(Method::POST, "/api/campaigns", Route::CampaignNew);
+    router.insert(Method::DELETE, "/api/campaigns/:campaign", Route::CampaignRemove);
+    router.insert(Method::POST, "/api/campaigns/:campaign/clone", Route::CampaignClone);
+
+    router.insert(Method::GET, "/api/campaigns/:campaign/state", Route::State);
+    router.insert(Method::GET, "/api/campaigns/:campaign/events", Route::Events);
+    router.insert(Method::POST, "/api/campaigns/:campaign/character", Route::CharNew);
+    router.insert(Method::DELETE, "/api/campaigns/:campaign/character/:name", Route::CharRemove);
+    router.insert(patch.clone(), "/api/campaigns/:campaign/character/:name/owner", Route::CharOwner);
+    router.insert(patch.clone(), "/api/campaigns/:campaign/character/:name/rename", Route::CharRename);
+    router.insert(Method::POST, "/api/campaigns/:campaign/inventory/:name", Route::InventoryGive);
+    router.insert(Method::DELETE, "/api/campaigns/:campaign/inventory/:name/:item", Route::InventoryDrop);
+    router.insert(patch.clone(), "/api/campaigns/:campaign/inventory/:name/:item/edit", Route::InventoryEdit);
+    router.insert(patch.clone(), "/api/campaigns/:campaign/inventory/:name/:item/equip", Route::InventoryEquip);
+    router.insert(patch.clone(), "/api/campaigns/:campaign/inventory/:name/:item/transfer", Route::InventoryTransfer);
+    router.insert(patch.clone(), "/api/campaigns/:campaign/character/:name/:stat/increment", Route::CharStatIncrement);
+    router.insert(patch.clone(), "/api/campaigns/:campaign/character/:name/:stat/decrement", Route::CharStatDecrement);
+    router.insert(patch.clone(), "/api/campaigns/:campaign/character/:name/:stat/toggle", Route::CharStatToggle);
+    router.insert(patch.clone(), "/api/campaigns/:campaign/character/:name/:stat/set", Route::CharStatSet);
+    router.insert(Method::POST, "/api/campaigns/:campaign/stat", Route::StatNew);
+    router.insert(Method::DELETE, "/api/campaigns/:campaign/stat/:name", Route::StatRemove);
+    router.insert(patch.clone(), "/api/campaigns/:campaign/stat/:name/rename", Route::StatRename);
+    router.insert(Method::POST, "/api/campaigns/:campaign/template", Route::TemplateNew);
+    router.insert(Method::DELETE, "/api/campaigns/:campaign/template/:name", Route::TemplateRemove);
+    router.insert(Method::GET, "/api/campaigns/:campaign/history", Route::History);
+    router.insert(Method::POST, "/api/campaigns/:campaign/undo", Route::Undo);
+    router.insert(Method::POST, "/api/campaigns/:campaign/redo", Route::Redo);
+    router.insert(Method::POST, "/api/campaigns/:campaign/roll", Route::Roll);
+    router.insert(Method::GET, "/api/campaigns/:campaign/rolls", Route::RollList);
+    router.insert(Method::POST, "/api/campaigns/:campaign/weapon/build", Route::WeaponBuild);
+    router.insert(Method::POST, "/api/campaigns/:campaign/weapon/generate", Route::WeaponGenerate);
+    router.insert(Method::GET, "/api/campaigns/:campaign/weapon/generate/batch", Route::WeaponGenerateBatch);
+    router.insert(Method::GET, "/api/campaigns/:campaign/weapon/weights", Route::WeaponWeightList);
+    router.insert(Method::POST, "/api/campaigns/:campaign/weapon/weights", Route::WeaponWeightSet);
+    router.insert(Method::DELETE, "/api/campaigns/:campaign/weapon/weights/:type/:level", Route::WeaponWeightRemove);
+    router.insert(Method::GET, "/api/campaigns/:campaign/weapon/rarities", Route::RarityWeightList);
+    router.insert(Method::POST, "/api/campaigns/:campaign/weapon/rarities", Route::RarityWeightSet);
+    router.insert(Method::DELETE, "/api/campaigns/:campaign/weapon/rarities/:rarity/:level", Route::RarityWeightRemove);
+    router.insert(Method::POST, "/api/campaigns/:campaign/weapon/part/init", Route::WeaponPartInit);
+    router.insert(Method::POST, "/api/campaigns/:campaign/weapon/part", Route::WeaponPartNew);
+    router.insert(Method::DELETE, "/api/campaigns/:campaign/weapon/part/:id", Route::WeaponPartRemove);
+    router.insert(patch.clone(), "/api/campaigns/:campaign/weapon/part/:id/rename", Route::WeaponPartRename);
+    router.insert(Method::POST, "/api/campaigns/:campaign/weapon/saved", Route::WeaponSave);
+    router.insert(Method::GET, "/api/campaigns/:campaign/weapon/saved", Route::WeaponList);
+    router.insert(patch, "/api/campaigns/:campaign/weapon/saved/:id/tags", Route::WeaponTag);
+    router.insert(Method::DELETE, "/api/campaigns/:campaign/weapon/saved/:id", Route::WeaponRemove);
+
+    router.insert(Method::GET, "/android-chrome-192x192.png", Route::FaviconAndroid192);
+    router.insert(Method::GET, "/android-chrome-512x512.png", Route::FaviconAndroid512);
+    router.insert(Method::GET, "/apple-touch-icon.png", Route::FaviconApple);
+    router.insert(Method::GET, "/favicon-16x16.png", Route::Favicon16);
+    router.insert(Method::GET, "/favicon-32x32.png", Route::Favicon32);
+    router.insert(Method::GET, "/favicon.ico", Route::Favicon);
+    router.insert(Method::GET, "/site.webmanifest", Route::WebManifest);
+
+    Ok(router.build())
+}
+
+/// Collects routes by path so that a path can be served by a handler per method.
+struct Router(Vec<(&'static str, Vec<(Method, Route)>)>);
+
+impl Router {
+    fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    fn insert(&mut self, method: Method, path: &'static str, route: Route) {
+        match self.0.iter_mut().find(|(p, _)| *p == path) {
+            Some((_, methods)) => methods.push((method, route)),
+            None => self.0.push((path, vec![(method, route)])),
+        }
+    }
+
+    fn build(self) -> PathTree<Vec<(Method, Route)>> {
+        let mut tree = PathTree::new();
+
+        for (path, methods) in self.0 {
+            tree.insert(path, methods);
+        }
+
+        tree
+    }
+}
+
+/// The id of the last server-sent event a reconnecting client has seen.
+fn last_event_id(request: &Request) -> Option<&str> {
+    let name = "last-event-id".parse::<HeaderName>().ok()?;
+
+    request.header(&name)?.to_str().ok()
+}
+
+/// The largest request body that will be read, bodies only ever carry small json objects.
+static BODY_MAX: u64 = 64 * 1024;
+
+fn handle(
+    router: &PathTree<Vec<(Method, Route)>>,
+    ctx: &Context,
+    request: &mut Request,
+) -> Response {
+    let mut body = Vec::new();
+    if let Err(err) = request.body_mut().take(BODY_MAX).read_to_end(&mut body) {
+        return ApiError::InvalidValue(format!("failed to read request body: {}", err)).response();
+    }
+
+    if let Some((methods, path)) = router.find(request.url().path()) {
+        let Some((_, route)) = methods.iter().find(|(m, _)| m == request.method()) else {
+            let allow = methods
+                .iter()
+                .map(|(m, _)| m.as_ref())
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            return ApiError::MethodNotAllowed(allow).response();
+        };
+
+        let payload = match Payload::new(&body) {
+            Ok(payload) => payload,
+            Err(err) => return err.response(),
+        };
+
         let pairs = Pairs::new(request.url().query_pairs().collect::<Vec<_>>());
         let params = Params::new(path.params());
 
+        let user = match authorize(ctx, route.access(), &params, auth::session(request)) {
+            Ok(user) => user,
+            Err(err) => return ApiError::from(err).response(),
+        };
+
         let handler = match route {
             Route::Index => handlers::index,
             Route::Css => handlers::css,
             Route::Js => handlers::js,
+            Route::LoginPage => handlers::login_page,
+
+            Route::Login => handlers::login,
+            Route::Logout => {
+                return match handlers::logout(ctx, auth::session(request)) {
+                    Ok(res) => res,
+                    Err(err) => ApiError::from(err).response(),
+                }
+            }
+            Route::Me => handlers::me,
+            Route::UserList => handlers::user_list,
+            Route::UserNew => handlers::user_new,
+            Route::UserRemove => handlers::user_remove,
+
+            Route::CampaignList => handlers::campaign_list,
+            Route::CampaignNew => handlers::campaign_new,
+            Route::CampaignRemove => handlers::campaign_remove,
+            Route::CampaignClone => handlers::campaign_clone,
 
             Route::State => handlers::state,
+            Route::Events => {
+                return match handlers::events(ctx, user.as_ref(), params, last_event_id(request)) {
+                    Ok(res) => res,
+                    Err(err) => ApiError::from(err).response(),
+                }
+            }
             Route::CharNew => handlers::char_new,
             Route::CharRemove => handlers::char_remove,
+            Route::CharOwner => handlers::char_owner,
+            Route::CharRename => handlers::char_rename,
+            Route::InventoryGive => handlers::inventory_give,
+            Route::InventoryDrop => handlers::inventory_drop,
+            Route::InventoryEdit => handlers::inventory_edit,
+            Route::InventoryEquip => handlers::inventory_equip,
+            Route::InventoryTransfer => handlers::inventory_transfer,
             Route::CharStatIncrement => handlers::char_stat_increment,
             Route::CharStatDecrement => handlers::char_stat_decrement,
             Route::CharStatToggle => handlers::char_stat_toggle,
+            Route::CharStatSet => handlers::char_stat_set,
             Route::StatNew => handlers::stat_new,
             Route::StatRemove => handlers::stat_remove,
+            Route::StatRename => handlers::stat_rename,
+            Route::TemplateNew => handlers::template_new,
+            Route::TemplateRemove => handlers::template_remove,
+            Route::History => handlers::history,
+            Route::Undo => handlers::undo,
+            Route::Redo => handlers::redo,
+            Route::Roll => handlers::roll,
+            Route::RollList => handlers::roll_list,
             Route::WeaponBuild => handlers::weapon_build,
             Route::WeaponGenerate => handlers::weapon_generate,
+            Route::WeaponGenerateBatch => handlers::weapon_generate_batch,
+            Route::WeaponWeightList => handlers::weapon_weight_list,
+            Route::WeaponWeightSet => handlers::weapon_weight_set,
+            Route::WeaponWeightRemove => handlers::weapon_weight_remove,
+            Route::RarityWeightList => handlers::rarity_weight_list,
+            Route::RarityWeightSet => handlers::rarity_weight_set,
+            Route::RarityWeightRemove => handlers::rarity_weight_remove,
             Route::WeaponPartInit => handlers::weapon_part_init,
             Route::WeaponPartNew => handlers::weapon_part_new,
             Route::WeaponPartRemove => handlers::weapon_part_remove,
+            Route::WeaponPartRename => handlers::weapon_part_rename,
+            Route::WeaponSave => handlers::weapon_save,
+            Route::WeaponList => handlers::weapon_list,
+            Route::WeaponTag => handlers::weapon_tag,
+            Route::WeaponRemove => handlers::weapon_remove,
 
             Route::FaviconAndroid192 => return Response::ok().with_body(FAVICON_ANDROID_192),
             Route::FaviconAndroid512 => return Response::ok().with_body(FAVICON_ANDROID_512),
@@ -221,16 +723,61 @@ fn handle(router: &PathTree<Route>, request: &mut Request) -> Response {
             Route::WebManifest => return Response::ok().with_body(WEB_MANIFEST),
         };
 
-        return match handler(params, pairs) {
+        return match handler(ctx, user.as_ref(), params, pairs, payload) {
             Ok(res) => res,
-            Err(err) => {
-                Response::builder(Status::INTERNAL_SERVER_ERROR).with_body(format!("{:#?}", err))
-            }
+            Err(err) => ApiError::from(err).response(),
         };
     }
 
-    Response::builder(Status::NOT_FOUND).build()
+    ApiError::NotFound(format!("no route for `{}`", request.url().path())).response()
+}
+
+/// Looks up the user behind the session token and checks they may use a route
+/// with the given access.
+fn authorize(
+    ctx: &Context,
+    access: Access,
+    params: &Params<'_>,
+    token: Option<&str>,
+) -> Result<Option<User>> {
+    if let Access::Public = access {
+        return Ok(None);
+    }
+
+    let conn = ctx.pool.get()?;
+
+    let user = match token {
+        Some(token) => Db::session(&conn, token)?,
+        None => None,
+    };
+
+    let Some(user) = user else {
+        return Err(ApiError::Unauthorized("log in to continue".to_string()).into());
+    };
+
+    match access {
+        Access::Public | Access::User => {}
+        _ if user.role == Role::Gm => {}
+        Access::Owner => {
+            let campaign = params.find("campaign")?;
+            let name = params.find("name")?;
+
+            if !Db::owns(&conn, &user, campaign, name)? {
+                return Err(ApiError::Forbidden(format!(
+                    "character `{}` is not assigned to you",
+                    name
+                ))
+                .into());
+            }
+        }
+        Access::Gm => {
+            return Err(ApiError::Forbidden("only the gm can do that".to_string()).into());
+        }
+    }
+
+    Ok(Some(user))
 }
+
 trait ResponseExt {
     fn ok() -> ResponseBuilder;
 }
@@ -245,7 +792,7 @@ trait AsConn {
     fn as_conn(&self) -> &Connection;
 }
 
-impl<'conn> AsConn for &'conn Connection {
+impl AsConn for &Connection {
     fn as_conn(&self) -> &Connection {
         self
     }
@@ -257,1159 +804,8083 @@ impl<'conn> AsConn for &'conn Transaction<'conn> {
     }
 }
 
+impl AsConn for &PooledConnection<'_> {
+    fn as_conn(&self) -> &Connection {
+        self
+    }
+}
+
 struct Db;
 
 impl Db {
-    fn state<C: AsConn>(conn: C, weapon: Option<Weapon>) -> Result<State> {
+    fn users<C: AsConn>(conn: C) -> Result<Vec<User>> {
         let conn = conn.as_conn();
 
-        Ok(State {
-            stats: Self::template(conn)?,
-            characters: Self::characters(conn)?,
-            parts: Self::parts(conn)?,
-            weapon: weapon.map(Weapon::display),
-        })
+        let mut users_stmt = conn.prepare_cached("SELECT id, name, role FROM users ORDER BY id")?;
+
+        let users = users_stmt
+            .query_map([], |row| {
+                Ok(User {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    role: row.get(2)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>();
+
+        users.context("failed to collect users")
     }
 
-    fn add_character<C: AsConn, A: AsRef<str>>(conn: C, name: A) -> Result<()> {
+    fn add_user<C: AsConn, A: AsRef<str>>(conn: C, name: A, password: A, role: Role) -> Result<()> {
         let conn = conn.as_conn();
 
-        conn.execute("INSERT INTO characters VALUES (?)", [name.as_ref()])?;
+        let hash = auth::hash(password.as_ref())?;
 
-        let mut stat_stmt = conn.prepare("INSERT INTO stats VALUES (?, ?, ?)")?;
-
-        let mut template_stmt = conn.prepare("SELECT key FROM template")?;
-        let mut template_rows = template_stmt.query(params![])?;
-        while let Some(template_row) = template_rows.next()? {
-            stat_stmt.execute(params![
-                name.as_ref(),
-                &template_row.get::<_, String>(0)?,
-                0
-            ])?;
-        }
+        conn.execute(
+            "INSERT INTO users (name, password, role) VALUES (?, ?, ?)",
+            params![name.as_ref(), hash, role],
+        )
+        .or_conflict(|| format!("user `{}` already exists", name.as_ref()))?;
 
         Ok(())
     }
 
-    fn remove_character<C: AsConn, A: AsRef<str>>(conn: C, name: A) -> Result<()> {
+    fn remove_user<C: AsConn, A: AsRef<str>>(conn: C, name: A) -> Result<()> {
         let conn = conn.as_conn();
 
-        conn.execute(
-            "DELETE FROM characters WHERE name = ?",
-            params![name.as_ref()],
-        )?;
-        conn.execute(
-            "DELETE FROM stats WHERE character = ?",
-            params![name.as_ref()],
-        )?;
+        let changed = conn.execute("DELETE FROM users WHERE name = ?", [name.as_ref()])?;
+
+        if changed == 0 {
+            return Err(ApiError::NotFound(format!("user `{}` not found", name.as_ref())).into());
+        }
 
         Ok(())
     }
 
-    fn add_stat<C: AsConn, A: AsRef<str>>(conn: C, name: A, typ: A) -> Result<()> {
+    /// Checks the password and starts a session, returns the session token.
+    fn login<C: AsConn, A: AsRef<str>>(conn: C, name: A, password: A) -> Result<(String, User)> {
         let conn = conn.as_conn();
 
+        let user = conn
+            .prepare_cached("SELECT id, name, role, password FROM users WHERE name = ?")?
+            .query_row([name.as_ref()], |row| {
+                Ok((
+                    User {
+                        id: row.get(0)?,
+                        name: row.get(1)?,
+                        role: row.get(2)?,
+                    },
+                    row.get::<_, String>(3)?,
+                ))
+            })
+            .optional()?;
+
+        let user = match user {
+            Some((user, hash)) if auth::verify(password.as_ref(), &hash) => user,
+            _ => return Err(ApiError::Unauthorized("invalid name or password".to_string()).into()),
+        };
+
+        let token = auth::token();
+
+        conn.execute("DELETE FROM sessions WHERE expires <= unixepoch()", [])?;
         conn.execute(
-            "INSERT INTO template VALUES (?, ?)",
-            [name.as_ref(), typ.as_ref()],
+            "INSERT INTO sessions (token, user, expires) VALUES (?, ?, unixepoch() + ?)",
+            params![token, user.id, auth::SESSION_AGE],
         )?;
 
-        let mut stat_stmt = conn.prepare("INSERT INTO stats VALUES (?, ?, ?)")?;
+        Ok((token, user))
+    }
 
-        let mut character_stmt = conn.prepare("SELECT name FROM characters")?;
-        let mut character_rows = character_stmt.query(params![])?;
-        while let Some(character_row) = character_rows.next()? {
-            stat_stmt.execute(params![
-                &character_row.get::<_, String>(0)?,
-                name.as_ref(),
-                0
-            ])?;
-        }
+    fn logout<C: AsConn, A: AsRef<str>>(conn: C, token: A) -> Result<()> {
+        let conn = conn.as_conn();
+
+        conn.execute("DELETE FROM sessions WHERE token = ?", [token.as_ref()])?;
 
         Ok(())
     }
 
-    fn remove_stat<C: AsConn, A: AsRef<str>>(conn: C, name: A) -> Result<()> {
+    /// The user behind an unexpired session token.
+    fn session<C: AsConn, A: AsRef<str>>(conn: C, token: A) -> Result<Option<User>> {
         let conn = conn.as_conn();
 
-        conn.execute("DELETE FROM template WHERE key = ?", params![name.as_ref()])?;
-        conn.execute("DELETE FROM stats WHERE key = ?", params![name.as_ref()])?;
+        let user = conn
+            .prepare_cached("SELECT u.id, u.name, u.role FROM sessions s JOIN users u ON u.id = s.user WHERE s.token = ? AND s.expires > unixepoch()")?
+            .query_row([token.as_ref()], |row| {
+                Ok(User {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    role: row.get(2)?,
+                })
+            })
+            .optional()?;
 
-        Ok(())
+        Ok(user)
     }
 
-    fn increment_stat<C: AsConn, A: AsRef<str>>(conn: C, name: A, stat: A) -> Result<()> {
+    /// Whether `user` is the player the character `name` is assigned to.
+    fn owns<C: AsConn, A: AsRef<str>>(conn: C, user: &User, campaign: A, name: A) -> Result<bool> {
         let conn = conn.as_conn();
 
-        conn.execute(
-            "UPDATE stats SET value = value + 1 WHERE character = ? AND key = ?",
-            params![name.as_ref(), stat.as_ref()],
-        )?;
+        let owned = conn
+            .prepare_cached("SELECT EXISTS (SELECT 1 FROM characters c JOIN campaigns p ON p.id = c.campaign WHERE p.name = ? AND c.name = ? AND c.owner = ?)")?
+            .query_row(params![campaign.as_ref(), name.as_ref(), user.id], |row| row.get(0))?;
 
-        Ok(())
+        Ok(owned)
     }
 
-    fn decrement_stat<C: AsConn, A: AsRef<str>>(conn: C, name: A, stat: A) -> Result<()> {
+    /// Assigns the character `name` to the user `owner`, or to nobody.
+    fn set_owner<C: AsConn, A: AsRef<str>>(
+        conn: C,
+        campaign: i64,
+        name: A,
+        owner: Option<A>,
+    ) -> Result<()> {
         let conn = conn.as_conn();
 
-        conn.execute(
-            "UPDATE stats SET value = value - 1 WHERE character = ? AND key = ?",
-            params![name.as_ref(), stat.as_ref()],
+        let owner = match owner {
+            Some(owner) => Some(
+                conn.prepare_cached("SELECT id FROM users WHERE name = ?")?
+                    .query_row([owner.as_ref()], |row| row.get::<_, i64>(0))
+                    .optional()?
+                    .ok_or_else(|| {
+                        ApiError::NotFound(format!("user `{}` not found", owner.as_ref()))
+                    })?,
+            ),
+            None => None,
+        };
+
+        let changed = conn.execute(
+            "UPDATE characters SET owner = ? WHERE campaign = ? AND name = ?",
+            params![owner, campaign, name.as_ref()],
         )?;
 
-        Ok(())
+        if changed == 0 {
+            return Err(
+                ApiError::NotFound(format!("character `{}` not found", name.as_ref())).into(),
+            );
+        }
+
+        Self::record(conn, campaign, Entity::Character, name, false)
     }
 
-    fn toggle_stat<C: AsConn, A: AsRef<str>>(conn: C, name: A, stat: A) -> Result<()> {
+    fn campaigns<C: AsConn>(conn: C) -> Result<Vec<Campaign>> {
         let conn = conn.as_conn();
 
-        conn.execute("UPDATE stats SET value = CASE value WHEN 0 THEN 1 ELSE 0 END WHERE character = ? AND key = ?", params![name.as_ref(), stat.as_ref()])?;
+        let mut campaigns_stmt = conn.prepare_cached("SELECT name FROM campaigns ORDER BY id")?;
 
-        Ok(())
+        let campaigns = campaigns_stmt
+            .query_map([], |row| Ok(Campaign { name: row.get(0)? }))?
+            .collect::<Result<Vec<_>, _>>();
+
+        campaigns.context("failed to collect campaigns")
     }
 
-    fn add_weapon_part<C: AsConn, A: AsRef<str>>(
-        conn: C,
-        name: A,
-        details: A,
-        part: PartType,
-        rarity: Rarity,
-        company: Company,
-    ) -> Result<()> {
+    /// Looks up the id everything in the campaign `name` is scoped by.
+    fn campaign<C: AsConn, A: AsRef<str>>(conn: C, name: A) -> Result<i64> {
         let conn = conn.as_conn();
 
-        conn.execute(
-            "INSERT INTO weapon_parts VALUES (?, ?, ?, ?, ?)",
-            params![name.as_ref(), details.as_ref(), part, rarity, company],
-        )?;
+        let id = conn
+            .prepare_cached("SELECT id FROM campaigns WHERE name = ?")?
+            .query_row([name.as_ref()], |row| row.get(0))
+            .optional()?;
 
-        Ok(())
+        match id {
+            Some(id) => Ok(id),
+            None => {
+                Err(ApiError::NotFound(format!("campaign `{}` not found", name.as_ref())).into())
+            }
+        }
     }
 
-    fn remove_weapon_part<C: AsConn, A: AsRef<str>>(conn: C, name: A) -> Result<()> {
+    fn add_campaign<C: AsConn, A: AsRef<str>>(conn: C, name: A) -> Result<i64> {
         let conn = conn.as_conn();
 
+        conn.execute("INSERT INTO campaigns (name) VALUES (?)", [name.as_ref()])
+            .or_conflict(|| format!("campaign `{}` already exists", name.as_ref()))?;
+
+        let id = conn.last_insert_rowid();
+
         conn.execute(
-            "DELETE FROM weapon_parts WHERE name = ?",
-            params![name.as_ref()],
+            "INSERT INTO templates (campaign, name) VALUES (?, ?)",
+            params![id, BASE_TEMPLATE],
         )?;
 
-        Ok(())
+        Ok(id)
     }
 
-    fn template<C: AsConn>(conn: C) -> Result<Vec<Stat>> {
+    fn remove_campaign<C: AsConn, A: AsRef<str>>(conn: C, name: A) -> Result<()> {
         let conn = conn.as_conn();
 
-        let mut template_stmt = conn.prepare("SELECT key, type FROM template")?;
+        let changed = conn.execute("DELETE FROM campaigns WHERE name = ?", [name.as_ref()])?;
 
-        let template = template_stmt
-            .query_map([], |row| {
-                Ok(Stat {
-                    name: row.get(0)?,
-                    typ: row.get(1)?,
-                })
-            })?
-            .collect::<Result<Vec<_>, _>>();
+        if changed == 0 {
+            return Err(
+                ApiError::NotFound(format!("campaign `{}` not found", name.as_ref())).into(),
+            );
+        }
 
-        template.context("failed to collect template stats")
+        Ok(())
     }
 
-    fn characters<C: AsConn>(conn: C) -> Result<Vec<Character>> {
+    /// Creates the campaign `name` with a copy of the templates, curves and
+    /// part catalog of `campaign`, parts keep their ids so weapon ids carry over.
+    fn clone_campaign<C: AsConn, A: AsRef<str>>(conn: C, campaign: i64, name: A) -> Result<()> {
         let conn = conn.as_conn();
 
-        let mut characters_stmt = conn.prepare("SELECT name FROM characters")?;
-        let mut stats_stmt = conn.prepare("SELECT s.key, (SELECT t.type FROM template t WHERE t.key = s.key) as type, s.value FROM stats s WHERE s.character = ?")?;
+        let clone = Self::add_campaign(conn, name)?;
+
+        for sql in [
+            "UPDATE campaigns SET part_seq = (SELECT part_seq FROM campaigns WHERE id = ?1) WHERE id = ?2",
+            "INSERT INTO templates (campaign, name) SELECT ?2, name FROM templates WHERE campaign = ?1 AND name NOT IN (SELECT name FROM templates WHERE campaign = ?2) ORDER BY id",
+            "UPDATE templates AS t SET parent = (SELECT c.id FROM templates o JOIN templates p ON p.id = o.parent JOIN templates c ON c.campaign = ?2 AND c.name = p.name WHERE o.campaign = ?1 AND o.name = t.name) WHERE t.campaign = ?2",
+            "INSERT INTO template (campaign, key, type, options, min, max, formula, template, default_value, label, description, position, category, visibility) SELECT ?2, t.key, t.type, t.options, t.min, t.max, t.formula, c.id, t.default_value, t.label, t.description, t.position, t.category, t.visibility FROM template t JOIN templates o ON o.id = t.template JOIN templates c ON c.campaign = ?2 AND c.name = o.name WHERE t.campaign = ?1 ORDER BY t.id",
+            "INSERT INTO weapon_curves (campaign, name, type, a, b, c, d) SELECT ?2, name, type, a, b, c, d FROM weapon_curves WHERE campaign = ?1 ORDER BY id",
+            "INSERT INTO weapon_weights (campaign, type, level, weight) SELECT ?2, type, level, weight FROM weapon_weights WHERE campaign = ?1 ORDER BY id",
+            "INSERT INTO rarity_weights (campaign, rarity, level, weight) SELECT ?2, rarity, level, weight FROM rarity_weights WHERE campaign = ?1 ORDER BY id",
+            "INSERT INTO weapon_parts (campaign, id, name, details, type, rarity, company) SELECT ?2, id, name, details, type, rarity, company FROM weapon_parts WHERE campaign = ?1",
+            "INSERT INTO weapon_parts_legacy (campaign, position, part) SELECT ?2, position, part FROM weapon_parts_legacy WHERE campaign = ?1",
+        ] {
+            conn.execute(sql, [campaign, clone])?;
+        }
 
-        let characters = characters_stmt
-            .query_map([], |row| {
-                let name = row.get(0)?;
-
-                let stats = stats_stmt
-                    .query_map([&name], |row| {
-                        Ok(CharacterStat {
-                            name: row.get(0)?,
-                            typ: row.get(1)?,
-                            value: row.get(2)?,
-                        })
-                    })?
-                    .collect::<Result<Vec<_>, _>>()?;
+        conn.execute(
+            "INSERT INTO changes (campaign, entity, key, removed) SELECT ?1, ?2, name, 0 FROM templates WHERE campaign = ?1 ORDER BY id",
+            params![clone, Entity::Template],
+        )?;
 
-                Ok(Character { name, stats })
-            })?
-            .collect::<Result<Vec<_>, _>>();
+        conn.execute(
+            "INSERT INTO changes (campaign, entity, key, removed) SELECT ?1, ?2, key, 0 FROM template WHERE campaign = ?1 ORDER BY id",
+            params![clone, Entity::Stat],
+        )?;
+
+        conn.execute(
+            "INSERT INTO changes (campaign, entity, key, removed) SELECT ?1, ?2, id, 0 FROM weapon_parts WHERE campaign = ?1 ORDER BY id",
+            params![clone, Entity::Part],
+        )?;
 
-        characters.context("failed to collect characters")
+        Ok(())
     }
 
-    fn curves<C: AsConn>(conn: C) -> Result<Vec<WeaponCurve>> {
+    /// Everything in `campaign` with `weapon` or else the weapon that was shown
+    /// last, stats only the gm may see are left out unless `gm` is set.
+    fn state<C: AsConn>(conn: C, campaign: i64, weapon: Option<Weapon>, gm: bool) -> Result<State> {
         let conn = conn.as_conn();
 
-        let mut parts_stmt = conn.prepare("SELECT name, type, a, b, c, d FROM weapon_curves")?;
+        let stats = Self::template(conn, campaign)?;
+        let hidden = Self::hidden(&stats, gm);
+        let parts = Self::parts(conn, campaign)?;
 
-        let parts = parts_stmt
-            .query_map([], |row| {
-                Ok(WeaponCurve {
-                    typ: row.get(0)?,
-                    curve: match row.get::<_, String>(1)?.as_str() {
-                        "cubic" => Curve::cubic(row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?),
-                        "linear" => Curve::linear(row.get(2)?, row.get(3)?),
-                        "quadratic" => Curve::quadratic(row.get(2)?, row.get(3)?, row.get(4)?),
-                        _ => panic!(),
-                    },
-                })
-            })?
-            .collect::<Result<Vec<_>, _>>();
+        let weapon = match weapon {
+            Some(weapon) => Some(weapon.display()),
+            None => {
+                let shown = conn
+                    .prepare_cached("SELECT key FROM changes WHERE campaign = ? AND entity = ? ORDER BY revision DESC LIMIT 1")?
+                    .query_row(params![campaign, Entity::Weapon], |row| row.get::<_, String>(0))
+                    .optional()?;
+
+                Self::shown_weapon(conn, campaign, &parts, shown)?
+            }
+        };
 
-        parts.context("failed to collect weapon curves")
+        Ok(State {
+            templates: Self::templates(conn, campaign)?,
+            stats: stats
+                .into_iter()
+                .filter(|stat| !hidden.contains(&stat.name))
+                .collect(),
+            characters: Self::characters(conn, campaign)?
+                .into_iter()
+                .map(|character| Self::hide(character, &hidden))
+                .collect(),
+            parts,
+            weapon,
+            revision: Self::revision(conn, campaign)?,
+        })
     }
 
-    fn parts<C: AsConn>(conn: C) -> Result<Vec<Part>> {
+    fn delta<C: AsConn>(conn: C, campaign: i64, since: i64, gm: bool) -> Result<Delta> {
         let conn = conn.as_conn();
 
-        let mut parts_stmt =
-            conn.prepare("SELECT name, details, type, rarity, company FROM weapon_parts")?;
+        let mut changes_stmt = conn.prepare_cached("SELECT entity, key, removed FROM changes WHERE revision IN (SELECT MAX(revision) FROM changes WHERE campaign = ? AND revision > ? GROUP BY entity, key) ORDER BY revision")?;
 
-        let parts = parts_stmt
-            .query_map([], |row| {
-                Ok(Part {
-                    name: row.get(0)?,
-                    details: row.get(1)?,
-                    typ: row.get(2)?,
-                    rarity: row.get(3)?,
-                    company: row.get(4)?,
-                })
+        let changes = changes_stmt
+            .query_map([campaign, since], |row| {
+                Ok((
+                    row.get::<_, Entity>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, bool>(2)?,
+                ))
             })?
-            .collect::<Result<Vec<_>, _>>();
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut templates = HashSet::new();
+        let mut stats = HashSet::new();
+        let mut characters = HashSet::new();
+        let mut parts = HashSet::new();
+        let mut weapon = None;
+        let mut weights = false;
+        let mut removed = Removed::default();
+
+        for (entity, key, gone) in changes {
+            match (entity, gone) {
+                (Entity::Template, false) => {
+                    templates.insert(key);
+                }
+                (Entity::Template, true) => removed.templates.push(key),
+                (Entity::Stat, false) => {
+                    stats.insert(key);
+                }
+                (Entity::Stat, true) => removed.stats.push(key),
+                (Entity::Character, false) => {
+                    characters.insert(key);
+                }
+                (Entity::Character, true) => removed.characters.push(key),
+                (Entity::Part, false) => {
+                    parts.insert(key.parse::<u8>()?);
+                }
+                (Entity::Part, true) => removed.parts.push(key.parse()?),
+                (Entity::Weapon, _) => weapon = Some(key),
+                (Entity::Weight, _) => weights = true,
+            }
+        }
 
-        parts.context("failed to collect weapon parts")
+        // characters hold a value for the stats of their template, so any
+        // template change may touch all of them
+        let template_changed = !stats.is_empty() || !removed.stats.is_empty();
+        // inventory weapons are shown with the parts they're built from
+        let parts_changed = !parts.is_empty() || !removed.parts.is_empty();
+
+        let all_parts = Self::parts(conn, campaign)?;
+
+        let all_stats = Self::template(conn, campaign)?;
+        let hidden = Self::hidden_names(conn, campaign, &all_stats, gm)?;
+
+        removed.stats.retain(|stat| !hidden.contains(stat));
+
+        let weapon = Self::shown_weapon(conn, campaign, &all_parts, weapon)?;
+
+        Ok(Delta {
+            templates: Self::templates(conn, campaign)?
+                .into_iter()
+                .filter(|template| templates.contains(&template.name))
+                .collect(),
+            stats: all_stats
+                .into_iter()
+                .filter(|stat| stats.contains(&stat.name) && !hidden.contains(&stat.name))
+                .collect(),
+            characters: Self::characters(conn, campaign)?
+                .into_iter()
+                .filter(|character| {
+                    template_changed
+                        || characters.contains(&character.name)
+                        || (parts_changed && !character.inventory.is_empty())
+                })
+                .map(|character| Self::hide(character, &hidden))
+                .collect(),
+            parts: all_parts
+                .into_iter()
+                .filter(|part| parts.contains(&part.id))
+                .collect(),
+            weapon,
+            weights,
+            removed,
+            revision: Self::revision(conn, campaign)?,
+        })
     }
-}
 
-mod handlers {
-    use anyhow::{Context as _, Result};
-    use oxhttp::model::{Response, Status};
-    use rusqlite::Connection;
+    /// The weapon with the recorded id `shown`, a weapon whose parts have since
+    /// been removed can't be shown anymore.
+    fn shown_weapon(
+        conn: &Connection,
+        campaign: i64,
+        parts: &[Part],
+        shown: Option<String>,
+    ) -> Result<Option<WeaponDisplay>> {
+        let Some(Ok(id)) = shown.map(|id| Id::try_from(id.as_str())) else {
+            return Ok(None);
+        };
 
-    use crate::{
-        models::{Id, Pairs, Params, PartType, Rarity, Weapon, Company},
-        Db, CSS, DB, INDEX, JS, WEAPON_PARTS_SQL,
-    };
+        Ok(Weapon::from_id(parts, &Self::curves(conn, campaign)?, id)
+            .ok()
+            .map(Weapon::display))
+    }
 
-    fn json<S: serde::Serialize>(s: S) -> Result<Response> {
-        let body = serde_json::to_string(&s)?;
+    /// Names of the stats that are left out of what is sent to players.
+    fn hidden(stats: &[Stat], gm: bool) -> HashSet<String> {
+        stats
+            .iter()
+            .filter(|stat| !gm && stat.meta.visibility == Visibility::Gm)
+            .map(|stat| stat.name.clone())
+            .collect()
+    }
 
-        Ok(Response::builder(Status::OK)
-            .with_header("Content-Type", "application/json")?
-            .with_body(body))
-    }
+    /// Like [`Db::hidden`], along with the names gm-only stats had before
+    /// they were removed or renamed, names of stats players see now are kept.
+    fn hidden_names<C: AsConn>(
+        conn: C,
+        campaign: i64,
+        stats: &[Stat],
+        gm: bool,
+    ) -> Result<HashSet<String>> {
+        let conn = conn.as_conn();
 
-    pub fn index(_params: Params<'_>, _pairs: Pairs<'_>) -> Result<Response> {
-        Ok(Response::builder(Status::OK)
-            .with_header("Content-Type", "text/html; charset=utf-8")?
-            .with_body(INDEX))
-    }
+        let mut hidden = Self::hidden(stats, gm);
+        if gm {
+            return Ok(hidden);
+        }
 
-    pub fn css(_params: Params<'_>, _pairs: Pairs<'_>) -> Result<Response> {
-        Ok(Response::builder(Status::OK)
-            .with_header("Content-Type", "text/css; charset=utf-8")?
-            .with_body(CSS))
+        let entries = conn
+            .prepare_cached("SELECT action, stat, before FROM history WHERE campaign = ? AND action IN (?, ?) ORDER BY id DESC")?
+            .query_map(
+                params![campaign, Action::RemoveStat, Action::RenameStat],
+                |row| {
+                    Ok((
+                        row.get::<_, Action>(0)?,
+                        row.get::<_, Option<String>>(1)?,
+                        row.get::<_, String>(2)?,
+                    ))
+                },
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // newest first, so a rename is seen after the stat it led to is known
+        // to be hidden
+        for (action, stat, before) in entries {
+            let before = serde_json::from_str::<serde_json::Value>(&before)?;
+
+            let name = match (action, stat) {
+                (Action::RemoveStat, Some(stat)) if before["visibility"] == "gm" => stat,
+                (Action::RenameStat, Some(stat)) if hidden.contains(&stat) => {
+                    match before.as_str() {
+                        Some(name) => name.to_string(),
+                        None => continue,
+                    }
+                }
+                _ => continue,
+            };
+
+            if !stats.iter().any(|stat| stat.name == name) {
+                hidden.insert(name);
+            }
+        }
+
+        Ok(hidden)
     }
 
-    pub fn js(_params: Params<'_>, _pairs: Pairs<'_>) -> Result<Response> {
-        Ok(Response::builder(Status::OK)
-            .with_header("Content-Type", "text/javascript; charset=utf-8")?
-            .with_body(JS))
+    fn hide(mut character: Character, hidden: &HashSet<String>) -> Character {
+        character.stats.retain(|stat| !hidden.contains(&stat.name));
+        character
     }
 
-    pub fn state(_: Params<'_>, _pairs: Pairs<'_>) -> Result<Response> {
-        let conn = Connection::open(DB)?;
+    fn revision<C: AsConn>(conn: C, campaign: i64) -> Result<i64> {
+        let conn = conn.as_conn();
 
-        json(Db::state(&conn, None)?)
-    }
+        let revision = conn
+            .prepare_cached("SELECT COALESCE(MAX(revision), 0) FROM changes WHERE campaign = ?")?
+            .query_row([campaign], |row| row.get(0))?;
 
-    pub fn char_new(_: Params<'_>, pairs: Pairs<'_>) -> Result<Response> {
-        let mut conn = Connection::open(DB)?;
-        let trans = conn.transaction()?;
+        Ok(revision)
+    }
 
-        let value = pairs.find("name")?;
-        Db::add_character(&trans, value)?;
+    fn record<C: AsConn, A: AsRef<str>>(
+        conn: C,
+        campaign: i64,
+        entity: Entity,
+        key: A,
+        removed: bool,
+    ) -> Result<()> {
+        let conn = conn.as_conn();
 
-        trans.commit()?;
+        conn.prepare_cached(
+            "INSERT INTO changes (campaign, entity, key, removed) VALUES (?, ?, ?, ?)",
+        )?
+        .execute(params![campaign, entity, key.as_ref(), removed])?;
 
-        json(Db::state(&conn, None)?)
+        Ok(())
     }
 
-    pub fn char_remove(params: Params<'_>, _: Pairs<'_>) -> Result<Response> {
-        let mut conn = Connection::open(DB)?;
-        let trans = conn.transaction()?;
+    fn add_character<C: AsConn, A: AsRef<str>>(
+        conn: C,
+        campaign: i64,
+        name: A,
+        template: Option<&str>,
+        actor: Option<&str>,
+    ) -> Result<()> {
+        let conn = conn.as_conn();
 
-        let name = params.find("name")?;
-        Db::remove_character(&trans, name)?;
+        let character = CharacterSnapshot {
+            template: Some(template.unwrap_or(BASE_TEMPLATE).to_string()),
+            ..Default::default()
+        };
+        Self::insert_character(conn, campaign, name.as_ref(), &character)?;
+
+        Self::log(
+            conn,
+            campaign,
+            actor,
+            Change {
+                action: Action::AddCharacter,
+                character: Some(name.as_ref()),
+                stat: None,
+                before: serde_json::Value::Null,
+                after: serde_json::to_value(character)?,
+            },
+        )
+    }
 
-        trans.commit()?;
+    fn remove_character<C: AsConn, A: AsRef<str>>(
+        conn: C,
+        campaign: i64,
+        name: A,
+        actor: Option<&str>,
+    ) -> Result<()> {
+        let conn = conn.as_conn();
 
-        json(Db::state(&conn, None)?)
+        let character = Self::delete_character(conn, campaign, name.as_ref())?;
+
+        Self::log(
+            conn,
+            campaign,
+            actor,
+            Change {
+                action: Action::RemoveCharacter,
+                character: Some(name.as_ref()),
+                stat: None,
+                before: serde_json::to_value(character)?,
+                after: serde_json::Value::Null,
+            },
+        )
     }
 
-    pub fn char_stat_increment(params: Params<'_>, _: Pairs<'_>) -> Result<Response> {
-        let mut conn = Connection::open(DB)?;
-        let trans = conn.transaction()?;
+    /// Creates the character `name` with the owner, template and stat values
+    /// of `character`, stats it has no value for start at their default.
+    fn insert_character<C: AsConn>(
+        conn: C,
+        campaign: i64,
+        name: &str,
+        character: &CharacterSnapshot,
+    ) -> Result<()> {
+        let conn = conn.as_conn();
 
-        let name = params.find("name")?;
-        let stat = params.find("stat")?;
-        Db::increment_stat(&trans, name, stat)?;
+        let template = character.template.as_deref().unwrap_or(BASE_TEMPLATE);
 
-        trans.commit()?;
+        conn.execute(
+            "INSERT INTO characters (campaign, name, owner, template) VALUES (?, ?, (SELECT id FROM users WHERE name = ?), ?)",
+            params![campaign, name, character.owner, Self::template_id(conn, campaign, template)?],
+        )
+        .or_conflict(|| format!("character `{}` already exists", name))?;
 
-        json(Db::state(&conn, None)?)
-    }
+        let id = conn.last_insert_rowid();
 
-    pub fn char_stat_decrement(params: Params<'_>, _: Pairs<'_>) -> Result<Response> {
-        let mut conn = Connection::open(DB)?;
-        let trans = conn.transaction()?;
+        let mut value_stmt = conn.prepare_cached("INSERT INTO stats (character, stat, value, text) VALUES (?1, (SELECT id FROM template WHERE campaign = ?2 AND key = ?3), ?4, ?5)")?;
 
-        let name = params.find("name")?;
-        let stat = params.find("stat")?;
-        Db::decrement_stat(&trans, name, stat)?;
+        for stat in Self::inherited(conn, campaign, template)? {
+            // values that no longer fit the stat are dropped
+            let value = character
+                .stats
+                .get(&stat.name)
+                .and_then(|value| stat.typ.check_value(value.clone()).ok())
+                .unwrap_or_else(|| stat.meta.default_value(&stat.typ));
 
-        trans.commit()?;
+            value_stmt.execute(params![
+                id,
+                campaign,
+                stat.name,
+                value.number(),
+                value.text()
+            ])?;
+        }
+
+        let mut item_stmt = conn.prepare_cached("INSERT INTO inventory (character, weapon, nickname, equipped, notes) VALUES (?, ?, ?, ?, ?)")?;
+
+        for item in &character.inventory {
+            item_stmt.execute(params![
+                id,
+                item.weapon,
+                item.nickname,
+                item.equipped,
+                item.notes
+            ])?;
+        }
 
-        json(Db::state(&conn, None)?)
+        Self::record(conn, campaign, Entity::Character, name, false)
     }
 
-    pub fn char_stat_toggle(params: Params<'_>, _: Pairs<'_>) -> Result<Response> {
-        let mut conn = Connection::open(DB)?;
-        let trans = conn.transaction()?;
+    /// Deletes the character `name` and returns what it held.
+    fn delete_character<C: AsConn>(
+        conn: C,
+        campaign: i64,
+        name: &str,
+    ) -> Result<CharacterSnapshot> {
+        let conn = conn.as_conn();
+
+        let (owner, template) = conn
+            .prepare_cached("SELECT u.name, p.name FROM characters c LEFT JOIN users u ON u.id = c.owner JOIN templates p ON p.id = c.template WHERE c.campaign = ? AND c.name = ?")?
+            .query_row(params![campaign, name], |row| Ok((row.get(0)?, row.get(1)?)))
+            .optional()?
+            .ok_or_else(|| ApiError::NotFound(format!("character `{}` not found", name)))?;
+
+        let stats = conn
+            .prepare_cached("SELECT t.key, COALESCE(s.text, s.value) FROM stats s JOIN characters c ON c.id = s.character JOIN template t ON t.id = s.stat WHERE c.campaign = ? AND c.name = ?")?
+            .query_map(params![campaign, name], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_, _>>()?;
+
+        let inventory = conn
+            .prepare_cached("SELECT i.weapon, i.nickname, i.equipped, i.notes FROM inventory i JOIN characters c ON c.id = i.character WHERE c.campaign = ? AND c.name = ? ORDER BY i.id")?
+            .query_map(params![campaign, name], |row| {
+                Ok(ItemSnapshot {
+                    weapon: row.get(0)?,
+                    nickname: row.get(1)?,
+                    equipped: row.get(2)?,
+                    notes: row.get(3)?,
+                })
+            })?
+            .collect::<Result<_, _>>()?;
 
-        let name = params.find("name")?;
-        let stat = params.find("stat")?;
-        Db::toggle_stat(&trans, name, stat)?;
+        conn.execute(
+            "DELETE FROM characters WHERE campaign = ? AND name = ?",
+            params![campaign, name],
+        )?;
 
-        trans.commit()?;
+        Self::record(conn, campaign, Entity::Character, name, true)?;
 
-        json(Db::state(&conn, None)?)
+        Ok(CharacterSnapshot {
+            owner,
+            template,
+            stats,
+            inventory,
+        })
     }
 
-    pub fn stat_new(_: Params<'_>, pairs: Pairs<'_>) -> Result<Response> {
-        let mut conn = Connection::open(DB)?;
-        let trans = conn.transaction()?;
+    fn rename_character<C: AsConn>(
+        conn: C,
+        campaign: i64,
+        name: &str,
+        to: &str,
+        actor: Option<&str>,
+    ) -> Result<()> {
+        let conn = conn.as_conn();
 
-        let name = pairs.find("name")?;
-        let typ = pairs.find("type")?;
-        Db::add_stat(&trans, name, typ)?;
+        Self::move_character(conn, campaign, name, to)?;
+
+        Self::log(
+            conn,
+            campaign,
+            actor,
+            Change {
+                action: Action::RenameCharacter,
+                character: Some(to),
+                stat: None,
+                before: name.into(),
+                after: to.into(),
+            },
+        )
+    }
 
-        trans.commit()?;
+    /// Renames the character `name` to `to` along with the history entries and
+    /// rolls that refer to it, stat values are kept by character id.
+    fn move_character<C: AsConn>(conn: C, campaign: i64, name: &str, to: &str) -> Result<()> {
+        let conn = conn.as_conn();
 
-        json(Db::state(&conn, None)?)
-    }
+        let changed = conn
+            .execute(
+                "UPDATE characters SET name = ?3 WHERE campaign = ?1 AND name = ?2",
+                params![campaign, name, to],
+            )
+            .or_conflict(|| format!("character `{}` already exists", to))?;
 
-    pub fn stat_remove(params: Params<'_>, _: Pairs<'_>) -> Result<Response> {
-        let mut conn = Connection::open(DB)?;
-        let trans = conn.transaction()?;
+        if changed == 0 {
+            return Err(ApiError::NotFound(format!("character `{}` not found", name)).into());
+        }
 
-        let name = params.find("name")?;
-        Db::remove_stat(&trans, name)?;
+        for sql in [
+            "UPDATE history SET character = ?3 WHERE campaign = ?1 AND character = ?2",
+            "UPDATE rolls SET character = ?3 WHERE campaign = ?1 AND character = ?2",
+        ] {
+            conn.execute(sql, params![campaign, name, to])?;
+        }
 
-        trans.commit()?;
+        // removed stats keep their values by character name
+        Self::rename_snapshot_key(
+            conn,
+            campaign,
+            [Action::AddStat, Action::RemoveStat],
+            "values",
+            name,
+            to,
+        )?;
 
-        json(Db::state(&conn, None)?)
+        Self::record(conn, campaign, Entity::Character, name, true)?;
+        Self::record(conn, campaign, Entity::Character, to, false)
     }
 
-    pub fn weapon_build(_params: Params<'_>, pairs: Pairs<'_>) -> Result<Response> {
-        let mut conn = Connection::open(DB)?;
-        let trans = conn.transaction()?;
+    /// Puts the weapon `id` into the inventory of the character `name`.
+    fn give_weapon<C: AsConn>(
+        conn: C,
+        campaign: i64,
+        name: &str,
+        id: Id,
+        nickname: Option<&str>,
+        notes: &str,
+    ) -> Result<()> {
+        let conn = conn.as_conn();
 
-        let id = Id::try_from(pairs.find("id")?)?;
+        let changed = conn.execute(
+            "INSERT INTO inventory (character, weapon, nickname, notes) SELECT id, ?3, NULLIF(?4, ''), ?5 FROM characters WHERE campaign = ?1 AND name = ?2",
+            params![campaign, name, id.to_string(), nickname, notes],
+        )?;
 
-        if !id.check() {
-            return Ok(Response::builder(Status::BAD_REQUEST)
-                .with_header("Location", "/")?
-                .build());
+        if changed == 0 {
+            return Err(ApiError::NotFound(format!("character `{}` not found", name)).into());
         }
 
-        let parts = Db::parts(&trans)?;
-        let curves = Db::curves(&trans)?;
+        Self::record(conn, campaign, Entity::Character, name, false)
+    }
 
-        let weapon = Weapon::from_id(&parts, &curves, id)
-            .context("failed to generate weapon, missing part")?;
+    fn drop_weapon<C: AsConn>(conn: C, campaign: i64, name: &str, item: i64) -> Result<()> {
+        let conn = conn.as_conn();
 
-        trans.commit()?;
+        let changed = conn.execute(
+            "DELETE FROM inventory WHERE id = ?3 AND character = (SELECT id FROM characters WHERE campaign = ?1 AND name = ?2)",
+            params![campaign, name, item],
+        )?;
 
-        json(Db::state(&conn, Some(weapon))?)
+        Self::item_changed(conn, campaign, name, item, changed)
     }
 
-    pub fn weapon_generate(_params: Params<'_>, pairs: Pairs<'_>) -> Result<Response> {
-        let mut conn = Connection::open(DB)?;
-        let trans = conn.transaction()?;
+    /// Changes the nickname and notes of an inventory item, those that are
+    /// `None` are kept and an empty nickname removes it.
+    fn edit_weapon<C: AsConn>(
+        conn: C,
+        campaign: i64,
+        name: &str,
+        item: i64,
+        nickname: Option<&str>,
+        notes: Option<&str>,
+    ) -> Result<()> {
+        let conn = conn.as_conn();
 
-        let level = pairs.find("level")?.parse::<u8>()?;
+        let changed = conn.execute(
+            "UPDATE inventory SET nickname = CASE WHEN ?4 IS NULL THEN nickname ELSE NULLIF(?4, '') END, notes = COALESCE(?5, notes) WHERE id = ?3 AND character = (SELECT id FROM characters WHERE campaign = ?1 AND name = ?2)",
+            params![campaign, name, item, nickname, notes],
+        )?;
 
-        let parts = Db::parts(&trans)?;
-        let curves = Db::curves(&trans)?;
+        Self::item_changed(conn, campaign, name, item, changed)
+    }
 
-        let weapon = Weapon::generate(&parts, &curves, level)
-            .context("failed to generate weapon, missing part")?;
+    fn equip_weapon<C: AsConn>(
+        conn: C,
+        campaign: i64,
+        name: &str,
+        item: i64,
+        equipped: bool,
+    ) -> Result<()> {
+        let conn = conn.as_conn();
 
-        trans.commit()?;
+        let changed = conn.execute(
+            "UPDATE inventory SET equipped = ?4 WHERE id = ?3 AND character = (SELECT id FROM characters WHERE campaign = ?1 AND name = ?2)",
+            params![campaign, name, item, equipped],
+        )?;
 
-        json(Db::state(&conn, Some(weapon))?)
+        Self::item_changed(conn, campaign, name, item, changed)
     }
 
-    pub fn weapon_part_init(_: Params<'_>, _: Pairs<'_>) -> Result<Response> {
-        let mut conn = Connection::open(DB)?;
-        let trans = conn.transaction()?;
+    /// Moves an inventory item from `name` to `to`, it arrives unequipped.
+    fn transfer_weapon<C: AsConn>(
+        conn: C,
+        campaign: i64,
+        name: &str,
+        item: i64,
+        to: &str,
+    ) -> Result<()> {
+        let conn = conn.as_conn();
 
-        trans.execute_batch(WEAPON_PARTS_SQL)?;
+        let target = conn
+            .prepare_cached("SELECT id FROM characters WHERE campaign = ? AND name = ?")?
+            .query_row(params![campaign, to], |row| row.get::<_, i64>(0))
+            .optional()?
+            .ok_or_else(|| ApiError::NotFound(format!("character `{}` not found", to)))?;
 
-        trans.commit()?;
+        let changed = conn.execute(
+            "UPDATE inventory SET character = ?4, equipped = 0 WHERE id = ?3 AND character = (SELECT id FROM characters WHERE campaign = ?1 AND name = ?2)",
+            params![campaign, name, item, target],
+        )?;
 
-        json(Db::state(&conn, None)?)
+        Self::item_changed(conn, campaign, name, item, changed)?;
+        Self::record(conn, campaign, Entity::Character, to, false)
     }
 
-    pub fn weapon_part_new(_: Params<'_>, pairs: Pairs<'_>) -> Result<Response> {
-        let mut conn = Connection::open(DB)?;
-        let trans = conn.transaction()?;
+    /// Records a change to the inventory of `name`, unless nothing `changed`
+    /// because `item` isn't in it.
+    fn item_changed<C: AsConn>(
+        conn: C,
+        campaign: i64,
+        name: &str,
+        item: i64,
+        changed: usize,
+    ) -> Result<()> {
+        if changed == 0 {
+            return Err(ApiError::NotFound(format!(
+                "item `{}` not found on character `{}`",
+                item, name
+            ))
+            .into());
+        }
+
+        Self::record(conn, campaign, Entity::Character, name, false)
+    }
 
-        let name = pairs.find("name")?;
-        let details = pairs.find("details")?;
+    fn add_stat<C: AsConn, A: AsRef<str>>(
+        conn: C,
+        campaign: i64,
+        name: A,
+        template: Option<&str>,
+        typ: StatType,
+        mut meta: StatMeta,
+        actor: Option<&str>,
+    ) -> Result<()> {
+        let conn = conn.as_conn();
 
-        let part = PartType::try_from(pairs.find("part")?)?;
-        let rarity = Rarity::try_from(pairs.find("rarity")?)?;
-        let company = Company::try_from(pairs.find("company")?)?;
+        let template = template.unwrap_or(BASE_TEMPLATE);
 
-        Db::add_weapon_part(&trans, name, details, part, rarity, company)?;
+        typ.check()?;
+        meta.default = meta
+            .default
+            .map(|default| typ.check_value(default))
+            .transpose()?;
 
-        trans.commit()?;
+        if let Some(formula) = typ.formula() {
+            Self::check_formula(conn, campaign, name.as_ref(), template, formula)?;
+        }
 
-        json(Db::state(&conn, None)?)
+        let stat = StatSnapshot {
+            typ,
+            meta,
+            template: Some(template.to_string()),
+            values: BTreeMap::new(),
+        };
+        Self::insert_stat(conn, campaign, name.as_ref(), &stat)?;
+
+        Self::log(
+            conn,
+            campaign,
+            actor,
+            Change {
+                action: Action::AddStat,
+                character: None,
+                stat: Some(name.as_ref()),
+                before: serde_json::Value::Null,
+                after: serde_json::to_value(stat)?,
+            },
+        )
     }
 
-    pub fn weapon_part_remove(params: Params<'_>, _: Pairs<'_>) -> Result<Response> {
-        let mut conn = Connection::open(DB)?;
-        let trans = conn.transaction()?;
+    fn remove_stat<C: AsConn, A: AsRef<str>>(
+        conn: C,
+        campaign: i64,
+        name: A,
+        actor: Option<&str>,
+    ) -> Result<()> {
+        let conn = conn.as_conn();
 
-        let name = params.find("name")?;
-        Db::remove_weapon_part(&trans, name)?;
+        let stat = Self::delete_stat(conn, campaign, name.as_ref())?;
+
+        Self::log(
+            conn,
+            campaign,
+            actor,
+            Change {
+                action: Action::RemoveStat,
+                character: None,
+                stat: Some(name.as_ref()),
+                before: serde_json::to_value(stat)?,
+                after: serde_json::Value::Null,
+            },
+        )
+    }
 
-        trans.commit()?;
+    /// Checks the formula of the new stat `name` of `template` only uses
+    /// numeric stats the template has and doesn't depend on itself.
+    fn check_formula<C: AsConn>(
+        conn: C,
+        campaign: i64,
+        name: &str,
+        template: &str,
+        formula: &str,
+    ) -> Result<()> {
+        let conn = conn.as_conn();
+
+        let stats = Self::template(conn, campaign)?;
+        let inherited = Self::inherited(conn, campaign, template)?;
+
+        let formula = Formula::try_from(formula)?;
+        for reference in formula.references() {
+            match inherited.iter().find(|stat| stat.name == reference) {
+                Some(stat) if !stat.typ.is_numeric() => {
+                    return Err(ApiError::Unprocessable(format!(
+                        "formula uses `{}`, a {} stat",
+                        reference,
+                        stat.typ.kind()
+                    ))
+                    .into());
+                }
+                None if stats.iter().any(|stat| stat.name == reference) => {
+                    return Err(ApiError::Unprocessable(format!(
+                        "formula uses `{}`, which template `{}` doesn't have",
+                        reference, template
+                    ))
+                    .into());
+                }
+                None if reference != name => {
+                    return Err(ApiError::Unprocessable(format!(
+                        "formula uses unknown stat `{}`",
+                        reference
+                    ))
+                    .into());
+                }
+                _ => {}
+            }
+        }
+
+        let formulas = stats
+            .iter()
+            .filter_map(|stat| Some((stat.name.as_str(), stat.typ.formula()?)))
+            .map(|(name, formula)| Ok((name, Formula::try_from(formula)?)))
+            .collect::<Result<Vec<_>, ApiError>>()?;
+
+        let mut references = formulas
+            .iter()
+            .map(|(name, formula)| (*name, formula.references()))
+            .collect::<HashMap<_, _>>();
+        references.insert(name, formula.references());
+
+        if let Some(cycle) = models::formula_cycle(&references) {
+            return Err(ApiError::Unprocessable(format!(
+                "formula depends on itself through {}",
+                cycle.join(" -> ")
+            ))
+            .into());
+        }
 
-        json(Db::state(&conn, None)?)
+        Ok(())
     }
-}
 
-mod models {
-    use std::{borrow::Cow, cmp::Ordering, fmt, ops};
+    /// Adds the template stat `name` with the values of `stat`, characters of
+    /// its template or one inheriting from it that it has no value for start at
+    /// its default.
+    fn insert_stat<C: AsConn>(
+        conn: C,
+        campaign: i64,
+        name: &str,
+        stat: &StatSnapshot,
+    ) -> Result<()> {
+        let conn = conn.as_conn();
 
-    use anyhow::{Context as _, Result};
-    use rand::{prelude::*, Rng};
-    use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+        let options = stat.typ.options().map(serde_json::to_string).transpose()?;
+        let (min, max) = stat.typ.bounds().unzip();
 
-    use crate::{utils, LEVEL_MAX};
+        let template = Self::template_id(
+            conn,
+            campaign,
+            stat.template.as_deref().unwrap_or(BASE_TEMPLATE),
+        )?;
 
-    pub struct Pairs<'p>(Vec<(Cow<'p, str>, Cow<'p, str>)>);
+        let meta = &stat.meta;
+        conn.execute(
+            "INSERT INTO template (campaign, key, type, options, min, max, formula, template, default_value, label, description, position, category, visibility) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                campaign,
+                name,
+                stat.typ,
+                options,
+                min,
+                max,
+                stat.typ.formula(),
+                template,
+                meta.default,
+                meta.label,
+                meta.description,
+                meta.order,
+                meta.category,
+                meta.visibility,
+            ],
+        )
+        .or_conflict(|| format!("stat `{}` already exists", name))?;
+
+        let default = meta.default_value(&stat.typ);
+        conn.execute(
+            "WITH RECURSIVE heirs (id) AS (SELECT ?1 UNION SELECT t.id FROM templates t JOIN heirs h ON t.parent = h.id) INSERT INTO stats (character, stat, value, text) SELECT id, ?2, ?3, ?4 FROM characters WHERE template IN (SELECT id FROM heirs)",
+            params![template, conn.last_insert_rowid(), default.number(), default.text()],
+        )?;
 
-    impl<'p> Pairs<'p> {
-        pub fn new(pairs: Vec<(Cow<'p, str>, Cow<'p, str>)>) -> Self {
-            Self(pairs)
+        let mut value_stmt = conn.prepare_cached("UPDATE stats SET value = ?4, text = ?5 WHERE character = (SELECT id FROM characters WHERE campaign = ?1 AND name = ?3) AND stat = (SELECT id FROM template WHERE campaign = ?1 AND key = ?2)")?;
+
+        for (character, value) in &stat.values {
+            if let Ok(value) = stat.typ.check_value(value.clone()) {
+                value_stmt.execute(params![
+                    campaign,
+                    name,
+                    character,
+                    value.number(),
+                    value.text()
+                ])?;
+            }
         }
 
-        pub fn find(&'p self, name: &str) -> Result<&'p str> {
-            self.0
-                .iter()
-                .find(|(k, _)| *k == name)
-                .map(|(_, v)| v.as_ref())
-                .with_context(|| format!("missing `{}` url parameter", name))
-        }
+        Self::record(conn, campaign, Entity::Stat, name, false)
     }
 
-    pub struct Params<'p>(Vec<(&'p str, &'p str)>);
+    /// Deletes the template stat `name` and returns the values characters had for it.
+    fn delete_stat<C: AsConn>(conn: C, campaign: i64, name: &str) -> Result<StatSnapshot> {
+        let conn = conn.as_conn();
 
-    impl<'p> Params<'p> {
-        pub fn new(pairs: Vec<(&'p str, &'p str)>) -> Self {
-            Self(pairs)
-        }
+        let (typ, template, meta) = conn
+            .prepare_cached(
+                "SELECT t.type, t.options, t.min, t.max, t.formula, p.name, t.default_value, t.label, t.description, t.position, t.category, t.visibility FROM template t JOIN templates p ON p.id = t.template WHERE t.campaign = ? AND t.key = ?",
+            )?
+            .query_row(params![campaign, name], |row| {
+                Ok((Self::stat_type(row, 0)?, row.get(5)?, Self::stat_meta(row, 6)?))
+            })
+            .optional()?
+            .ok_or_else(|| ApiError::NotFound(format!("stat `{}` not found", name)))?;
 
-        pub fn find(&self, name: &str) -> Result<&'p str> {
-            self.0
-                .iter()
-                .find(|(k, _)| *k == name)
-                .map(|(_, v)| *v)
-                .with_context(|| format!("missing `{}` path parameter", name))
+        for stat in Self::template(conn, campaign)? {
+            let Some(formula) = stat.typ.formula() else {
+                continue;
+            };
+
+            if stat.name != name && Formula::try_from(formula)?.references().contains(&name) {
+                return Err(ApiError::Conflict(format!(
+                    "stat `{}` is used by the formula of `{}`",
+                    name, stat.name
+                ))
+                .into());
+            }
         }
+
+        let values = conn
+            .prepare_cached("SELECT c.name, COALESCE(s.text, s.value) FROM stats s JOIN characters c ON c.id = s.character JOIN template t ON t.id = s.stat WHERE t.campaign = ? AND t.key = ?")?
+            .query_map(params![campaign, name], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_, _>>()?;
+
+        conn.execute(
+            "DELETE FROM template WHERE campaign = ? AND key = ?",
+            params![campaign, name],
+        )?;
+
+        Self::record(conn, campaign, Entity::Stat, name, true)?;
+
+        Ok(StatSnapshot {
+            typ,
+            meta,
+            template,
+            values,
+        })
     }
 
-    #[derive(Clone, Copy, serde::Serialize)]
-    pub struct Id(#[serde(with = "id_serde")] [u8; 8]);
+    fn rename_stat<C: AsConn>(
+        conn: C,
+        campaign: i64,
+        name: &str,
+        to: &str,
+        actor: Option<&str>,
+    ) -> Result<()> {
+        let conn = conn.as_conn();
 
-    impl Id {
-        #[allow(dead_code)]
-        pub fn new(id: u64) -> Self {
-            Self(id.to_be_bytes())
-        }
+        Self::move_stat(conn, campaign, name, to)?;
+
+        Self::log(
+            conn,
+            campaign,
+            actor,
+            Change {
+                action: Action::RenameStat,
+                character: None,
+                stat: Some(to),
+                before: name.into(),
+                after: to.into(),
+            },
+        )
+    }
 
-        pub fn from(level: u8, typ: u8, body: u8, barrel: u8, magazine: u8, stock: u8) -> Self {
-            let parity = level ^ typ ^ body ^ barrel ^ magazine ^ stock;
+    /// Renames the template stat `name` to `to` along with the formulas, dice
+    /// and history entries that refer to it, stat values are kept by stat id.
+    fn move_stat<C: AsConn>(conn: C, campaign: i64, name: &str, to: &str) -> Result<()> {
+        let conn = conn.as_conn();
 
-            Self([level, typ, body, barrel, magazine, stock, 0, parity])
+        let changed = conn
+            .execute(
+                "UPDATE template SET key = ?3 WHERE campaign = ?1 AND key = ?2",
+                params![campaign, name, to],
+            )
+            .or_conflict(|| format!("stat `{}` already exists", to))?;
+
+        if changed == 0 {
+            return Err(ApiError::NotFound(format!("stat `{}` not found", name)).into());
         }
 
-        pub fn check(&self) -> bool {
-            let [level, typ, body, barrel, magazine, stock, _, truth] = self.0;
+        let unusable = |user: String| {
+            ApiError::Unprocessable(format!("{} can't refer to a stat named `{}`", user, to))
+        };
 
-            let result = level ^ typ ^ body ^ barrel ^ magazine ^ stock;
+        for stat in Self::template(conn, campaign)? {
+            let dice = match (&stat.typ, &stat.meta.default) {
+                (StatType::Dice, Some(StatValue::Text(dice))) => Dice::rename(dice, name, to)
+                    .map_err(|_| unusable(format!("the default of `{}`", stat.name)))?,
+                _ => None,
+            };
 
-            truth == result
-        }
+            if let Some(dice) = dice {
+                conn.execute(
+                    "UPDATE template SET default_value = ? WHERE campaign = ? AND key = ?",
+                    params![dice, campaign, stat.name],
+                )?;
+                Self::record(conn, campaign, Entity::Stat, &stat.name, false)?;
+            }
 
-        #[inline]
-        pub fn level(&self) -> u8 {
-            self.0[0]
-        }
+            let formula = match stat.typ.formula() {
+                Some(formula) => Formula::rename(formula, name, to)
+                    .map_err(|_| unusable(format!("the formula of `{}`", stat.name)))?,
+                None => None,
+            };
 
-        #[inline]
-        pub fn typ(&self) -> u8 {
-            self.0[1]
+            if let Some(formula) = formula {
+                conn.execute(
+                    "UPDATE template SET formula = ? WHERE campaign = ? AND key = ?",
+                    params![formula, campaign, stat.name],
+                )?;
+                Self::record(conn, campaign, Entity::Stat, &stat.name, false)?;
+            }
         }
-    }
 
-    impl Id {
-        #[inline]
-        pub fn body(&self) -> u8 {
-            self.0[2]
-        }
+        let values = conn
+            .prepare_cached("SELECT s.id, s.text, c.name, t.key FROM stats s JOIN characters c ON c.id = s.character JOIN template t ON t.id = s.stat WHERE t.campaign = ? AND t.type = ? AND s.text IS NOT NULL")?
+            .query_map(params![campaign, StatType::Dice], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
 
-        #[inline]
-        pub fn barrel(&self) -> u8 {
-            self.0[3]
-        }
+        for (id, value, character, stat) in values {
+            let dice = Dice::rename(&value, name, to)
+                .map_err(|_| unusable(format!("`{}` of `{}`", stat, character)))?;
 
-        #[inline]
-        pub fn magazine(&self) -> u8 {
-            self.0[4]
+            if let Some(dice) = dice {
+                conn.execute("UPDATE stats SET text = ? WHERE id = ?", params![dice, id])?;
+                Self::record(conn, campaign, Entity::Character, &character, false)?;
+            }
         }
 
-        #[inline]
-        pub fn stock(&self) -> u8 {
-            self.0[5]
-        }
-    }
+        conn.execute(
+            "UPDATE history SET stat = ?3 WHERE campaign = ?1 AND stat = ?2",
+            params![campaign, name, to],
+        )?;
 
-    impl TryFrom<&str> for Id {
-        type Error = std::num::ParseIntError;
+        // removed characters keep their values by stat name
+        Self::rename_snapshot_key(
+            conn,
+            campaign,
+            [Action::AddCharacter, Action::RemoveCharacter],
+            "stats",
+            name,
+            to,
+        )?;
 
-        fn try_from(id: &str) -> Result<Self, Self::Error> {
-            u64::from_str_radix(id, 16).map(|id| Self(id.to_be_bytes()))
-        }
+        Self::record(conn, campaign, Entity::Stat, name, true)?;
+        Self::record(conn, campaign, Entity::Stat, to, false)
     }
 
-    impl std::fmt::Display for Id {
-        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-            write!(f, "{:0>16x?}", u64::from_be_bytes(self.0))
+    /// Renames the key `name` of the `field` object in the before and after
+    /// snapshots of the history entries of `actions`.
+    fn rename_snapshot_key<C: AsConn>(
+        conn: C,
+        campaign: i64,
+        actions: [Action; 2],
+        field: &str,
+        name: &str,
+        to: &str,
+    ) -> Result<()> {
+        let conn = conn.as_conn();
+
+        // only the entries whose snapshots hold `name`, not the whole history
+        let path = format!("$.{}", field);
+        let entries = conn
+            .prepare_cached("SELECT id, before, after FROM history WHERE campaign = ?1 AND action IN (?2, ?3) AND EXISTS (SELECT 1 FROM json_each(before, ?4) WHERE key = ?5 UNION ALL SELECT 1 FROM json_each(after, ?4) WHERE key = ?5)")?
+            .query_map(params![campaign, actions[0], actions[1], path, name], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut update_stmt =
+            conn.prepare_cached("UPDATE history SET before = ?, after = ? WHERE id = ?")?;
+
+        for (id, before, after) in entries {
+            let mut snapshots = [
+                serde_json::from_str::<serde_json::Value>(&before)?,
+                serde_json::from_str::<serde_json::Value>(&after)?,
+            ];
+
+            let mut renamed = false;
+            for snapshot in &mut snapshots {
+                let Some(keys) = snapshot
+                    .get_mut(field)
+                    .and_then(serde_json::Value::as_object_mut)
+                else {
+                    continue;
+                };
+
+                if let Some(value) = keys.remove(name) {
+                    keys.insert(to.to_string(), value);
+                    renamed = true;
+                }
+            }
+
+            if renamed {
+                let [before, after] = snapshots;
+                update_stmt.execute(params![before.to_string(), after.to_string(), id])?;
+            }
         }
+
+        Ok(())
     }
 
-    mod id_serde {
+    fn increment_stat<C: AsConn, A: AsRef<str>>(
+        conn: C,
+        campaign: i64,
+        name: A,
+        stat: A,
+        actor: Option<&str>,
+    ) -> Result<()> {
+        Self::change_stat(
+            conn,
+            campaign,
+            name.as_ref(),
+            stat.as_ref(),
+            actor,
+            Action::IncrementStat,
+            |typ, value| typ.step(value, 1),
+        )
+    }
+
+    fn decrement_stat<C: AsConn, A: AsRef<str>>(
+        conn: C,
+        campaign: i64,
+        name: A,
+        stat: A,
+        actor: Option<&str>,
+    ) -> Result<()> {
+        Self::change_stat(
+            conn,
+            campaign,
+            name.as_ref(),
+            stat.as_ref(),
+            actor,
+            Action::DecrementStat,
+            |typ, value| typ.step(value, -1),
+        )
+    }
+
+    fn toggle_stat<C: AsConn, A: AsRef<str>>(
+        conn: C,
+        campaign: i64,
+        name: A,
+        stat: A,
+        actor: Option<&str>,
+    ) -> Result<()> {
+        Self::change_stat(
+            conn,
+            campaign,
+            name.as_ref(),
+            stat.as_ref(),
+            actor,
+            Action::ToggleStat,
+            |typ, value| typ.toggle(value),
+        )
+    }
+
+    fn set_stat_value<C: AsConn, A: AsRef<str>>(
+        conn: C,
+        campaign: i64,
+        name: A,
+        stat: A,
+        value: StatValue,
+        actor: Option<&str>,
+    ) -> Result<()> {
+        Self::change_stat(
+            conn,
+            campaign,
+            name.as_ref(),
+            stat.as_ref(),
+            actor,
+            Action::SetStat,
+            |typ, _| typ.check_value(value),
+        )
+    }
+
+    /// Replaces the value of `stat` on the character `name` with `change` of it.
+    fn change_stat<C: AsConn, F: FnOnce(&StatType, &StatValue) -> Result<StatValue, ApiError>>(
+        conn: C,
+        campaign: i64,
+        name: &str,
+        stat: &str,
+        actor: Option<&str>,
+        action: Action,
+        change: F,
+    ) -> Result<()> {
+        let conn = conn.as_conn();
+
+        let (typ, before) = Self::stat_value(conn, campaign, name, stat)?;
+        let after = change(&typ, &before)?;
+
+        Self::set_stat(conn, campaign, name, stat, &after)?;
+
+        Self::log(
+            conn,
+            campaign,
+            actor,
+            Change {
+                action,
+                character: Some(name),
+                stat: Some(stat),
+                before: serde_json::to_value(before)?,
+                after: serde_json::to_value(after)?,
+            },
+        )
+    }
+
+    fn stat_value<C: AsConn>(
+        conn: C,
+        campaign: i64,
+        name: &str,
+        stat: &str,
+    ) -> Result<(StatType, StatValue)> {
+        let conn = conn.as_conn();
+
+        let value = conn
+            .prepare_cached("SELECT t.type, t.options, t.min, t.max, t.formula, COALESCE(s.text, s.value) FROM stats s JOIN characters c ON c.id = s.character JOIN template t ON t.id = s.stat WHERE c.campaign = ?1 AND c.name = ?2 AND t.key = ?3")?
+            .query_row(params![campaign, name, stat], |row| {
+                Ok((Self::stat_type(row, 0)?, row.get(5)?))
+            })
+            .optional()?;
+
+        value.ok_or_else(|| {
+            ApiError::NotFound(format!("stat `{}` not found on character `{}`", stat, name)).into()
+        })
+    }
+
+    fn set_stat<C: AsConn>(
+        conn: C,
+        campaign: i64,
+        name: &str,
+        stat: &str,
+        value: &StatValue,
+    ) -> Result<()> {
+        let conn = conn.as_conn();
+
+        let changed = conn
+            .prepare_cached("UPDATE stats SET value = ?4, text = ?5 WHERE character = (SELECT id FROM characters WHERE campaign = ?1 AND name = ?2) AND stat = (SELECT id FROM template WHERE campaign = ?1 AND key = ?3)")?
+            .execute(params![campaign, name, stat, value.number(), value.text()])?;
+
+        if changed == 0 {
+            return Err(ApiError::NotFound(format!(
+                "stat `{}` not found on character `{}`",
+                stat, name
+            ))
+            .into());
+        }
+
+        Self::record(conn, campaign, Entity::Character, name, false)
+    }
+
+    /// Reads the `default_value`, `label`, `description`, `position`,
+    /// `category` and `visibility` template columns starting at `index`.
+    fn stat_meta(row: &rusqlite::Row<'_>, index: usize) -> rusqlite::Result<StatMeta> {
+        Ok(StatMeta {
+            default: row.get(index)?,
+            label: row.get(index + 1)?,
+            description: row.get(index + 2)?,
+            order: row.get(index + 3)?,
+            category: row.get(index + 4)?,
+            visibility: row.get(index + 5)?,
+        })
+    }
+
+    /// Reads the `type`, `options`, `min`, `max` and `formula` template columns
+    /// starting at `index`.
+    fn stat_type(row: &rusqlite::Row<'_>, index: usize) -> rusqlite::Result<StatType> {
+        let kind = row.get::<_, String>(index)?;
+        let options = row
+            .get::<_, Option<String>>(index + 1)?
+            .map(|options| serde_json::from_str(&options))
+            .transpose()
+            .map_err(|err| {
+                rusqlite::Error::FromSqlConversionFailure(index + 1, Type::Text, err.into())
+            })?;
+
+        StatType::new(
+            &kind,
+            options,
+            row.get(index + 2)?,
+            row.get(index + 3)?,
+            row.get(index + 4)?,
+        )
+        .map_err(|err| rusqlite::Error::FromSqlConversionFailure(index, Type::Text, err.into()))
+    }
+
+    /// Adds `change` to the history, changes that were undone can't be redone
+    /// once something else has changed.
+    fn log<C: AsConn>(
+        conn: C,
+        campaign: i64,
+        actor: Option<&str>,
+        change: Change<'_>,
+    ) -> Result<()> {
+        let conn = conn.as_conn();
+
+        conn.prepare_cached("UPDATE history SET status = ? WHERE campaign = ? AND status = ?")?
+            .execute(params![
+                HistoryStatus::Discarded,
+                campaign,
+                HistoryStatus::Undone
+            ])?;
+
+        conn.prepare_cached("INSERT INTO history (campaign, time, actor, action, character, stat, before, after, status) VALUES (?, unixepoch(), ?, ?, ?, ?, ?, ?, ?)")?
+            .execute(params![
+                campaign,
+                actor,
+                change.action,
+                change.character,
+                change.stat,
+                change.before.to_string(),
+                change.after.to_string(),
+                HistoryStatus::Done,
+            ])?;
+
+        Ok(())
+    }
+
+    /// Rolls `expr` for `character` and adds it to the roll history, without a
+    /// `seed` a random one is picked. Stats only the gm may see can't be
+    /// referred to unless `gm` is set.
+    fn roll<C: AsConn>(
+        conn: C,
+        campaign: i64,
+        actor: Option<&str>,
+        character: Option<&str>,
+        expr: &str,
+        seed: Option<u32>,
+        gm: bool,
+    ) -> Result<RollEntry> {
+        let conn = conn.as_conn();
+
+        let hidden = Self::hidden(&Self::template(conn, campaign)?, false);
+
+        let stats = match character {
+            Some(character) => Self::character_stats(conn, campaign, character)?
+                .into_iter()
+                .filter(|stat| gm || !hidden.contains(&stat.name))
+                .collect(),
+            None => Vec::new(),
+        };
+
+        // the breakdown holds the values of the stats, players don't see
+        // rolls that used hidden ones
+        let secret = Cell::new(false);
+
+        let find = |name: &str| {
+            stats
+                .iter()
+                .find(|stat| stat.name == name)
+                .inspect(|stat| secret.set(secret.get() || hidden.contains(&stat.name)))
+                .ok_or_else(|| match character {
+                    Some(character) => ApiError::NotFound(format!(
+                        "stat `{}` not found on character `{}`",
+                        name, character
+                    )),
+                    None => ApiError::Unprocessable(format!(
+                        "`@{}` needs a character to roll for",
+                        name
+                    )),
+                })
+        };
+
+        let unusable = |name: &str, typ: &StatType| {
+            ApiError::Unprocessable(format!("`@{}` is a {} stat", name, typ.kind()))
+        };
+
+        let dice = Dice::try_from(expr)?.expand(&mut |name| {
+            let stat = find(name)?;
+
+            match (&stat.typ, &stat.value) {
+                (StatType::Dice, StatValue::Text(dice)) => {
+                    Ok(DiceStat::Dice(Dice::try_from(dice.as_str())?))
+                }
+                (typ, StatValue::Number(_)) if typ.is_numeric() => Ok(DiceStat::Number),
+                (typ, _) => Err(unusable(name, typ)),
+            }
+        })?;
+
+        let seed = seed.unwrap_or_else(rand::random);
+        let roll = dice.roll(&mut ChaCha8Rng::seed_from_u64(seed.into()), |name| {
+            let stat = find(name)?;
+
+            stat.value.number().ok_or_else(|| unusable(name, &stat.typ))
+        })?;
+
+        let (id, time) = conn
+            .prepare_cached("INSERT INTO rolls (campaign, time, actor, character, expr, seed, total, terms, breakdown, hidden) VALUES (?, unixepoch(), ?, ?, ?, ?, ?, ?, ?, ?) RETURNING id, time")?
+            .query_row(
+                params![
+                    campaign,
+                    actor,
+                    character,
+                    expr,
+                    seed,
+                    roll.total,
+                    serde_json::to_string(&roll.terms)?,
+                    roll.breakdown,
+                    secret.get(),
+                ],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )?;
+
+        Ok(RollEntry {
+            id,
+            time,
+            actor: actor.map(str::to_string),
+            character: character.map(str::to_string),
+            expr: expr.to_string(),
+            seed,
+            roll,
+        })
+    }
+
+    /// The newest rolls, only those for `character` when it's given. Rolls
+    /// that used stats only the gm may see are left out unless `gm` is set.
+    fn rolls<C: AsConn>(
+        conn: C,
+        campaign: i64,
+        character: Option<&str>,
+        limit: i64,
+        gm: bool,
+    ) -> Result<Vec<RollEntry>> {
+        let conn = conn.as_conn();
+
+        let mut rolls_stmt = conn.prepare_cached("SELECT id, time, actor, character, expr, seed, total, terms, breakdown FROM rolls WHERE campaign = ?1 AND (?2 IS NULL OR character = ?2) AND (?4 OR hidden = 0) ORDER BY id DESC LIMIT ?3")?;
+
+        let mut rows = rolls_stmt.query(params![campaign, character, limit, gm])?;
+
+        let mut rolls = Vec::new();
+        while let Some(row) = rows.next()? {
+            rolls.push(RollEntry {
+                id: row.get(0)?,
+                time: row.get(1)?,
+                actor: row.get(2)?,
+                character: row.get(3)?,
+                expr: row.get(4)?,
+                seed: row.get(5)?,
+                roll: Roll {
+                    total: row.get(6)?,
+                    terms: serde_json::from_str(&row.get::<_, String>(7)?)?,
+                    breakdown: row.get(8)?,
+                },
+            });
+        }
+
+        Ok(rolls)
+    }
+
+    /// Adds `weapon` to the weapon library of `campaign`.
+    fn save_weapon<C: AsConn>(
+        conn: C,
+        campaign: i64,
+        weapon: Weapon,
+        tags: &[String],
+    ) -> Result<SavedWeapon> {
+        let conn = conn.as_conn();
+
+        let weapon = weapon.display();
+
+        let (id, time) = conn
+            .prepare_cached("INSERT INTO weapons (campaign, time, weapon, name, level, rarity, type, company, snapshot) VALUES (?, unixepoch(), ?, ?, ?, ?, ?, ?, ?) RETURNING id, time")?
+            .query_row(
+                params![
+                    campaign,
+                    weapon.id,
+                    weapon.name,
+                    weapon.level,
+                    weapon.rarity,
+                    weapon.typ,
+                    weapon.company,
+                    serde_json::to_string(&weapon)?,
+                ],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )?;
+
+        Self::set_weapon_tags(conn, id, tags)?;
+
+        Ok(SavedWeapon {
+            id,
+            time,
+            tags: Self::weapon_tags(conn, id)?,
+            weapon,
+        })
+    }
+
+    /// The newest saved weapons that match `filter`.
+    fn saved_weapons<C: AsConn>(
+        conn: C,
+        campaign: i64,
+        filter: &WeaponFilter<'_>,
+    ) -> Result<Vec<SavedWeapon>> {
+        let conn = conn.as_conn();
+
+        let mut weapons_stmt = conn.prepare_cached("SELECT id, time, snapshot FROM weapons w WHERE campaign = ?1 AND (?2 IS NULL OR rarity = ?2) AND (?3 IS NULL OR company = ?3) AND (?4 IS NULL OR type = ?4) AND (?5 IS NULL OR level >= ?5) AND (?6 IS NULL OR level <= ?6) AND (?7 IS NULL OR EXISTS (SELECT 1 FROM weapon_tags WHERE weapon = w.id AND tag = ?7)) AND (?8 IS NULL OR instr(lower(name), lower(?8)) > 0 OR EXISTS (SELECT 1 FROM weapon_tags WHERE weapon = w.id AND instr(lower(tag), lower(?8)) > 0)) ORDER BY id DESC LIMIT ?9")?;
+
+        let mut rows = weapons_stmt.query(params![
+            campaign,
+            filter.rarity,
+            filter.company,
+            filter.typ,
+            filter.min_level,
+            filter.max_level,
+            filter.tag,
+            filter.search,
+            filter.limit,
+        ])?;
+
+        let mut weapons = Vec::new();
+        while let Some(row) = rows.next()? {
+            let id = row.get(0)?;
+
+            weapons.push(SavedWeapon {
+                id,
+                time: row.get(1)?,
+                tags: Self::weapon_tags(conn, id)?,
+                weapon: serde_json::from_str(&row.get::<_, String>(2)?)?,
+            });
+        }
+
+        Ok(weapons)
+    }
+
+    fn saved_weapon<C: AsConn>(conn: C, campaign: i64, id: i64) -> Result<SavedWeapon> {
+        let conn = conn.as_conn();
+
+        let (time, snapshot) = conn
+            .prepare_cached("SELECT time, snapshot FROM weapons WHERE campaign = ? AND id = ?")?
+            .query_row(params![campaign, id], |row| {
+                Ok((row.get(0)?, row.get::<_, String>(1)?))
+            })
+            .optional()?
+            .ok_or_else(|| ApiError::NotFound(format!("saved weapon `{}` not found", id)))?;
+
+        Ok(SavedWeapon {
+            id,
+            time,
+            tags: Self::weapon_tags(conn, id)?,
+            weapon: serde_json::from_str(&snapshot)?,
+        })
+    }
+
+    /// Replaces the tags of the saved weapon `id`.
+    fn tag_weapon<C: AsConn>(
+        conn: C,
+        campaign: i64,
+        id: i64,
+        tags: &[String],
+    ) -> Result<SavedWeapon> {
+        let conn = conn.as_conn();
+
+        // makes sure the weapon is in this campaign before touching its tags
+        Self::saved_weapon(conn, campaign, id)?;
+
+        conn.execute("DELETE FROM weapon_tags WHERE weapon = ?", [id])?;
+        Self::set_weapon_tags(conn, id, tags)?;
+
+        Self::saved_weapon(conn, campaign, id)
+    }
+
+    fn remove_saved_weapon<C: AsConn>(conn: C, campaign: i64, id: i64) -> Result<()> {
+        let conn = conn.as_conn();
+
+        let changed = conn.execute(
+            "DELETE FROM weapons WHERE campaign = ? AND id = ?",
+            params![campaign, id],
+        )?;
+
+        if changed == 0 {
+            return Err(ApiError::NotFound(format!("saved weapon `{}` not found", id)).into());
+        }
+
+        Ok(())
+    }
+
+    fn set_weapon_tags<C: AsConn>(conn: C, id: i64, tags: &[String]) -> Result<()> {
+        let conn = conn.as_conn();
+
+        let mut tag_stmt =
+            conn.prepare_cached("INSERT OR IGNORE INTO weapon_tags (weapon, tag) VALUES (?, ?)")?;
+
+        for tag in tags {
+            let tag = tag.trim();
+
+            if tag.is_empty() {
+                return Err(ApiError::invalid("tag", tag).into());
+            }
+
+            tag_stmt.execute(params![id, tag])?;
+        }
+
+        Ok(())
+    }
+
+    fn weapon_tags<C: AsConn>(conn: C, id: i64) -> Result<Vec<String>> {
+        let conn = conn.as_conn();
+
+        let tags = conn
+            .prepare_cached("SELECT tag FROM weapon_tags WHERE weapon = ? ORDER BY tag")?
+            .query_map([id], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+
+        Ok(tags)
+    }
+
+    /// The newest history entries that match `filter`, entries of stats only
+    /// the gm may see are left out and their values are left out of character
+    /// snapshots unless `gm` is set.
+    fn history<C: AsConn>(
+        conn: C,
+        campaign: i64,
+        filter: &HistoryFilter<'_>,
+        gm: bool,
+    ) -> Result<Vec<HistoryEntry>> {
+        let conn = conn.as_conn();
+
+        let mut history_stmt = conn.prepare_cached("SELECT id, time, actor, action, character, stat, before, after, status FROM history WHERE campaign = ?1 AND (?2 IS NULL OR actor = ?2) AND (?3 IS NULL OR character = ?3) AND (?4 IS NULL OR stat = ?4) AND (?5 IS NULL OR action = ?5) AND (?6 IS NULL OR time >= ?6) AND (?7 IS NULL OR time < ?7) ORDER BY id DESC LIMIT ?8")?;
+
+        let rows = history_stmt.query(params![
+            campaign,
+            filter.actor,
+            filter.character,
+            filter.stat,
+            filter.action,
+            filter.since,
+            filter.until,
+            filter.limit,
+        ])?;
+
+        let mut entries = Self::history_entries(rows)?;
+        if gm {
+            return Ok(entries);
+        }
+
+        let stats = Self::template(conn, campaign)?;
+        let hidden = Self::hidden_names(conn, campaign, &stats, false)?;
+
+        entries.retain(|entry| {
+            !entry
+                .stat
+                .as_ref()
+                .is_some_and(|stat| hidden.contains(stat))
+        });
+
+        for entry in &mut entries {
+            for snapshot in [&mut entry.before, &mut entry.after] {
+                if let Some(stats) = snapshot
+                    .get_mut("stats")
+                    .and_then(serde_json::Value::as_object_mut)
+                {
+                    stats.retain(|stat, _| !hidden.contains(stat));
+                }
+            }
+        }
+
+        Ok(entries)
+    }
+
+    fn history_entries(mut rows: rusqlite::Rows<'_>) -> Result<Vec<HistoryEntry>> {
+        let mut entries = Vec::new();
+
+        while let Some(row) = rows.next()? {
+            entries.push(HistoryEntry {
+                id: row.get(0)?,
+                time: row.get(1)?,
+                actor: row.get(2)?,
+                action: row.get(3)?,
+                character: row.get(4)?,
+                stat: row.get(5)?,
+                before: serde_json::from_str(&row.get::<_, String>(6)?)?,
+                after: serde_json::from_str(&row.get::<_, String>(7)?)?,
+                status: row.get(8)?,
+            });
+        }
+
+        Ok(entries)
+    }
+
+    /// Reverts the last `count` changes that are still done, newest first.
+    fn undo<C: AsConn>(conn: C, campaign: i64, count: i64) -> Result<()> {
+        let conn = conn.as_conn();
+
+        // entries are read one at a time, replaying a rename rewrites the
+        // snapshots of the entries that come after it
+        for undone in 0..count {
+            let entry = Self::history_entries(
+                conn.prepare_cached("SELECT id, time, actor, action, character, stat, before, after, status FROM history WHERE campaign = ? AND status = ? ORDER BY id DESC LIMIT 1")?
+                    .query(params![campaign, HistoryStatus::Done])?,
+            )?
+            .pop();
+
+            let Some(entry) = entry else {
+                if undone == 0 {
+                    return Err(ApiError::Conflict("nothing to undo".to_string()).into());
+                }
+
+                break;
+            };
+
+            Self::replay(conn, campaign, &entry, true)?;
+            Self::set_history_status(conn, entry.id, HistoryStatus::Undone)?;
+        }
+
+        Ok(())
+    }
+
+    /// Applies the last `count` undone changes again, in the order they were
+    /// first made.
+    fn redo<C: AsConn>(conn: C, campaign: i64, count: i64) -> Result<()> {
+        let conn = conn.as_conn();
+
+        // undone entries always follow every done one, so the oldest undone
+        // entry is the one undone last, see `undo` for why they are read one
+        // at a time
+        for redone in 0..count {
+            let entry = Self::history_entries(
+                conn.prepare_cached("SELECT id, time, actor, action, character, stat, before, after, status FROM history WHERE campaign = ? AND status = ? ORDER BY id LIMIT 1")?
+                    .query(params![campaign, HistoryStatus::Undone])?,
+            )?
+            .pop();
+
+            let Some(entry) = entry else {
+                if redone == 0 {
+                    return Err(ApiError::Conflict("nothing to redo".to_string()).into());
+                }
+
+                break;
+            };
+
+            Self::replay(conn, campaign, &entry, false)?;
+            Self::set_history_status(conn, entry.id, HistoryStatus::Done)?;
+        }
+
+        Ok(())
+    }
+
+    /// Puts back the values from before `entry` when `undo`, otherwise the
+    /// values from after it.
+    fn replay<C: AsConn>(conn: C, campaign: i64, entry: &HistoryEntry, undo: bool) -> Result<()> {
+        let conn = conn.as_conn();
+
+        let character = entry.character.as_deref().unwrap_or_default();
+        let stat = entry.stat.as_deref().unwrap_or_default();
+        let value = if undo { &entry.before } else { &entry.after };
+
+        // the name a renamed character or stat has before replaying
+        let current = if undo { &entry.after } else { &entry.before };
+
+        // undoing an add is the same as redoing a remove
+        let removes = matches!(
+            (entry.action, undo),
+            (Action::AddCharacter | Action::AddStat, true)
+                | (Action::RemoveCharacter | Action::RemoveStat, false)
+        );
+
+        match entry.action {
+            Action::IncrementStat
+            | Action::DecrementStat
+            | Action::ToggleStat
+            | Action::SetStat => Self::set_stat(
+                conn,
+                campaign,
+                character,
+                stat,
+                &serde_json::from_value(value.clone())?,
+            ),
+            Action::RenameCharacter => Self::move_character(
+                conn,
+                campaign,
+                &serde_json::from_value::<String>(current.clone())?,
+                &serde_json::from_value::<String>(value.clone())?,
+            ),
+            Action::RenameStat => Self::move_stat(
+                conn,
+                campaign,
+                &serde_json::from_value::<String>(current.clone())?,
+                &serde_json::from_value::<String>(value.clone())?,
+            ),
+            Action::AddCharacter | Action::RemoveCharacter if removes => {
+                Self::delete_character(conn, campaign, character)?;
+                Ok(())
+            }
+            Action::AddCharacter | Action::RemoveCharacter => Self::insert_character(
+                conn,
+                campaign,
+                character,
+                &serde_json::from_value(value.clone())?,
+            ),
+            Action::AddStat | Action::RemoveStat if removes => {
+                Self::delete_stat(conn, campaign, stat)?;
+                Ok(())
+            }
+            Action::AddStat | Action::RemoveStat => Self::insert_stat(
+                conn,
+                campaign,
+                stat,
+                &serde_json::from_value(value.clone())?,
+            ),
+        }
+    }
+
+    fn set_history_status<C: AsConn>(conn: C, id: i64, status: HistoryStatus) -> Result<()> {
+        let conn = conn.as_conn();
+
+        conn.prepare_cached("UPDATE history SET status = ? WHERE id = ?")?
+            .execute(params![status, id])?;
+
+        Ok(())
+    }
+
+    fn add_weapon_part<C: AsConn, A: AsRef<str>>(
+        conn: C,
+        campaign: i64,
+        name: A,
+        details: A,
+        part: PartType,
+        rarity: Rarity,
+        company: Company,
+    ) -> Result<()> {
+        let conn = conn.as_conn();
+
+        // weapon ids hold a byte per part, ids are never reused so an id keeps
+        // pointing at the part it was made with
+        let id = conn.query_row(
+            "UPDATE campaigns SET part_seq = part_seq + 1 WHERE id = ? RETURNING part_seq",
+            [campaign],
+            |row| row.get::<_, i64>(0),
+        )?;
+
+        if id >= PART_LIMIT as i64 {
+            return Err(ApiError::Conflict(format!(
+                "all {} part ids of the campaign have been used, `{}` can't be added",
+                PART_LIMIT,
+                name.as_ref()
+            ))
+            .into());
+        }
+
+        conn.execute(
+            "INSERT INTO weapon_parts (campaign, id, name, details, type, rarity, company) VALUES (?, ?, ?, ?, ?, ?, ?)",
+            params![campaign, id, name.as_ref(), details.as_ref(), part, rarity, company],
+        )
+        .or_conflict(|| format!("{} part `{}` already exists", part, name.as_ref()))?;
+
+        Self::record(conn, campaign, Entity::Part, id.to_string(), false)
+    }
+
+    /// Records `id` as the weapon on display, so it's sent along to every client.
+    fn show_weapon<C: AsConn>(conn: C, campaign: i64, id: Id) -> Result<()> {
+        Self::record(conn, campaign, Entity::Weapon, id.to_string(), false)
+    }
+
+    fn init_weapon_parts<C: AsConn>(conn: C, campaign: i64) -> Result<()> {
+        let conn = conn.as_conn();
+
+        conn.execute(WEAPON_CURVES_SQL, [campaign])?;
+        conn.execute(WEAPON_WEIGHTS_SQL, [campaign])?;
+        conn.execute(RARITY_WEIGHTS_SQL, [campaign])?;
+        conn.execute(WEAPON_PARTS_SQL, [campaign]).or_conflict(|| {
+            format!("all {} part ids of the campaign have been used", PART_LIMIT)
+        })?;
+
+        conn.execute(
+            "UPDATE campaigns SET part_seq = (SELECT MAX(id) FROM weapon_parts WHERE campaign = ?1) WHERE id = ?1 AND part_seq < (SELECT MAX(id) FROM weapon_parts WHERE campaign = ?1)",
+            [campaign],
+        )?;
+
+        conn.execute(
+            "INSERT INTO changes (campaign, entity, key, removed) SELECT ?1, ?2, id, 0 FROM weapon_parts WHERE campaign = ?1",
+            params![campaign, Entity::Part],
+        )?;
+
+        Ok(())
+    }
+
+    fn remove_weapon_part<C: AsConn>(conn: C, campaign: i64, id: u8) -> Result<()> {
+        let conn = conn.as_conn();
+
+        let changed = conn.execute(
+            "DELETE FROM weapon_parts WHERE campaign = ? AND id = ?",
+            params![campaign, id],
+        )?;
+
+        if changed == 0 {
+            return Err(ApiError::NotFound(format!("weapon part `{}` not found", id)).into());
+        }
+
+        Self::record(conn, campaign, Entity::Part, id.to_string(), true)
+    }
+
+    /// Renames the weapon part `id`, weapons refer to parts by id so they keep it.
+    fn rename_weapon_part<C: AsConn>(conn: C, campaign: i64, id: u8, to: &str) -> Result<()> {
+        let conn = conn.as_conn();
+
+        let changed = conn
+            .execute(
+                "UPDATE weapon_parts SET name = ? WHERE campaign = ? AND id = ?",
+                params![to, campaign, id],
+            )
+            .or_conflict(|| format!("weapon part `{}` already exists", to))?;
+
+        if changed == 0 {
+            return Err(ApiError::NotFound(format!("weapon part `{}` not found", id)).into());
+        }
+
+        Self::record(conn, campaign, Entity::Part, id.to_string(), false)
+    }
+
+    fn templates<C: AsConn>(conn: C, campaign: i64) -> Result<Vec<Template>> {
+        let conn = conn.as_conn();
+
+        let mut templates_stmt = conn.prepare_cached("SELECT t.name, p.name FROM templates t LEFT JOIN templates p ON p.id = t.parent WHERE t.campaign = ? ORDER BY t.id")?;
+
+        let templates = templates_stmt
+            .query_map([campaign], |row| {
+                Ok(Template {
+                    name: row.get(0)?,
+                    parent: row.get(1)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>();
+
+        templates.context("failed to collect templates")
+    }
+
+    fn template_id<C: AsConn>(conn: C, campaign: i64, name: &str) -> Result<i64> {
+        let conn = conn.as_conn();
+
+        let id = conn
+            .prepare_cached("SELECT id FROM templates WHERE campaign = ? AND name = ?")?
+            .query_row(params![campaign, name], |row| row.get(0))
+            .optional()?;
+
+        id.ok_or_else(|| ApiError::NotFound(format!("template `{}` not found", name)).into())
+    }
+
+    /// Adds the template `name`, it has the stats of `parent` as well as its own.
+    fn add_template<C: AsConn>(
+        conn: C,
+        campaign: i64,
+        name: &str,
+        parent: Option<&str>,
+    ) -> Result<()> {
+        let conn = conn.as_conn();
+
+        let parent = parent
+            .map(|parent| Self::template_id(conn, campaign, parent))
+            .transpose()?;
+
+        conn.execute(
+            "INSERT INTO templates (campaign, name, parent) VALUES (?, ?, ?)",
+            params![campaign, name, parent],
+        )
+        .or_conflict(|| format!("template `{}` already exists", name))?;
+
+        Self::record(conn, campaign, Entity::Template, name, false)
+    }
+
+    /// Removes the template `name`, only once no stat, character or other
+    /// template uses it.
+    fn remove_template<C: AsConn>(conn: C, campaign: i64, name: &str) -> Result<()> {
+        let conn = conn.as_conn();
+
+        if name == BASE_TEMPLATE {
+            return Err(ApiError::Conflict(format!(
+                "template `{}` can't be removed",
+                BASE_TEMPLATE
+            ))
+            .into());
+        }
+
+        let id = Self::template_id(conn, campaign, name)?;
+
+        for (sql, used) in [
+            (
+                "SELECT name FROM templates WHERE parent = ?",
+                "is inherited by template",
+            ),
+            (
+                "SELECT key FROM template WHERE template = ?",
+                "still has the stat",
+            ),
+            (
+                "SELECT name FROM characters WHERE template = ?",
+                "is used by character",
+            ),
+        ] {
+            let user = conn
+                .prepare_cached(&format!("{} ORDER BY id LIMIT 1", sql))?
+                .query_row([id], |row| row.get::<_, String>(0))
+                .optional()?;
+
+            if let Some(user) = user {
+                return Err(
+                    ApiError::Conflict(format!("template `{}` {} `{}`", name, used, user)).into(),
+                );
+            }
+        }
+
+        conn.execute("DELETE FROM templates WHERE id = ?", [id])?;
+
+        Self::record(conn, campaign, Entity::Template, name, true)
+    }
+
+    fn template<C: AsConn>(conn: C, campaign: i64) -> Result<Vec<Stat>> {
+        let conn = conn.as_conn();
+
+        let mut template_stmt = conn.prepare_cached(
+            "SELECT t.key, p.name, t.type, t.options, t.min, t.max, t.formula, t.default_value, t.label, t.description, t.position, t.category, t.visibility FROM template t JOIN templates p ON p.id = t.template WHERE t.campaign = ? ORDER BY t.position, t.id",
+        )?;
+
+        let template = template_stmt
+            .query_map([campaign], |row| {
+                Ok(Stat {
+                    name: row.get(0)?,
+                    template: row.get(1)?,
+                    typ: Self::stat_type(row, 2)?,
+                    meta: Self::stat_meta(row, 7)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>();
+
+        template.context("failed to collect template stats")
+    }
+
+    /// The stats characters of `template` hold, its own and those of every
+    /// template it inherits from.
+    fn inherited<C: AsConn>(conn: C, campaign: i64, template: &str) -> Result<Vec<Stat>> {
+        let conn = conn.as_conn();
+
+        let templates = Self::templates(conn, campaign)?;
+
+        let mut chain = Vec::new();
+        let mut next = Some(template);
+        while let Some(name) = next {
+            let template = templates
+                .iter()
+                .find(|template| template.name == name)
+                .ok_or_else(|| ApiError::NotFound(format!("template `{}` not found", name)))?;
+
+            chain.push(name);
+            next = template.parent.as_deref();
+        }
+
+        Ok(Self::template(conn, campaign)?
+            .into_iter()
+            .filter(|stat| chain.contains(&stat.template.as_str()))
+            .collect())
+    }
+
+    fn characters<C: AsConn>(conn: C, campaign: i64) -> Result<Vec<Character>> {
+        let conn = conn.as_conn();
+
+        let mut characters_stmt = conn.prepare_cached("SELECT c.id, c.name, u.name, t.key, t.type, t.options, t.min, t.max, t.formula, COALESCE(s.text, s.value), p.name FROM characters c JOIN templates p ON p.id = c.template LEFT JOIN stats s ON s.character = c.id LEFT JOIN template t ON t.id = s.stat LEFT JOIN users u ON u.id = c.owner WHERE c.campaign = ? ORDER BY c.id, t.position, t.id")?;
+
+        let mut rows = characters_stmt.query([campaign])?;
+
+        let mut characters: Vec<(i64, Character)> = Vec::new();
+        while let Some(row) = rows.next()? {
+            let id = row.get::<_, i64>(0)?;
+
+            if characters.last().map(|(last, _)| *last) != Some(id) {
+                characters.push((
+                    id,
+                    Character {
+                        name: row.get(1)?,
+                        owner: row.get(2)?,
+                        template: row.get(10)?,
+                        stats: Vec::new(),
+                        inventory: Vec::new(),
+                    },
+                ));
+            }
+
+            // characters without any stats still have a single row of nulls
+            let Some(name) = row.get::<_, Option<String>>(3)? else {
+                continue;
+            };
+
+            if let Some((_, character)) = characters.last_mut() {
+                character.stats.push(CharacterStat {
+                    name,
+                    typ: Self::stat_type(row, 4)?,
+                    value: row.get(9)?,
+                });
+            }
+        }
+
+        let mut inventories = Self::inventories(conn, campaign)?;
+
+        Ok(characters
+            .into_iter()
+            .map(|(id, mut character)| {
+                models::evaluate_formulas(&mut character.stats);
+                character.inventory = inventories.remove(&id).unwrap_or_default();
+                character
+            })
+            .collect())
+    }
+
+    /// The stats of the character `name`, with formulas evaluated.
+    fn character_stats<C: AsConn>(
+        conn: C,
+        campaign: i64,
+        name: &str,
+    ) -> Result<Vec<CharacterStat>> {
+        let conn = conn.as_conn();
+
+        let mut stats_stmt = conn.prepare_cached("SELECT t.key, t.type, t.options, t.min, t.max, t.formula, COALESCE(s.text, s.value) FROM characters c LEFT JOIN stats s ON s.character = c.id LEFT JOIN template t ON t.id = s.stat WHERE c.campaign = ? AND c.name = ? ORDER BY t.position, t.id")?;
+
+        let mut rows = stats_stmt.query(params![campaign, name])?;
+
+        let mut found = false;
+        let mut stats = Vec::new();
+        while let Some(row) = rows.next()? {
+            found = true;
+
+            // characters without any stats still have a single row of nulls
+            let Some(key) = row.get::<_, Option<String>>(0)? else {
+                continue;
+            };
+
+            stats.push(CharacterStat {
+                name: key,
+                typ: Self::stat_type(row, 1)?,
+                value: row.get(6)?,
+            });
+        }
+
+        if !found {
+            return Err(ApiError::NotFound(format!("character `{}` not found", name)).into());
+        }
+
+        models::evaluate_formulas(&mut stats);
+
+        Ok(stats)
+    }
+
+    /// The inventory items of every character in `campaign` by character id.
+    fn inventories<C: AsConn>(conn: C, campaign: i64) -> Result<HashMap<i64, Vec<InventoryItem>>> {
+        let conn = conn.as_conn();
+
+        let parts = Self::parts(conn, campaign)?;
+        let curves = Self::curves(conn, campaign)?;
+
+        let mut inventory_stmt = conn.prepare_cached("SELECT i.character, i.id, i.weapon, i.nickname, i.equipped, i.notes FROM inventory i JOIN characters c ON c.id = i.character WHERE c.campaign = ? ORDER BY i.id")?;
+
+        let mut rows = inventory_stmt.query([campaign])?;
+
+        let mut inventories = HashMap::<i64, Vec<InventoryItem>>::new();
+        while let Some(row) = rows.next()? {
+            // weapons whose parts have since been removed can't be shown anymore
+            let weapon = Id::try_from(row.get::<_, String>(2)?.as_str())
+                .ok()
+                .and_then(|id| Weapon::from_id(&parts, &curves, id).ok())
+                .map(Weapon::display);
+
+            inventories
+                .entry(row.get(0)?)
+                .or_default()
+                .push(InventoryItem {
+                    id: row.get(1)?,
+                    weapon,
+                    nickname: row.get(3)?,
+                    equipped: row.get(4)?,
+                    notes: row.get(5)?,
+                });
+        }
+
+        Ok(inventories)
+    }
+
+    fn weapon_weights<C: AsConn>(conn: C, campaign: i64) -> Result<Vec<WeaponWeight>> {
+        let conn = conn.as_conn();
+
+        let weights = conn
+            .prepare_cached(
+                "SELECT type, level, weight FROM weapon_weights WHERE campaign = ? ORDER BY type, level",
+            )?
+            .query_map([campaign], |row| {
+                Ok(WeaponWeight {
+                    typ: row.get(0)?,
+                    level: row.get(1)?,
+                    weight: row.get(2)?,
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+
+        Ok(weights)
+    }
+
+    /// Sets the weight `weight.typ` has from `weight.level` on.
+    fn set_weapon_weight<C: AsConn>(conn: C, campaign: i64, weight: &WeaponWeight) -> Result<()> {
+        let conn = conn.as_conn();
+
+        if weight.level > LEVEL_MAX {
+            return Err(WeaponError::InvalidLevel(weight.level).into());
+        }
+
+        conn.execute(
+            "INSERT INTO weapon_weights (campaign, type, level, weight) VALUES (?, ?, ?, ?) ON CONFLICT (campaign, type, level) DO UPDATE SET weight = excluded.weight",
+            params![campaign, weight.typ, weight.level, weight.weight],
+        )?;
+
+        Self::record(conn, campaign, Entity::Weight, "types", false)
+    }
+
+    fn remove_weapon_weight<C: AsConn>(
+        conn: C,
+        campaign: i64,
+        typ: WeaponType,
+        level: u8,
+    ) -> Result<()> {
+        let conn = conn.as_conn();
+
+        let changed = conn.execute(
+            "DELETE FROM weapon_weights WHERE campaign = ? AND type = ? AND level = ?",
+            params![campaign, typ, level],
+        )?;
+
+        if changed == 0 {
+            return Err(
+                ApiError::NotFound(format!("no {} weight from level {}", typ, level)).into(),
+            );
+        }
+
+        Self::record(conn, campaign, Entity::Weight, "types", false)
+    }
+
+    fn rarity_weights<C: AsConn>(conn: C, campaign: i64) -> Result<Vec<RarityWeight>> {
+        let conn = conn.as_conn();
+
+        let weights = conn
+            .prepare_cached(
+                "SELECT rarity, level, weight FROM rarity_weights WHERE campaign = ? ORDER BY level, id",
+            )?
+            .query_map([campaign], |row| {
+                Ok(RarityWeight {
+                    rarity: row.get(0)?,
+                    level: row.get(1)?,
+                    weight: row.get(2)?,
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+
+        Ok(weights)
+    }
+
+    /// Sets the weight `weight.rarity` has from `weight.level` on.
+    fn set_rarity_weight<C: AsConn>(conn: C, campaign: i64, weight: &RarityWeight) -> Result<()> {
+        let conn = conn.as_conn();
+
+        if weight.level > LEVEL_MAX {
+            return Err(WeaponError::InvalidLevel(weight.level).into());
+        }
+
+        conn.execute(
+            "INSERT INTO rarity_weights (campaign, rarity, level, weight) VALUES (?, ?, ?, ?) ON CONFLICT (campaign, rarity, level) DO UPDATE SET weight = excluded.weight",
+            params![campaign, weight.rarity, weight.level, weight.weight],
+        )?;
+
+        Self::record(conn, campaign, Entity::Weight, "rarities", false)
+    }
+
+    fn remove_rarity_weight<C: AsConn>(
+        conn: C,
+        campaign: i64,
+        rarity: Rarity,
+        level: u8,
+    ) -> Result<()> {
+        let conn = conn.as_conn();
+
+        let changed = conn.execute(
+            "DELETE FROM rarity_weights WHERE campaign = ? AND rarity = ? AND level = ?",
+            params![campaign, rarity, level],
+        )?;
+
+        if changed == 0 {
+            return Err(
+                ApiError::NotFound(format!("no {} weight from level {}", rarity, level)).into(),
+            );
+        }
+
+        Self::record(conn, campaign, Entity::Weight, "rarities", false)
+    }
+
+    /// Both weight tables weapons are generated with.
+    fn drop_table<C: AsConn>(conn: C, campaign: i64) -> Result<DropTable> {
+        let conn = conn.as_conn();
+
+        Ok(DropTable {
+            types: Self::weapon_weights(conn, campaign)?,
+            rarities: Self::rarity_weights(conn, campaign)?,
+        })
+    }
+
+    fn curves<C: AsConn>(conn: C, campaign: i64) -> Result<Vec<WeaponCurve>> {
+        let conn = conn.as_conn();
+
+        let mut parts_stmt = conn.prepare_cached(
+            "SELECT name, type, a, b, c, d FROM weapon_curves WHERE campaign = ?",
+        )?;
+
+        let rows = parts_stmt
+            .query_map([campaign], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    [row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?],
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()
+            .context("failed to collect weapon curves")?;
+
+        let curves = rows
+            .into_iter()
+            .map(|(name, typ, [a, b, c, d])| {
+                let curve = match typ.as_str() {
+                    "cubic" => Curve::cubic(a, b, c, d),
+                    "linear" => Curve::linear(a, b),
+                    "quadratic" => Curve::quadratic(a, b, c),
+                    _ => return Err(WeaponError::InvalidCurve(name, typ)),
+                };
+
+                let typ = WeaponType::try_from(name.as_str())
+                    .map_err(|_| WeaponError::InvalidCurve(name.clone(), typ))?;
+
+                Ok(WeaponCurve { typ, curve })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(curves)
+    }
+
+    fn parts<C: AsConn>(conn: C, campaign: i64) -> Result<Vec<Part>> {
+        let conn = conn.as_conn();
+
+        let mut parts_stmt = conn.prepare_cached(
+            "SELECT id, name, details, type, rarity, company FROM weapon_parts WHERE campaign = ? ORDER BY id",
+        )?;
+
+        let parts = parts_stmt
+            .query_map([campaign], |row| {
+                Ok(Part {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    details: row.get(2)?,
+                    typ: row.get(3)?,
+                    rarity: row.get(4)?,
+                    company: row.get(5)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>();
+
+        parts.context("failed to collect weapon parts")
+    }
+
+    /// The part ids in the order the part table had before parts had stable
+    /// ids, used to decode weapon ids that index into that order.
+    fn legacy_parts<C: AsConn>(conn: C, campaign: i64) -> Result<Vec<u8>> {
+        let conn = conn.as_conn();
+
+        let mut legacy_stmt = conn.prepare_cached(
+            "SELECT part FROM weapon_parts_legacy WHERE campaign = ? ORDER BY position",
+        )?;
+
+        let legacy = legacy_stmt
+            .query_map([campaign], |row| row.get(0))?
+            .collect::<Result<Vec<_>, _>>();
+
+        legacy.context("failed to collect legacy weapon parts")
+    }
+}
+
+mod pool {
+    use std::{
+        ops::{Deref, DerefMut},
+        path::{Path, PathBuf},
+        sync::{Condvar, Mutex, MutexGuard},
+        time::Duration,
+    };
+
+    use anyhow::{anyhow, Result};
+    use rusqlite::{Connection, Transaction, TransactionBehavior};
+
+    /// How long a connection waits on another connection's write lock before failing with `SQLITE_BUSY`.
+    static BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+    static STATEMENT_CACHE_CAPACITY: usize = 64;
+
+    struct Idle {
+        connections: Vec<Connection>,
+        /// Connections that have been opened, idle or handed out.
+        open: usize,
+    }
+
+    /// A fixed size pool of sqlite connections, connections are opened lazily
+    /// and callers wait for one to be returned once `size` are in use.
+    pub struct Pool {
+        path: PathBuf,
+        size: usize,
+        idle: Mutex<Idle>,
+        returned: Condvar,
+    }
+
+    impl Pool {
+        pub fn new<P: AsRef<Path>>(path: P, size: usize) -> Self {
+            Self {
+                path: path.as_ref().to_path_buf(),
+                size,
+                idle: Mutex::new(Idle {
+                    connections: Vec::with_capacity(size),
+                    open: 0,
+                }),
+                returned: Condvar::new(),
+            }
+        }
+
+        pub fn get(&self) -> Result<PooledConnection<'_>> {
+            let mut idle = self.lock()?;
+
+            loop {
+                if let Some(conn) = idle.connections.pop() {
+                    return Ok(PooledConnection {
+                        pool: self,
+                        conn: Some(conn),
+                    });
+                }
+
+                if idle.open < self.size {
+                    idle.open += 1;
+                    drop(idle);
+
+                    return match Self::connect(&self.path) {
+                        Ok(conn) => Ok(PooledConnection {
+                            pool: self,
+                            conn: Some(conn),
+                        }),
+                        Err(err) => {
+                            self.lock()?.open -= 1;
+                            self.returned.notify_one();
+
+                            Err(err)
+                        }
+                    };
+                }
+
+                idle = self
+                    .returned
+                    .wait(idle)
+                    .map_err(|_| anyhow!("connection pool lock poisoned"))?;
+            }
+        }
+
+        fn lock(&self) -> Result<MutexGuard<'_, Idle>> {
+            self.idle
+                .lock()
+                .map_err(|_| anyhow!("connection pool lock poisoned"))
+        }
+
+        fn connect(path: &Path) -> Result<Connection> {
+            let conn = Connection::open(path)?;
+
+            conn.busy_timeout(BUSY_TIMEOUT)?;
+            conn.set_prepared_statement_cache_capacity(STATEMENT_CACHE_CAPACITY);
+
+            conn.query_row("PRAGMA journal_mode = WAL", [], |_| Ok(()))?;
+            conn.execute_batch("PRAGMA foreign_keys = ON")?;
+
+            Ok(conn)
+        }
+    }
+
+    pub struct PooledConnection<'p> {
+        pool: &'p Pool,
+        conn: Option<Connection>,
+    }
+
+    impl PooledConnection<'_> {
+        /// Starts a transaction that takes the write lock up front, deferred
+        /// transactions that upgrade to writes can't be retried by the busy
+        /// handler.
+        pub fn write(&mut self) -> rusqlite::Result<Transaction<'_>> {
+            self.transaction_with_behavior(TransactionBehavior::Immediate)
+        }
+    }
+
+    impl Deref for PooledConnection<'_> {
+        type Target = Connection;
+
+        fn deref(&self) -> &Self::Target {
+            self.conn.as_ref().expect("connection taken before drop")
+        }
+    }
+
+    impl DerefMut for PooledConnection<'_> {
+        fn deref_mut(&mut self) -> &mut Self::Target {
+            self.conn.as_mut().expect("connection taken before drop")
+        }
+    }
+
+    impl Drop for PooledConnection<'_> {
+        fn drop(&mut self) {
+            let Some(conn) = self.conn.take() else {
+                return;
+            };
+
+            if let Ok(mut idle) = self.pool.idle.lock() {
+                idle.connections.push(conn);
+            }
+
+            self.pool.returned.notify_one();
+        }
+    }
+}
+
+mod auth {
+    use anyhow::{anyhow, Result};
+    use argon2::{
+        password_hash::{
+            rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString,
+        },
+        Argon2,
+    };
+    use oxhttp::model::{HeaderName, Request};
+    use rand::{distributions::Alphanumeric, Rng};
+
+    pub static SESSION_COOKIE: &str = "lumen_session";
+
+    /// How long a session lasts after logging in, in seconds.
+    pub static SESSION_AGE: i64 = 30 * 24 * 60 * 60;
+
+    pub fn hash(password: &str) -> Result<String> {
+        let salt = SaltString::generate(&mut OsRng);
+
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|err| anyhow!("failed to hash password: {}", err))
+    }
+
+    pub fn verify(password: &str, hash: &str) -> bool {
+        PasswordHash::new(hash)
+            .map(|hash| {
+                Argon2::default()
+                    .verify_password(password.as_bytes(), &hash)
+                    .is_ok()
+            })
+            .unwrap_or(false)
+    }
+
+    pub fn token() -> String {
+        OsRng
+            .sample_iter(&Alphanumeric)
+            .take(32)
+            .map(char::from)
+            .collect()
+    }
+
+    /// The session token sent along in the `Cookie` header.
+    pub fn session(request: &Request) -> Option<&str> {
+        let name = "cookie".parse::<HeaderName>().ok()?;
+
+        request
+            .header(&name)?
+            .to_str()
+            .ok()?
+            .split(';')
+            .filter_map(|cookie| cookie.trim().split_once('='))
+            .find(|(name, _)| *name == SESSION_COOKIE)
+            .map(|(_, token)| token)
+    }
+
+    pub fn cookie(token: &str, age: i64) -> String {
+        format!(
+            "{}={}; Path=/; Max-Age={}; HttpOnly; SameSite=Strict",
+            SESSION_COOKIE, token, age
+        )
+    }
+}
+
+mod events {
+    use std::{
+        sync::{Condvar, Mutex},
+        time::Duration,
+    };
+
+    use anyhow::{anyhow, Result};
+
+    /// Wakes up waiting event streams whenever a mutation has been committed,
+    /// the events themselves are read back from the change log.
+    pub struct Events {
+        generation: Mutex<u64>,
+        changed: Condvar,
+    }
+
+    impl Events {
+        pub fn new() -> Self {
+            Self {
+                generation: Mutex::new(0),
+                changed: Condvar::new(),
+            }
+        }
+
+        pub fn generation(&self) -> Result<u64> {
+            self.generation
+                .lock()
+                .map(|generation| *generation)
+                .map_err(|_| anyhow!("events lock poisoned"))
+        }
+
+        pub fn notify(&self) {
+            if let Ok(mut generation) = self.generation.lock() {
+                *generation += 1;
+            }
+
+            self.changed.notify_all();
+        }
+
+        /// Waits for a notification after `generation`, returns `false` if
+        /// `timeout` ran out first.
+        pub fn wait(&self, generation: u64, timeout: Duration) -> Result<bool> {
+            let guard = self
+                .generation
+                .lock()
+                .map_err(|_| anyhow!("events lock poisoned"))?;
+
+            let (_generation, result) = self
+                .changed
+                .wait_timeout_while(guard, timeout, |current| *current == generation)
+                .map_err(|_| anyhow!("events lock poisoned"))?;
+
+            Ok(!result.timed_out())
+        }
+    }
+}
+
+mod migrations {
+    use anyhow::{Context as _, Result};
+    use rusqlite::{Connection, Transaction};
+
+    /// Schema migrations in the order they are applied, `PRAGMA user_version`
+    /// records how many of them a database has already seen.
+    static MIGRATIONS: &[&str] = &[
+        // 1: initial schema
+        "
+        CREATE TABLE IF NOT EXISTS characters (name TEXT NOT NULL);
+        CREATE TABLE IF NOT EXISTS template (key TEXT NOT NULL, type TEXT NOT NULL);
+        CREATE TABLE IF NOT EXISTS stats (character TEXT NOT NULL, key TEXT NOT NULL, value INTEGER NOT NULL);
+        CREATE TABLE IF NOT EXISTS weapon_curves (name TEXT NOT NULL, type TEXT NOT NULL, a REAL NOT NULL, b REAL NOT NULL, c REAL NOT NULL, d REAL NOT NULL);
+        CREATE TABLE IF NOT EXISTS weapon_parts (name TEXT NOT NULL, details TEXT NOT NULL, type TEXT NOT NULL, rarity TEXT NOT NULL, company TEXT NOT NULL);
+        ",
+        // 2: primary keys, uniqueness and foreign keys. rows stored more than
+        // once are merged, parts that share a name but differ get the first
+        // ` (n)` suffix no other part has, everything else is rejected by
+        // `check_duplicates`. parts are
+        // numbered from 0 so their ids fit in a byte, legacy weapon ids hold
+        // positions in the original part table and are mapped to the part
+        // each row is merged into
+        "
+        CREATE TABLE weapon_parts_merged (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            details TEXT NOT NULL,
+            type TEXT NOT NULL,
+            rarity TEXT NOT NULL,
+            company TEXT NOT NULL,
+            n INTEGER NOT NULL
+        );
+        INSERT INTO weapon_parts_merged (id, name, details, type, rarity, company, n)
+            SELECT ROW_NUMBER() OVER (ORDER BY MIN(rowid)) - 1, name, details, type, rarity, company, ROW_NUMBER() OVER (PARTITION BY name, type, company ORDER BY MIN(rowid))
+            FROM weapon_parts GROUP BY name, details, type, rarity, company;
+
+        CREATE TABLE weapon_parts_legacy (
+            position INTEGER PRIMARY KEY,
+            part INTEGER NOT NULL
+        );
+        INSERT INTO weapon_parts_legacy (position, part)
+            SELECT ROW_NUMBER() OVER (ORDER BY p.rowid) - 1, (
+                SELECT m.id FROM weapon_parts_merged m
+                WHERE m.name = p.name AND m.details = p.details AND m.type = p.type AND m.rarity = p.rarity AND m.company = p.company
+            )
+            FROM weapon_parts p;
+
+        CREATE TABLE characters_new (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE
+        );
+        INSERT INTO characters_new (id, name) SELECT MIN(rowid), name FROM characters GROUP BY name ORDER BY MIN(rowid);
+
+        CREATE TABLE template_new (
+            id INTEGER PRIMARY KEY,
+            key TEXT NOT NULL UNIQUE,
+            type TEXT NOT NULL
+        );
+        INSERT INTO template_new (id, key, type) SELECT MIN(rowid), key, type FROM template GROUP BY key, type ORDER BY MIN(rowid);
+
+        CREATE TABLE stats_new (
+            id INTEGER PRIMARY KEY,
+            character INTEGER NOT NULL REFERENCES characters (id) ON DELETE CASCADE,
+            stat INTEGER NOT NULL REFERENCES template (id) ON DELETE CASCADE,
+            value INTEGER NOT NULL,
+            UNIQUE (character, stat)
+        );
+        INSERT INTO stats_new (character, stat, value)
+            SELECT c.id, t.id, s.value FROM stats s
+            JOIN characters_new c ON c.name = s.character
+            JOIN template_new t ON t.key = s.key
+            GROUP BY c.id, t.id, s.value
+            ORDER BY MIN(s.rowid);
+
+        CREATE TABLE weapon_curves_new (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE,
+            type TEXT NOT NULL,
+            a REAL NOT NULL,
+            b REAL NOT NULL,
+            c REAL NOT NULL,
+            d REAL NOT NULL
+        );
+        INSERT INTO weapon_curves_new (id, name, type, a, b, c, d)
+            SELECT MIN(rowid), name, type, a, b, c, d FROM weapon_curves
+            GROUP BY name, type, a, b, c, d
+            ORDER BY MIN(rowid);
+
+        CREATE TABLE weapon_parts_new (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            details TEXT NOT NULL,
+            type TEXT NOT NULL,
+            rarity TEXT NOT NULL,
+            company TEXT NOT NULL,
+            UNIQUE (name, type, company)
+        );
+        INSERT INTO weapon_parts_new (id, name, details, type, rarity, company)
+            WITH RECURSIVE suffixes (k) AS (
+                SELECT 2 UNION ALL SELECT k + 1 FROM suffixes WHERE k <= (SELECT COUNT(*) FROM weapon_parts_merged)
+            ),
+            free (name, type, company, k, n) AS (
+                SELECT g.name, g.type, g.company, s.k, ROW_NUMBER() OVER (PARTITION BY g.name, g.type, g.company ORDER BY s.k) + 1
+                FROM (SELECT DISTINCT name, type, company FROM weapon_parts_merged WHERE n > 1) g, suffixes s
+                WHERE NOT EXISTS (SELECT 1 FROM weapon_parts_merged o WHERE o.name = g.name || ' (' || s.k || ')' AND o.type = g.type AND o.company = g.company)
+            )
+            SELECT m.id, COALESCE(m.name || ' (' || f.k || ')', m.name), m.details, m.type, m.rarity, m.company
+            FROM weapon_parts_merged m
+            LEFT JOIN free f ON f.name = m.name AND f.type = m.type AND f.company = m.company AND f.n = m.n
+            ORDER BY m.id;
+
+        DROP TABLE stats;
+        DROP TABLE characters;
+        DROP TABLE template;
+        DROP TABLE weapon_curves;
+        DROP TABLE weapon_parts;
+        DROP TABLE weapon_parts_merged;
+
+        ALTER TABLE characters_new RENAME TO characters;
+        ALTER TABLE template_new RENAME TO template;
+        ALTER TABLE stats_new RENAME TO stats;
+        ALTER TABLE weapon_curves_new RENAME TO weapon_curves;
+        ALTER TABLE weapon_parts_new RENAME TO weapon_parts;
+        ",
+        // 3: stable part ids, weapon ids store a byte per part so an id is
+        // handed out once, stays with its part after that part is removed and
+        // can't grow past 255
+        "
+        CREATE TABLE weapon_parts_new (
+            id INTEGER PRIMARY KEY AUTOINCREMENT CHECK (id BETWEEN 0 AND 255),
+            name TEXT NOT NULL,
+            details TEXT NOT NULL,
+            type TEXT NOT NULL,
+            rarity TEXT NOT NULL,
+            company TEXT NOT NULL,
+            UNIQUE (name, type, company)
+        );
+        INSERT INTO weapon_parts_new (id, name, details, type, rarity, company) SELECT id, name, details, type, rarity, company FROM weapon_parts;
+
+        DROP TABLE weapon_parts;
+
+        ALTER TABLE weapon_parts_new RENAME TO weapon_parts;
+        ",
+        // 4: change log, the latest revision is the state revision
+        "
+        CREATE TABLE changes (
+            revision INTEGER PRIMARY KEY AUTOINCREMENT,
+            entity TEXT NOT NULL,
+            key TEXT NOT NULL,
+            removed INTEGER NOT NULL
+        );
+        ",
+        // 5: campaigns, everything that existed so far belongs to the default
+        // campaign. part ids are handed out per campaign from `part_seq`
+        "
+        CREATE TABLE campaigns (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE,
+            part_seq INTEGER NOT NULL DEFAULT 0
+        );
+        INSERT INTO campaigns (id, name, part_seq)
+            VALUES (1, 'default', COALESCE((SELECT seq FROM sqlite_sequence WHERE name = 'weapon_parts'), 0));
+
+        CREATE TABLE characters_new (
+            id INTEGER PRIMARY KEY,
+            campaign INTEGER NOT NULL REFERENCES campaigns (id) ON DELETE CASCADE,
+            name TEXT NOT NULL,
+            UNIQUE (campaign, name)
+        );
+        INSERT INTO characters_new (id, campaign, name) SELECT id, 1, name FROM characters;
+
+        CREATE TABLE template_new (
+            id INTEGER PRIMARY KEY,
+            campaign INTEGER NOT NULL REFERENCES campaigns (id) ON DELETE CASCADE,
+            key TEXT NOT NULL,
+            type TEXT NOT NULL,
+            UNIQUE (campaign, key)
+        );
+        INSERT INTO template_new (id, campaign, key, type) SELECT id, 1, key, type FROM template;
+
+        CREATE TABLE weapon_curves_new (
+            id INTEGER PRIMARY KEY,
+            campaign INTEGER NOT NULL REFERENCES campaigns (id) ON DELETE CASCADE,
+            name TEXT NOT NULL,
+            type TEXT NOT NULL,
+            a REAL NOT NULL,
+            b REAL NOT NULL,
+            c REAL NOT NULL,
+            d REAL NOT NULL,
+            UNIQUE (campaign, name)
+        );
+        INSERT INTO weapon_curves_new (id, campaign, name, type, a, b, c, d) SELECT id, 1, name, type, a, b, c, d FROM weapon_curves;
+
+        CREATE TABLE weapon_parts_new (
+            campaign INTEGER NOT NULL REFERENCES campaigns (id) ON DELETE CASCADE,
+            id INTEGER NOT NULL CHECK (id BETWEEN 0 AND 255),
+            name TEXT NOT NULL,
+            details TEXT NOT NULL,
+            type TEXT NOT NULL,
+            rarity TEXT NOT NULL,
+            company TEXT NOT NULL,
+            PRIMARY KEY (campaign, id),
+            UNIQUE (campaign, name, type, company)
+        );
+        INSERT INTO weapon_parts_new (campaign, id, name, details, type, rarity, company) SELECT 1, id, name, details, type, rarity, company FROM weapon_parts;
+
+        CREATE TABLE weapon_parts_legacy_new (
+            campaign INTEGER NOT NULL REFERENCES campaigns (id) ON DELETE CASCADE,
+            position INTEGER NOT NULL,
+            part INTEGER NOT NULL,
+            PRIMARY KEY (campaign, position)
+        );
+        INSERT INTO weapon_parts_legacy_new (campaign, position, part) SELECT 1, position, part FROM weapon_parts_legacy;
+
+        CREATE TABLE changes_new (
+            revision INTEGER PRIMARY KEY AUTOINCREMENT,
+            campaign INTEGER NOT NULL REFERENCES campaigns (id) ON DELETE CASCADE,
+            entity TEXT NOT NULL,
+            key TEXT NOT NULL,
+            removed INTEGER NOT NULL
+        );
+        INSERT INTO changes_new (revision, campaign, entity, key, removed) SELECT revision, 1, entity, key, removed FROM changes;
+
+        DROP TABLE characters;
+        DROP TABLE template;
+        DROP TABLE weapon_curves;
+        DROP TABLE weapon_parts;
+        DROP TABLE weapon_parts_legacy;
+        DROP TABLE changes;
+
+        ALTER TABLE characters_new RENAME TO characters;
+        ALTER TABLE template_new RENAME TO template;
+        ALTER TABLE weapon_curves_new RENAME TO weapon_curves;
+        ALTER TABLE weapon_parts_new RENAME TO weapon_parts;
+        ALTER TABLE weapon_parts_legacy_new RENAME TO weapon_parts_legacy;
+        ALTER TABLE changes_new RENAME TO changes;
+
+        CREATE INDEX changes_campaign ON changes (campaign, revision);
+        ",
+        // 6: accounts, sessions and the players characters are assigned to
+        "
+        CREATE TABLE users (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE,
+            password TEXT NOT NULL,
+            role TEXT NOT NULL
+        );
+
+        CREATE TABLE sessions (
+            token TEXT PRIMARY KEY,
+            user INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+            expires INTEGER NOT NULL
+        );
+
+        ALTER TABLE characters ADD COLUMN owner INTEGER REFERENCES users (id) ON DELETE SET NULL;
+        ",
+        // 7: the history of stat and character changes, kept for undo and redo
+        "
+        CREATE TABLE history (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            campaign INTEGER NOT NULL REFERENCES campaigns (id) ON DELETE CASCADE,
+            time INTEGER NOT NULL,
+            actor TEXT,
+            action TEXT NOT NULL,
+            character TEXT,
+            stat TEXT,
+            before TEXT NOT NULL,
+            after TEXT NOT NULL,
+            status TEXT NOT NULL
+        );
+
+        CREATE INDEX history_campaign ON history (campaign, status, id);
+        ",
+        // 8: stat types with settings, text values are kept apart from numbers
+        "
+        ALTER TABLE template ADD COLUMN options TEXT;
+        ALTER TABLE template ADD COLUMN min INTEGER;
+        ALTER TABLE template ADD COLUMN max INTEGER;
+
+        CREATE TABLE stats_new (
+            id INTEGER PRIMARY KEY,
+            character INTEGER NOT NULL REFERENCES characters (id) ON DELETE CASCADE,
+            stat INTEGER NOT NULL REFERENCES template (id) ON DELETE CASCADE,
+            value INTEGER,
+            text TEXT,
+            UNIQUE (character, stat),
+            CHECK ((value IS NULL) <> (text IS NULL))
+        );
+        INSERT INTO stats_new (id, character, stat, value) SELECT id, character, stat, value FROM stats;
+
+        DROP TABLE stats;
+        ALTER TABLE stats_new RENAME TO stats;
+        ",
+        // 9: formula stats
+        "
+        ALTER TABLE template ADD COLUMN formula TEXT;
+        ",
+        // 10: the rolls made in a campaign
+        "
+        CREATE TABLE rolls (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            campaign INTEGER NOT NULL REFERENCES campaigns (id) ON DELETE CASCADE,
+            time INTEGER NOT NULL,
+            actor TEXT,
+            character TEXT,
+            expr TEXT NOT NULL,
+            seed INTEGER NOT NULL,
+            total INTEGER NOT NULL,
+            terms TEXT NOT NULL,
+            breakdown TEXT NOT NULL
+        );
+
+        CREATE INDEX rolls_campaign ON rolls (campaign, id);
+        ",
+        // 11: named templates, stats and characters belong to one, templates
+        // also have the stats of the template they inherit from
+        "
+        CREATE TABLE templates (
+            id INTEGER PRIMARY KEY,
+            campaign INTEGER NOT NULL REFERENCES campaigns (id) ON DELETE CASCADE,
+            name TEXT NOT NULL,
+            parent INTEGER REFERENCES templates (id),
+            UNIQUE (campaign, name)
+        );
+        INSERT INTO templates (campaign, name) SELECT id, 'base' FROM campaigns ORDER BY id;
+
+        ALTER TABLE template ADD COLUMN template INTEGER REFERENCES templates (id);
+        UPDATE template SET template = (SELECT id FROM templates WHERE templates.campaign = template.campaign);
+
+        ALTER TABLE characters ADD COLUMN template INTEGER REFERENCES templates (id);
+        UPDATE characters SET template = (SELECT id FROM templates WHERE templates.campaign = characters.campaign);
+        ",
+        // 12: how template stats are shown and the value characters start
+        // with, `default_value` holds a number or text like a stat does
+        "
+        ALTER TABLE template ADD COLUMN default_value;
+        ALTER TABLE template ADD COLUMN label TEXT;
+        ALTER TABLE template ADD COLUMN description TEXT;
+        ALTER TABLE template ADD COLUMN position INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE template ADD COLUMN category TEXT;
+        ALTER TABLE template ADD COLUMN visibility TEXT NOT NULL DEFAULT 'public';
+        ",
+        // 13: the weapons characters carry, stored by their id
+        "
+        CREATE TABLE inventory (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            character INTEGER NOT NULL REFERENCES characters (id) ON DELETE CASCADE,
+            weapon TEXT NOT NULL,
+            nickname TEXT,
+            equipped INTEGER NOT NULL DEFAULT 0,
+            notes TEXT NOT NULL DEFAULT ''
+        );
+
+        CREATE INDEX inventory_character ON inventory (character, id);
+        ",
+        // 14: the weapon library, weapons are kept as they were displayed when
+        // saved, the columns next to the snapshot are there to filter on
+        "
+        CREATE TABLE weapons (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            campaign INTEGER NOT NULL REFERENCES campaigns (id) ON DELETE CASCADE,
+            time INTEGER NOT NULL,
+            weapon TEXT NOT NULL,
+            name TEXT NOT NULL,
+            level INTEGER NOT NULL,
+            rarity TEXT NOT NULL,
+            type TEXT NOT NULL,
+            company TEXT NOT NULL,
+            snapshot TEXT NOT NULL
+        );
+
+        CREATE INDEX weapons_campaign ON weapons (campaign, id);
+
+        CREATE TABLE weapon_tags (
+            weapon INTEGER NOT NULL REFERENCES weapons (id) ON DELETE CASCADE,
+            tag TEXT NOT NULL,
+            PRIMARY KEY (weapon, tag)
+        );
+        ",
+        // 15: how often each weapon type is generated, a weight holds from its
+        // level up to the next level the type has a weight for
+        "
+        CREATE TABLE weapon_weights (
+            id INTEGER PRIMARY KEY,
+            campaign INTEGER NOT NULL REFERENCES campaigns (id) ON DELETE CASCADE,
+            type TEXT NOT NULL,
+            level INTEGER NOT NULL,
+            weight INTEGER NOT NULL,
+            UNIQUE (campaign, type, level)
+        );
+
+        INSERT INTO weapon_weights (campaign, type, level, weight)
+            SELECT c.id, w.* FROM campaigns c, (VALUES
+                ('assault rifle', 0, 2),
+                ('grenade launcher', 0, 1),
+                ('pistol', 0, 2),
+                ('rocket launcher', 20, 1),
+                ('shotgun', 0, 2),
+                ('sniper rifle', 0, 1),
+                ('submachine gun', 0, 2)
+            ) w
+            WHERE c.id IN (SELECT campaign FROM weapon_curves)
+            ORDER BY c.id;
+        ",
+        // 16: the rarity drop table, how often parts of each rarity come up
+        // from a level on
+        "
+        CREATE TABLE rarity_weights (
+            id INTEGER PRIMARY KEY,
+            campaign INTEGER NOT NULL REFERENCES campaigns (id) ON DELETE CASCADE,
+            rarity TEXT NOT NULL,
+            level INTEGER NOT NULL,
+            weight INTEGER NOT NULL,
+            UNIQUE (campaign, rarity, level)
+        );
+
+        INSERT INTO rarity_weights (campaign, rarity, level, weight)
+            SELECT c.id, w.* FROM campaigns c, (VALUES
+                ('common', 0, 6),
+                ('common', 20, 3),
+                ('uncommon', 0, 4),
+                ('rare', 8, 2),
+                ('rare', 20, 3),
+                ('epic', 12, 1),
+                ('epic', 20, 2),
+                ('legendary', 20, 1),
+                ('unique', 20, 1)
+            ) w
+            WHERE c.id IN (SELECT campaign FROM weapon_curves)
+            ORDER BY c.id;
+        ",
+        // 17: rolls that refer to stats only the gm may see, rolls made so far
+        // are marked when their expression names one
+        "
+        ALTER TABLE rolls ADD COLUMN hidden INTEGER NOT NULL DEFAULT 0;
+        UPDATE rolls SET hidden = 1 WHERE EXISTS (SELECT 1 FROM template t WHERE t.campaign = rolls.campaign AND t.visibility = 'gm' AND instr(rolls.expr, '@' || t.key) > 0);
+        ",
+    ];
+
+    /// Lists stats, template keys and weapon curves the original schema
+    /// stored more than once with different values, migration 2 can't pick
+    /// one of them.
+    fn check_duplicates(trans: &Transaction) -> Result<()> {
+        let conflicts = trans
+            .prepare(
+                "SELECT 'template key `' || key || '`' FROM template
+                 GROUP BY key HAVING COUNT(DISTINCT type) > 1
+                 UNION ALL
+                 SELECT 'stat `' || key || '` of `' || character || '`' FROM stats
+                 GROUP BY character, key HAVING COUNT(DISTINCT value) > 1
+                 UNION ALL
+                 SELECT 'weapon curve `' || name || '`' FROM (
+                     SELECT DISTINCT name, type, a, b, c, d FROM weapon_curves
+                 )
+                 GROUP BY name HAVING COUNT(*) > 1",
+            )?
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        anyhow::ensure!(
+            conflicts.is_empty(),
+            "can't upgrade the database, these are stored more than once with different values: {}",
+            conflicts.join(", ")
+        );
+
+        let orphans = trans
+            .prepare(
+                "SELECT DISTINCT 'stat `' || key || '` of `' || character || '`' FROM stats s
+                 WHERE NOT EXISTS (SELECT 1 FROM characters c WHERE c.name = s.character)
+                 OR NOT EXISTS (SELECT 1 FROM template t WHERE t.key = s.key)",
+            )?
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        anyhow::ensure!(
+            orphans.is_empty(),
+            "can't upgrade the database, these belong to a character or template key that doesn't exist: {}",
+            orphans.join(", ")
+        );
+
+        let parts = trans.query_row(
+            "SELECT COUNT(*) FROM (SELECT DISTINCT name, details, type, rarity, company FROM weapon_parts)",
+            [],
+            |row| row.get::<_, usize>(0),
+        )?;
+
+        anyhow::ensure!(
+            parts <= crate::PART_LIMIT,
+            "can't upgrade the database, it holds {} weapon parts and weapon ids fit at most {}",
+            parts,
+            crate::PART_LIMIT
+        );
+
+        Ok(())
+    }
+
+    pub fn run(conn: &mut Connection) -> Result<()> {
+        let version = conn.query_row("PRAGMA user_version", [], |row| row.get::<_, usize>(0))?;
+
+        anyhow::ensure!(
+            version <= MIGRATIONS.len(),
+            "database schema version {} is newer than the supported version {}",
+            version,
+            MIGRATIONS.len()
+        );
+
+        // foreign keys have to be off while tables are being rebuilt, this
+        // can't be changed inside of a transaction
+        conn.execute_batch("PRAGMA foreign_keys = OFF")?;
+
+        for (index, sql) in MIGRATIONS.iter().enumerate().skip(version) {
+            let trans = conn.transaction()?;
+
+            if index == 1 {
+                check_duplicates(&trans)?;
+            }
+
+            trans
+                .execute_batch(sql)
+                .with_context(|| format!("failed to apply migration {}", index + 1))?;
+
+            let violations = trans
+                .prepare("PRAGMA foreign_key_check")?
+                .query_map([], |_| Ok(()))?
+                .count();
+
+            anyhow::ensure!(
+                violations == 0,
+                "migration {} left {} foreign key violations",
+                index + 1,
+                violations
+            );
+
+            trans.pragma_update(None, "user_version", index + 1)?;
+
+            trans.commit()?;
+        }
+
+        conn.execute_batch("PRAGMA foreign_keys = ON")?;
+
+        Ok(())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use rusqlite::Connection;
+
+        use super::{run, MIGRATIONS};
+
+        fn original(data: &str) -> Connection {
+            let conn = Connection::open_in_memory().unwrap();
+            conn.execute_batch(MIGRATIONS[0]).unwrap();
+            conn.execute_batch(data).unwrap();
+            conn.pragma_update(None, "user_version", 1).unwrap();
+            conn
+        }
+
+        #[test]
+        fn duplicates_are_merged_or_renamed() {
+            let mut conn = original(
+                "
+                INSERT INTO characters VALUES ('Ash'), ('Ash');
+                INSERT INTO template VALUES ('hp', 'number'), ('hp', 'number');
+                INSERT INTO stats VALUES ('Ash', 'hp', 3), ('Ash', 'hp', 3);
+                INSERT INTO weapon_parts VALUES
+                    ('Grip', 'a', 'Grip', 'Common', 'Maliwan'),
+                    ('Grip', 'a', 'Grip', 'Common', 'Maliwan'),
+                    ('Grip', 'b', 'Grip', 'Rare', 'Maliwan'),
+                    ('Grip (2)', 'c', 'Grip', 'Common', 'Maliwan');
+                INSERT INTO weapon_curves VALUES
+                    ('pistol', 'linear', 1, 2, 0, 0),
+                    ('pistol', 'linear', 1, 2, 0, 0);
+                ",
+            );
+
+            run(&mut conn).unwrap();
+
+            let count = |sql: &str| conn.query_row(sql, [], |row| row.get::<_, i64>(0)).unwrap();
+            assert_eq!(count("SELECT COUNT(*) FROM characters"), 1);
+            assert_eq!(count("SELECT COUNT(*) FROM template"), 1);
+            assert_eq!(count("SELECT COUNT(*) FROM stats"), 1);
+            assert_eq!(count("SELECT COUNT(*) FROM weapon_curves"), 1);
+
+            let parts = conn
+                .prepare("SELECT name, details FROM weapon_parts ORDER BY id")
+                .unwrap()
+                .query_map([], |row| {
+                    Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+                })
+                .unwrap()
+                .collect::<rusqlite::Result<Vec<_>>>()
+                .unwrap();
+
+            assert_eq!(
+                parts,
+                [
+                    ("Grip".to_owned(), "a".to_owned()),
+                    ("Grip (3)".to_owned(), "b".to_owned()),
+                    ("Grip (2)".to_owned(), "c".to_owned())
+                ]
+            );
+
+            // legacy positions still point at the part each row was merged into
+            let legacy = conn
+                .prepare("SELECT l.position, p.details FROM weapon_parts_legacy l JOIN weapon_parts p ON p.id = l.part ORDER BY l.position")
+                .unwrap()
+                .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))
+                .unwrap()
+                .collect::<rusqlite::Result<Vec<_>>>()
+                .unwrap();
+
+            assert_eq!(
+                legacy,
+                [
+                    (0, "a".to_owned()),
+                    (1, "a".to_owned()),
+                    (2, "b".to_owned()),
+                    (3, "c".to_owned())
+                ]
+            );
+        }
+
+        #[test]
+        fn part_ids_are_renumbered() {
+            // parts that were added and removed many times left high rowids
+            let mut conn = original(
+                "
+                WITH RECURSIVE n (i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 400)
+                INSERT INTO weapon_parts SELECT 'Grip', i, 'Grip', 'Common', 'Maliwan' FROM n;
+                DELETE FROM weapon_parts WHERE rowid <= 398;
+                ",
+            );
+
+            run(&mut conn).unwrap();
+
+            let parts = conn
+                .prepare("SELECT l.position, p.id, p.details FROM weapon_parts_legacy l JOIN weapon_parts p ON p.id = l.part ORDER BY l.position")
+                .unwrap()
+                .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?, row.get::<_, String>(2)?)))
+                .unwrap()
+                .collect::<rusqlite::Result<Vec<_>>>()
+                .unwrap();
+
+            assert_eq!(parts, [(0, 0, "399".to_owned()), (1, 1, "400".to_owned())]);
+        }
+
+        #[test]
+        fn conflicting_duplicates_abort() {
+            let mut conn = original(
+                "
+                INSERT INTO characters VALUES ('Ash');
+                INSERT INTO template VALUES ('hp', 'number');
+                INSERT INTO stats VALUES ('Ash', 'hp', 3), ('Ash', 'hp', 4);
+                INSERT INTO weapon_curves VALUES
+                    ('pistol', 'linear', 1, 2, 0, 0),
+                    ('pistol', 'linear', 1, 3, 0, 0);
+                ",
+            );
+
+            let error = run(&mut conn).unwrap_err().to_string();
+
+            assert!(error.contains("stat `hp` of `Ash`"), "{}", error);
+            assert!(error.contains("weapon curve `pistol`"), "{}", error);
+            let version = conn.query_row("PRAGMA user_version", [], |row| row.get::<_, i64>(0));
+            assert_eq!(version.unwrap(), 1);
+        }
+    }
+}
+
+mod error {
+    use std::fmt;
+
+    use oxhttp::model::{HeaderName, Response, Status};
+    use rusqlite::ErrorCode;
+
+    use crate::models::WeaponError;
+
+    #[derive(Debug)]
+    pub enum ApiError {
+        MissingParameter(String),
+        InvalidValue(String),
+        NotFound(String),
+        Unauthorized(String),
+        Forbidden(String),
+        /// Holds the value of the `Allow` header.
+        MethodNotAllowed(String),
+        Conflict(String),
+        Unprocessable(String),
+        Internal(anyhow::Error),
+    }
+
+    impl ApiError {
+        pub fn invalid(kind: &str, value: &str) -> Self {
+            ApiError::InvalidValue(format!("invalid {} `{}`", kind, value))
+        }
+
+        pub fn status(&self) -> Status {
+            match self {
+                ApiError::MissingParameter(_) | ApiError::InvalidValue(_) => Status::BAD_REQUEST,
+                ApiError::NotFound(_) => Status::NOT_FOUND,
+                ApiError::Unauthorized(_) => Status::UNAUTHORIZED,
+                ApiError::Forbidden(_) => Status::FORBIDDEN,
+                ApiError::MethodNotAllowed(_) => Status::METHOD_NOT_ALLOWED,
+                ApiError::Conflict(_) => Status::CONFLICT,
+                ApiError::Unprocessable(_) => Status::UNPROCESSABLE_CONTENT,
+                ApiError::Internal(_) => Status::INTERNAL_SERVER_ERROR,
+            }
+        }
+
+        /// A stable identifier for the frontend to match on, unlike the message.
+        pub fn code(&self) -> &'static str {
+            match self {
+                ApiError::MissingParameter(_) => "missing_parameter",
+                ApiError::InvalidValue(_) => "invalid_value",
+                ApiError::NotFound(_) => "not_found",
+                ApiError::Unauthorized(_) => "unauthorized",
+                ApiError::Forbidden(_) => "forbidden",
+                ApiError::MethodNotAllowed(_) => "method_not_allowed",
+                ApiError::Conflict(_) => "conflict",
+                ApiError::Unprocessable(_) => "unprocessable",
+                ApiError::Internal(_) => "internal",
+            }
+        }
+
+        pub fn response(&self) -> Response {
+            if let ApiError::Internal(err) = self {
+                eprintln!("internal error: {:?}", err);
+            }
+
+            let body = serde_json::json!({
+                "error": {
+                    "code": self.code(),
+                    "message": self.to_string(),
+                }
+            });
+
+            let mut builder =
+                Response::builder(self.status()).with_header("Content-Type", "application/json");
+
+            if let ApiError::MethodNotAllowed(allow) = self {
+                builder = builder
+                    .and_then(|builder| builder.with_header(HeaderName::ALLOW, allow.as_str()));
+            }
+
+            builder
+                .map(|builder| builder.with_body(body.to_string()))
+                .unwrap_or_else(|_| Response::builder(Status::INTERNAL_SERVER_ERROR).build())
+        }
+    }
+
+    impl fmt::Display for ApiError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                ApiError::MissingParameter(message)
+                | ApiError::InvalidValue(message)
+                | ApiError::NotFound(message)
+                | ApiError::Unauthorized(message)
+                | ApiError::Forbidden(message)
+                | ApiError::Conflict(message)
+                | ApiError::Unprocessable(message) => write!(f, "{}", message),
+                // the cause is logged but never sent to the client
+                ApiError::MethodNotAllowed(allow) => write!(f, "method not allowed, use {}", allow),
+                ApiError::Internal(_) => write!(f, "internal server error"),
+            }
+        }
+    }
+
+    impl std::error::Error for ApiError {}
+
+    impl From<anyhow::Error> for ApiError {
+        fn from(err: anyhow::Error) -> Self {
+            let err = match err.downcast::<ApiError>() {
+                Ok(err) => return err,
+                Err(err) => err,
+            };
+
+            let err = match err.downcast::<WeaponError>() {
+                Ok(err) => return err.into(),
+                Err(err) => err,
+            };
+
+            if let Some(rusqlite::Error::SqliteFailure(sqlite_err, _)) = err.downcast_ref() {
+                if sqlite_err.code == ErrorCode::ConstraintViolation {
+                    return ApiError::Conflict("conflicts with existing data".to_string());
+                }
+            }
+
+            ApiError::Internal(err)
+        }
+    }
+
+    pub trait OrConflict<T> {
+        /// Turns constraint violations into a [`ApiError::Conflict`] with the given message.
+        fn or_conflict<F: FnOnce() -> String>(self, message: F) -> anyhow::Result<T>;
+    }
+
+    impl<T> OrConflict<T> for rusqlite::Result<T> {
+        fn or_conflict<F: FnOnce() -> String>(self, message: F) -> anyhow::Result<T> {
+            match self {
+                Err(rusqlite::Error::SqliteFailure(err, _))
+                    if err.code == ErrorCode::ConstraintViolation =>
+                {
+                    Err(ApiError::Conflict(message()).into())
+                }
+                other => other.map_err(Into::into),
+            }
+        }
+    }
+}
+
+mod handlers {
+    use std::{
+        io::{self, Read},
+        sync::Arc,
+        time::Duration,
+    };
+
+    use anyhow::Result;
+    use oxhttp::model::{Body, Response, Status};
+
+    use crate::{
+        auth,
+        error::ApiError,
+        events::Events,
+        models::{
+            Action, Company, HistoryFilter, Id, LootFilter, Pairs, Params, PartType, Payload,
+            Rarity, Role, StatMeta, StatType, StatValue, User, Weapon, WeaponError, WeaponFilter,
+        },
+        pool::Pool,
+        AsConn, Context, Db, CSS, INDEX, JS, LOGIN,
+    };
+
+    fn json<S: serde::Serialize>(s: S) -> Result<Response> {
+        let body = serde_json::to_string(&s)?;
+
+        Ok(Response::builder(Status::OK)
+            .with_header("Content-Type", "application/json")?
+            .with_body(body))
+    }
+
+    /// The revision a mutation started from, when the client asked for
+    /// `?delta=true` instead of the full state.
+    fn since<C: AsConn>(conn: C, campaign: i64, pairs: &Pairs<'_>) -> Result<Option<i64>> {
+        let delta = match pairs.get("delta") {
+            Some(delta) => delta
+                .parse::<bool>()
+                .map_err(|_| ApiError::invalid("delta", delta))?,
+            None => false,
+        };
+
+        if delta {
+            Ok(Some(Db::revision(conn, campaign)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// The name history entries are recorded under.
+    fn actor(user: Option<&User>) -> Option<&str> {
+        user.map(|user| user.name.as_str())
+    }
+
+    /// Whether `user` sees the stats only the gm may see.
+    fn gm(user: Option<&User>) -> bool {
+        user.is_some_and(|user| user.role == Role::Gm)
+    }
+
+    fn respond<C: AsConn>(
+        conn: C,
+        user: Option<&User>,
+        campaign: i64,
+        since: Option<i64>,
+    ) -> Result<Response> {
+        match since {
+            Some(since) => json(Db::delta(conn, campaign, since, gm(user))?),
+            None => json(Db::state(conn, campaign, None, gm(user))?),
+        }
+    }
+
+    pub fn index(
+        _ctx: &Context,
+        _: Option<&User>,
+        _params: Params<'_>,
+        _pairs: Pairs<'_>,
+        _: Payload,
+    ) -> Result<Response> {
+        Ok(Response::builder(Status::OK)
+            .with_header("Content-Type", "text/html; charset=utf-8")?
+            .with_body(INDEX))
+    }
+
+    pub fn login_page(
+        _ctx: &Context,
+        _: Option<&User>,
+        _params: Params<'_>,
+        _pairs: Pairs<'_>,
+        _: Payload,
+    ) -> Result<Response> {
+        Ok(Response::builder(Status::OK)
+            .with_header("Content-Type", "text/html; charset=utf-8")?
+            .with_body(LOGIN))
+    }
+
+    pub fn css(
+        _ctx: &Context,
+        _: Option<&User>,
+        _params: Params<'_>,
+        _pairs: Pairs<'_>,
+        _: Payload,
+    ) -> Result<Response> {
+        Ok(Response::builder(Status::OK)
+            .with_header("Content-Type", "text/css; charset=utf-8")?
+            .with_body(CSS))
+    }
+
+    pub fn js(
+        _ctx: &Context,
+        _: Option<&User>,
+        _params: Params<'_>,
+        _pairs: Pairs<'_>,
+        _: Payload,
+    ) -> Result<Response> {
+        Ok(Response::builder(Status::OK)
+            .with_header("Content-Type", "text/javascript; charset=utf-8")?
+            .with_body(JS))
+    }
+
+    pub fn login(
+        ctx: &Context,
+        _: Option<&User>,
+        _: Params<'_>,
+        _: Pairs<'_>,
+        payload: Payload,
+    ) -> Result<Response> {
+        let mut conn = ctx.pool.get()?;
+        let trans = conn.write()?;
+
+        let name = payload.find("name")?;
+        let password = payload.find("password")?;
+        let (token, user) = Db::login(&trans, name, password)?;
+
+        trans.commit()?;
+
+        Ok(Response::builder(Status::OK)
+            .with_header("Content-Type", "application/json")?
+            .with_header("Set-Cookie", auth::cookie(&token, auth::SESSION_AGE))?
+            .with_body(serde_json::to_string(&user)?))
+    }
+
+    pub fn logout(ctx: &Context, token: Option<&str>) -> Result<Response> {
+        if let Some(token) = token {
+            let conn = ctx.pool.get()?;
+
+            Db::logout(&conn, token)?;
+        }
+
+        Ok(Response::builder(Status::OK)
+            .with_header("Content-Type", "application/json")?
+            .with_header("Set-Cookie", auth::cookie("", 0))?
+            .with_body("null"))
+    }
+
+    pub fn me(
+        _ctx: &Context,
+        user: Option<&User>,
+        _: Params<'_>,
+        _: Pairs<'_>,
+        _: Payload,
+    ) -> Result<Response> {
+        json(user)
+    }
+
+    pub fn user_list(
+        ctx: &Context,
+        _: Option<&User>,
+        _: Params<'_>,
+        _: Pairs<'_>,
+        _: Payload,
+    ) -> Result<Response> {
+        let conn = ctx.pool.get()?;
+
+        json(Db::users(&conn)?)
+    }
+
+    pub fn user_new(
+        ctx: &Context,
+        _: Option<&User>,
+        _: Params<'_>,
+        _: Pairs<'_>,
+        payload: Payload,
+    ) -> Result<Response> {
+        let mut conn = ctx.pool.get()?;
+        let trans = conn.write()?;
+
+        let name = payload.find("name")?;
+        let password = payload.find("password")?;
+        let role = Role::try_from(payload.find("role")?)?;
+
+        if password.is_empty() {
+            return Err(ApiError::invalid("password", "").into());
+        }
+
+        Db::add_user(&trans, name, password, role)?;
+
+        trans.commit()?;
+
+        json(Db::users(&conn)?)
+    }
+
+    pub fn user_remove(
+        ctx: &Context,
+        _: Option<&User>,
+        params: Params<'_>,
+        _: Pairs<'_>,
+        _: Payload,
+    ) -> Result<Response> {
+        let mut conn = ctx.pool.get()?;
+        let trans = conn.write()?;
+
+        let name = params.find("name")?;
+        Db::remove_user(&trans, name)?;
+
+        trans.commit()?;
+
+        json(Db::users(&conn)?)
+    }
+
+    pub fn campaign_list(
+        ctx: &Context,
+        _: Option<&User>,
+        _: Params<'_>,
+        _: Pairs<'_>,
+        _: Payload,
+    ) -> Result<Response> {
+        let conn = ctx.pool.get()?;
+
+        json(Db::campaigns(&conn)?)
+    }
+
+    pub fn campaign_new(
+        ctx: &Context,
+        _: Option<&User>,
+        _: Params<'_>,
+        _: Pairs<'_>,
+        payload: Payload,
+    ) -> Result<Response> {
+        let mut conn = ctx.pool.get()?;
+        let trans = conn.write()?;
+
+        let name = payload.find("name")?;
+        Db::add_campaign(&trans, name)?;
+
+        trans.commit()?;
+        ctx.events.notify();
+
+        json(Db::campaigns(&conn)?)
+    }
+
+    pub fn campaign_remove(
+        ctx: &Context,
+        _: Option<&User>,
+        params: Params<'_>,
+        _: Pairs<'_>,
+        _: Payload,
+    ) -> Result<Response> {
+        let mut conn = ctx.pool.get()?;
+        let trans = conn.write()?;
+
+        let name = params.find("campaign")?;
+        Db::remove_campaign(&trans, name)?;
+
+        trans.commit()?;
+        ctx.events.notify();
+
+        json(Db::campaigns(&conn)?)
+    }
+
+    pub fn campaign_clone(
+        ctx: &Context,
+        _: Option<&User>,
+        params: Params<'_>,
+        _: Pairs<'_>,
+        payload: Payload,
+    ) -> Result<Response> {
+        let mut conn = ctx.pool.get()?;
+        let trans = conn.write()?;
+        let campaign = Db::campaign(&trans, params.find("campaign")?)?;
+
+        let name = payload.find("name")?;
+        Db::clone_campaign(&trans, campaign, name)?;
+
+        trans.commit()?;
+        ctx.events.notify();
+
+        json(Db::campaigns(&conn)?)
+    }
+
+    pub fn state(
+        ctx: &Context,
+        user: Option<&User>,
+        params: Params<'_>,
+        pairs: Pairs<'_>,
+        _: Payload,
+    ) -> Result<Response> {
+        let conn = ctx.pool.get()?;
+        let campaign = Db::campaign(&conn, params.find("campaign")?)?;
+
+        match pairs.get("since") {
+            Some(since) => {
+                let since = since
+                    .parse::<i64>()
+                    .map_err(|_| ApiError::invalid("revision", since))?;
+
+                json(Db::delta(&conn, campaign, since, gm(user))?)
+            }
+            None => json(Db::state(&conn, campaign, None, gm(user))?),
+        }
+    }
+
+    /// How long an event stream waits for a change before sending a keep-alive.
+    static EVENTS_WAIT: Duration = Duration::from_secs(25);
+
+    /// Milliseconds a client waits before reconnecting a dropped stream.
+    static EVENTS_RETRY: u64 = 250;
+
+    /// `oxhttp` writes responses through a buffer this large that is only
+    /// flushed once a write doesn't fit in it anymore. Events are padded with a
+    /// comment until the writes after them overflow it, `EventStream` keeps
+    /// track of what's buffered to pad no more than that.
+    static WRITE_BUFFER: usize = 8 * 1024;
+
+    /// Streamed bodies are read in chunks of up to `CHUNK_MAX` bytes, reading
+    /// goes on until a chunk holds at least `CHUNK_MIN`.
+    static CHUNK_MAX: usize = 4096;
+    static CHUNK_MIN: usize = 1024;
+
+    /// Server-sent events, the response streams every change as it's
+    /// committed. `EventSource` reconnects on its own and sends the last
+    /// revision back in `Last-Event-ID`.
+    pub fn events(
+        ctx: &Context,
+        user: Option<&User>,
+        params: Params<'_>,
+        last: Option<&str>,
+    ) -> Result<Response> {
+        let last = last
+            .map(|last| {
+                last.parse::<i64>()
+                    .map_err(|_| ApiError::invalid("event id", last))
+            })
+            .transpose()?;
+
+        let campaign = {
+            let conn = ctx.pool.get()?;
+
+            Db::campaign(&conn, params.find("campaign")?)?
+        };
+
+        // the response head is still buffered when the body starts, two full
+        // chunks overflow the buffer at the same write whatever its size
+        let retry = format!("retry: {}\n\n", EVENTS_RETRY);
+        let buffered = written(0, retry.len(), 2 * CHUNK_MAX).unwrap_or_default();
+
+        let stream = EventStream {
+            pool: Arc::clone(&ctx.pool),
+            events: Arc::clone(&ctx.events),
+            campaign,
+            gm: gm(user),
+            last,
+            pending: pad(retry, 2 * CHUNK_MAX),
+            offset: 0,
+            buffered,
+        };
+
+        Ok(Response::builder(Status::OK)
+            .with_header("Content-Type", "text/event-stream")?
+            .with_header("Cache-Control", "no-cache")?
+            .with_body(Body::from_read(stream)))
+    }
+
+    /// The body of an event stream, reads block until the next event is due.
+    struct EventStream {
+        pool: Arc<Pool>,
+        events: Arc<Events>,
+        campaign: i64,
+        gm: bool,
+        /// The revision the client has seen.
+        last: Option<i64>,
+        pending: Vec<u8>,
+        offset: usize,
+        /// What `oxhttp` holds back once `pending` is read.
+        buffered: usize,
+    }
+
+    impl EventStream {
+        /// Waits for the next event, a keep-alive is sent when nothing
+        /// changed for `EVENTS_WAIT`.
+        fn next(&mut self) -> Result<String> {
+            loop {
+                let generation = self.events.generation()?;
+
+                {
+                    let conn = self.pool.get()?;
+                    let revision = Db::revision(&conn, self.campaign)?;
+
+                    match self.last {
+                        Some(last) if last == revision => {}
+                        Some(last) if last < revision => {
+                            let delta = Db::delta(&conn, self.campaign, last, self.gm)?;
+                            self.last = Some(delta.revision);
+
+                            return event("delta", delta.revision, &delta);
+                        }
+                        // new clients, and clients that are ahead of a database
+                        // that has been replaced, start over from the full state
+                        _ => {
+                            let state = Db::state(&conn, self.campaign, None, self.gm)?;
+                            self.last = Some(state.revision);
+
+                            return event("state", state.revision, &state);
+                        }
+                    }
+                }
+
+                if !self.events.wait(generation, EVENTS_WAIT)? {
+                    return Ok(": keep-alive\n\n".to_string());
+                }
+            }
+        }
+    }
+
+    impl Read for EventStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.offset == self.pending.len() {
+                let event = self.next().map_err(io::Error::other)?;
+                let (len, buffered) = padded(self.buffered, event.len());
+
+                self.pending = pad(event, len);
+                self.offset = 0;
+                self.buffered = buffered;
+            }
+
+            let len = buf.len().min(self.pending.len() - self.offset);
+            buf[..len].copy_from_slice(&self.pending[self.offset..self.offset + len]);
+            self.offset += len;
+
+            Ok(len)
+        }
+    }
+
+    /// `message` followed by a comment that makes it `len` bytes long.
+    fn pad(mut message: String, len: usize) -> Vec<u8> {
+        if len > message.len() {
+            let spaces = len - message.len() - 2;
+            message = format!("{}:{}\n", message, " ".repeat(spaces));
+        }
+
+        message.into_bytes()
+    }
+
+    /// How long a message holding an `event` bytes long event has to be for
+    /// the event to be sent right away, and what's left buffered after it.
+    fn padded(buffered: usize, event: usize) -> (usize, usize) {
+        let mut len = event;
+
+        loop {
+            // a comment is at least the `:` and a newline
+            if len - event != 1 {
+                if let Some(buffered) = written(buffered, event, len) {
+                    return (len, buffered);
+                }
+            }
+
+            len += 1;
+        }
+    }
+
+    /// Writes a `len` bytes long message the way `oxhttp` does to a buffer
+    /// holding `buffered` bytes, returns what's buffered afterwards when the
+    /// first `event` bytes of it were flushed out.
+    fn written(mut buffered: usize, event: usize, len: usize) -> Option<usize> {
+        let mut offset = 0;
+        let mut waiting = false;
+        let mut flushed = false;
+
+        while offset < len {
+            let chunk = (len - offset).min(CHUNK_MAX);
+
+            // a shorter chunk would wait on the next message
+            if chunk < CHUNK_MIN {
+                return None;
+            }
+
+            // the chunk size in hex, its line break, the chunk and another one
+            let size = format!("{:X}", chunk).len();
+            for (write, data) in [(size, false), (2, false), (chunk, true), (2, false)] {
+                // a write that doesn't fit flushes everything before it
+                if buffered + write > WRITE_BUFFER {
+                    flushed |= waiting;
+                    buffered = 0;
+                }
+
+                buffered += write;
+                waiting |= data && offset + chunk >= event;
+            }
+
+            offset += chunk;
+        }
+
+        flushed.then_some(buffered)
+    }
+
+    fn event<S: serde::Serialize>(name: &str, revision: i64, data: S) -> Result<String> {
+        Ok(format!(
+            "id: {}\nevent: {}\ndata: {}\n\n",
+            revision,
+            name,
+            serde_json::to_string(&data)?
+        ))
+    }
+
+    pub fn char_new(
+        ctx: &Context,
+        user: Option<&User>,
+        params: Params<'_>,
+        pairs: Pairs<'_>,
+        payload: Payload,
+    ) -> Result<Response> {
+        let mut conn = ctx.pool.get()?;
+        let trans = conn.write()?;
+        let campaign = Db::campaign(&trans, params.find("campaign")?)?;
+        let since = since(&trans, campaign, &pairs)?;
+
+        let value = payload.find("name")?;
+        let template = payload.get("template")?;
+        Db::add_character(&trans, campaign, value, template, actor(user))?;
+
+        trans.commit()?;
+        ctx.events.notify();
+
+        respond(&conn, user, campaign, since)
+    }
+
+    pub fn char_remove(
+        ctx: &Context,
+        user: Option<&User>,
+        params: Params<'_>,
+        pairs: Pairs<'_>,
+        _: Payload,
+    ) -> Result<Response> {
+        let mut conn = ctx.pool.get()?;
+        let trans = conn.write()?;
+        let campaign = Db::campaign(&trans, params.find("campaign")?)?;
+        let since = since(&trans, campaign, &pairs)?;
+
+        let name = params.find("name")?;
+        Db::remove_character(&trans, campaign, name, actor(user))?;
+
+        trans.commit()?;
+        ctx.events.notify();
+
+        respond(&conn, user, campaign, since)
+    }
+
+    pub fn char_owner(
+        ctx: &Context,
+        user: Option<&User>,
+        params: Params<'_>,
+        pairs: Pairs<'_>,
+        payload: Payload,
+    ) -> Result<Response> {
+        let mut conn = ctx.pool.get()?;
+        let trans = conn.write()?;
+        let campaign = Db::campaign(&trans, params.find("campaign")?)?;
+        let since = since(&trans, campaign, &pairs)?;
+
+        let name = params.find("name")?;
+        let owner = Some(payload.find("owner")?).filter(|owner| !owner.is_empty());
+        Db::set_owner(&trans, campaign, name, owner)?;
+
+        trans.commit()?;
+        ctx.events.notify();
+
+        respond(&conn, user, campaign, since)
+    }
+
+    pub fn char_rename(
+        ctx: &Context,
+        user: Option<&User>,
+        params: Params<'_>,
+        pairs: Pairs<'_>,
+        payload: Payload,
+    ) -> Result<Response> {
+        let mut conn = ctx.pool.get()?;
+        let trans = conn.write()?;
+        let campaign = Db::campaign(&trans, params.find("campaign")?)?;
+        let since = since(&trans, campaign, &pairs)?;
+
+        let name = params.find("name")?;
+        let to = payload.find("name")?;
+        Db::rename_character(&trans, campaign, name, to, actor(user))?;
+
+        trans.commit()?;
+        ctx.events.notify();
+
+        respond(&conn, user, campaign, since)
+    }
+
+    pub fn inventory_give(
+        ctx: &Context,
+        user: Option<&User>,
+        params: Params<'_>,
+        pairs: Pairs<'_>,
+        payload: Payload,
+    ) -> Result<Response> {
+        let mut conn = ctx.pool.get()?;
+        let trans = conn.write()?;
+        let campaign = Db::campaign(&trans, params.find("campaign")?)?;
+        let since = since(&trans, campaign, &pairs)?;
+
+        let name = params.find("name")?;
+        let id = weapon_id(&trans, campaign, payload.find("id")?)?;
+
+        // only weapons that can be built from the current parts are given out
+        Weapon::from_id(
+            &Db::parts(&trans, campaign)?,
+            &Db::curves(&trans, campaign)?,
+            id,
+        )?;
+
+        let nickname = payload.get("nickname")?;
+        let notes = payload.get("notes")?.unwrap_or_default();
+        Db::give_weapon(&trans, campaign, name, id, nickname, notes)?;
+
+        trans.commit()?;
+        ctx.events.notify();
+
+        respond(&conn, user, campaign, since)
+    }
+
+    pub fn inventory_drop(
+        ctx: &Context,
+        user: Option<&User>,
+        params: Params<'_>,
+        pairs: Pairs<'_>,
+        _: Payload,
+    ) -> Result<Response> {
+        let mut conn = ctx.pool.get()?;
+        let trans = conn.write()?;
+        let campaign = Db::campaign(&trans, params.find("campaign")?)?;
+        let since = since(&trans, campaign, &pairs)?;
+
+        let name = params.find("name")?;
+        Db::drop_weapon(&trans, campaign, name, item(&params)?)?;
+
+        trans.commit()?;
+        ctx.events.notify();
+
+        respond(&conn, user, campaign, since)
+    }
+
+    pub fn inventory_edit(
+        ctx: &Context,
+        user: Option<&User>,
+        params: Params<'_>,
+        pairs: Pairs<'_>,
+        payload: Payload,
+    ) -> Result<Response> {
+        let mut conn = ctx.pool.get()?;
+        let trans = conn.write()?;
+        let campaign = Db::campaign(&trans, params.find("campaign")?)?;
+        let since = since(&trans, campaign, &pairs)?;
+
+        let name = params.find("name")?;
+        let nickname = payload.get("nickname")?;
+        let notes = payload.get("notes")?;
+        Db::edit_weapon(&trans, campaign, name, item(&params)?, nickname, notes)?;
+
+        trans.commit()?;
+        ctx.events.notify();
+
+        respond(&conn, user, campaign, since)
+    }
+
+    pub fn inventory_equip(
+        ctx: &Context,
+        user: Option<&User>,
+        params: Params<'_>,
+        pairs: Pairs<'_>,
+        payload: Payload,
+    ) -> Result<Response> {
+        let mut conn = ctx.pool.get()?;
+        let trans = conn.write()?;
+        let campaign = Db::campaign(&trans, params.find("campaign")?)?;
+        let since = since(&trans, campaign, &pairs)?;
+
+        let name = params.find("name")?;
+        let equipped = payload.value::<bool>("equipped")?;
+        Db::equip_weapon(&trans, campaign, name, item(&params)?, equipped)?;
+
+        trans.commit()?;
+        ctx.events.notify();
+
+        respond(&conn, user, campaign, since)
+    }
+
+    pub fn inventory_transfer(
+        ctx: &Context,
+        user: Option<&User>,
+        params: Params<'_>,
+        pairs: Pairs<'_>,
+        payload: Payload,
+    ) -> Result<Response> {
+        let mut conn = ctx.pool.get()?;
+        let trans = conn.write()?;
+        let campaign = Db::campaign(&trans, params.find("campaign")?)?;
+        let since = since(&trans, campaign, &pairs)?;
+
+        let name = params.find("name")?;
+        let to = payload.find("character")?;
+        Db::transfer_weapon(&trans, campaign, name, item(&params)?, to)?;
+
+        trans.commit()?;
+        ctx.events.notify();
+
+        respond(&conn, user, campaign, since)
+    }
+
+    /// The inventory item `:item` refers to.
+    fn item(params: &Params<'_>) -> Result<i64, ApiError> {
+        let item = params.find("item")?;
+
+        item.parse().map_err(|_| ApiError::invalid("item", item))
+    }
+
+    pub fn char_stat_increment(
+        ctx: &Context,
+        user: Option<&User>,
+        params: Params<'_>,
+        pairs: Pairs<'_>,
+        _: Payload,
+    ) -> Result<Response> {
+        let mut conn = ctx.pool.get()?;
+        let trans = conn.write()?;
+        let campaign = Db::campaign(&trans, params.find("campaign")?)?;
+        let since = since(&trans, campaign, &pairs)?;
+
+        let name = params.find("name")?;
+        let stat = params.find("stat")?;
+        Db::increment_stat(&trans, campaign, name, stat, actor(user))?;
+
+        trans.commit()?;
+        ctx.events.notify();
+
+        respond(&conn, user, campaign, since)
+    }
+
+    pub fn char_stat_decrement(
+        ctx: &Context,
+        user: Option<&User>,
+        params: Params<'_>,
+        pairs: Pairs<'_>,
+        _: Payload,
+    ) -> Result<Response> {
+        let mut conn = ctx.pool.get()?;
+        let trans = conn.write()?;
+        let campaign = Db::campaign(&trans, params.find("campaign")?)?;
+        let since = since(&trans, campaign, &pairs)?;
+
+        let name = params.find("name")?;
+        let stat = params.find("stat")?;
+        Db::decrement_stat(&trans, campaign, name, stat, actor(user))?;
+
+        trans.commit()?;
+        ctx.events.notify();
+
+        respond(&conn, user, campaign, since)
+    }
+
+    pub fn char_stat_toggle(
+        ctx: &Context,
+        user: Option<&User>,
+        params: Params<'_>,
+        pairs: Pairs<'_>,
+        _: Payload,
+    ) -> Result<Response> {
+        let mut conn = ctx.pool.get()?;
+        let trans = conn.write()?;
+        let campaign = Db::campaign(&trans, params.find("campaign")?)?;
+        let since = since(&trans, campaign, &pairs)?;
+
+        let name = params.find("name")?;
+        let stat = params.find("stat")?;
+        Db::toggle_stat(&trans, campaign, name, stat, actor(user))?;
+
+        trans.commit()?;
+        ctx.events.notify();
+
+        respond(&conn, user, campaign, since)
+    }
+
+    pub fn char_stat_set(
+        ctx: &Context,
+        user: Option<&User>,
+        params: Params<'_>,
+        pairs: Pairs<'_>,
+        payload: Payload,
+    ) -> Result<Response> {
+        let mut conn = ctx.pool.get()?;
+        let trans = conn.write()?;
+        let campaign = Db::campaign(&trans, params.find("campaign")?)?;
+        let since = since(&trans, campaign, &pairs)?;
+
+        let name = params.find("name")?;
+        let stat = params.find("stat")?;
+        let value = payload.value::<StatValue>("value")?;
+        Db::set_stat_value(&trans, campaign, name, stat, value, actor(user))?;
+
+        trans.commit()?;
+        ctx.events.notify();
+
+        respond(&conn, user, campaign, since)
+    }
+
+    pub fn stat_new(
+        ctx: &Context,
+        user: Option<&User>,
+        params: Params<'_>,
+        pairs: Pairs<'_>,
+        payload: Payload,
+    ) -> Result<Response> {
+        let mut conn = ctx.pool.get()?;
+        let trans = conn.write()?;
+        let campaign = Db::campaign(&trans, params.find("campaign")?)?;
+        let since = since(&trans, campaign, &pairs)?;
+
+        let name = payload.find("name")?;
+        let template = payload.get("template")?;
+        let typ = payload.parse::<StatType>()?;
+        let meta = payload.parse::<StatMeta>()?;
+        Db::add_stat(&trans, campaign, name, template, typ, meta, actor(user))?;
+
+        trans.commit()?;
+        ctx.events.notify();
+
+        respond(&conn, user, campaign, since)
+    }
+
+    pub fn stat_remove(
+        ctx: &Context,
+        user: Option<&User>,
+        params: Params<'_>,
+        pairs: Pairs<'_>,
+        _: Payload,
+    ) -> Result<Response> {
+        let mut conn = ctx.pool.get()?;
+        let trans = conn.write()?;
+        let campaign = Db::campaign(&trans, params.find("campaign")?)?;
+        let since = since(&trans, campaign, &pairs)?;
+
+        let name = params.find("name")?;
+        Db::remove_stat(&trans, campaign, name, actor(user))?;
+
+        trans.commit()?;
+        ctx.events.notify();
+
+        respond(&conn, user, campaign, since)
+    }
+
+    pub fn stat_rename(
+        ctx: &Context,
+        user: Option<&User>,
+        params: Params<'_>,
+        pairs: Pairs<'_>,
+        payload: Payload,
+    ) -> Result<Response> {
+        let mut conn = ctx.pool.get()?;
+        let trans = conn.write()?;
+        let campaign = Db::campaign(&trans, params.find("campaign")?)?;
+        let since = since(&trans, campaign, &pairs)?;
+
+        let name = params.find("name")?;
+        let to = payload.find("name")?;
+        Db::rename_stat(&trans, campaign, name, to, actor(user))?;
+
+        trans.commit()?;
+        ctx.events.notify();
+
+        respond(&conn, user, campaign, since)
+    }
+
+    pub fn template_new(
+        ctx: &Context,
+        user: Option<&User>,
+        params: Params<'_>,
+        pairs: Pairs<'_>,
+        payload: Payload,
+    ) -> Result<Response> {
+        let mut conn = ctx.pool.get()?;
+        let trans = conn.write()?;
+        let campaign = Db::campaign(&trans, params.find("campaign")?)?;
+        let since = since(&trans, campaign, &pairs)?;
+
+        let name = payload.find("name")?;
+        let parent = payload.get("parent")?;
+        Db::add_template(&trans, campaign, name, parent)?;
+
+        trans.commit()?;
+        ctx.events.notify();
+
+        respond(&conn, user, campaign, since)
+    }
+
+    pub fn template_remove(
+        ctx: &Context,
+        user: Option<&User>,
+        params: Params<'_>,
+        pairs: Pairs<'_>,
+        _: Payload,
+    ) -> Result<Response> {
+        let mut conn = ctx.pool.get()?;
+        let trans = conn.write()?;
+        let campaign = Db::campaign(&trans, params.find("campaign")?)?;
+        let since = since(&trans, campaign, &pairs)?;
+
+        let name = params.find("name")?;
+        Db::remove_template(&trans, campaign, name)?;
+
+        trans.commit()?;
+        ctx.events.notify();
+
+        respond(&conn, user, campaign, since)
+    }
+
+    /// Largest number of history entries that are sent at once.
+    static HISTORY_LIMIT: i64 = 1000;
+
+    pub fn history(
+        ctx: &Context,
+        user: Option<&User>,
+        params: Params<'_>,
+        pairs: Pairs<'_>,
+        _: Payload,
+    ) -> Result<Response> {
+        let conn = ctx.pool.get()?;
+        let campaign = Db::campaign(&conn, params.find("campaign")?)?;
+
+        let time = |name: &str| {
+            pairs
+                .get(name)
+                .map(|time| {
+                    time.parse::<i64>()
+                        .map_err(|_| ApiError::invalid("time", time))
+                })
+                .transpose()
+        };
+
+        let filter = HistoryFilter {
+            actor: pairs.get("actor"),
+            character: pairs.get("character"),
+            stat: pairs.get("stat"),
+            action: pairs.get("action").map(Action::try_from).transpose()?,
+            since: time("since")?,
+            until: time("until")?,
+            limit: limit(&pairs)?,
+        };
+
+        json(Db::history(&conn, campaign, &filter, gm(user))?)
+    }
+
+    /// How many entries `?limit=` asks for from a history.
+    fn limit(pairs: &Pairs<'_>) -> Result<i64, ApiError> {
+        match pairs.get("limit") {
+            Some(limit) => limit
+                .parse::<i64>()
+                .ok()
+                .filter(|limit| (1..=HISTORY_LIMIT).contains(limit))
+                .ok_or_else(|| ApiError::invalid("limit", limit)),
+            None => Ok(100),
+        }
+    }
+
+    /// How many history entries `?count=` asks to undo or redo.
+    fn count(pairs: &Pairs<'_>) -> Result<i64, ApiError> {
+        match pairs.get("count") {
+            Some(count) => count
+                .parse::<i64>()
+                .ok()
+                .filter(|count| (1..=HISTORY_LIMIT).contains(count))
+                .ok_or_else(|| ApiError::invalid("count", count)),
+            None => Ok(1),
+        }
+    }
+
+    pub fn undo(
+        ctx: &Context,
+        user: Option<&User>,
+        params: Params<'_>,
+        pairs: Pairs<'_>,
+        _: Payload,
+    ) -> Result<Response> {
+        let mut conn = ctx.pool.get()?;
+        let trans = conn.write()?;
+        let campaign = Db::campaign(&trans, params.find("campaign")?)?;
+        let since = since(&trans, campaign, &pairs)?;
+
+        Db::undo(&trans, campaign, count(&pairs)?)?;
+
+        trans.commit()?;
+        ctx.events.notify();
+
+        respond(&conn, user, campaign, since)
+    }
+
+    pub fn redo(
+        ctx: &Context,
+        user: Option<&User>,
+        params: Params<'_>,
+        pairs: Pairs<'_>,
+        _: Payload,
+    ) -> Result<Response> {
+        let mut conn = ctx.pool.get()?;
+        let trans = conn.write()?;
+        let campaign = Db::campaign(&trans, params.find("campaign")?)?;
+        let since = since(&trans, campaign, &pairs)?;
+
+        Db::redo(&trans, campaign, count(&pairs)?)?;
+
+        trans.commit()?;
+        ctx.events.notify();
+
+        respond(&conn, user, campaign, since)
+    }
+
+    /// Rolls the `expr` body field, stats it refers to are read from
+    /// `character`.
+    pub fn roll(
+        ctx: &Context,
+        user: Option<&User>,
+        params: Params<'_>,
+        _: Pairs<'_>,
+        payload: Payload,
+    ) -> Result<Response> {
+        let mut conn = ctx.pool.get()?;
+        let trans = conn.write()?;
+        let name = params.find("campaign")?;
+        let campaign = Db::campaign(&trans, name)?;
+
+        let expr = payload.find("expr")?;
+        let character = payload.get("character")?;
+
+        // players only roll with and log to the characters assigned to them
+        if let (Some(character), Some(user)) = (character, user) {
+            if user.role != Role::Gm && !Db::owns(&trans, user, name, character)? {
+                return Err(ApiError::Forbidden(format!(
+                    "character `{}` is not assigned to you",
+                    character
+                ))
+                .into());
+            }
+        }
+
+        let roll = Db::roll(
+            &trans,
+            campaign,
+            actor(user),
+            character,
+            expr,
+            optional(&payload, "seed")?,
+            gm(user),
+        )?;
+
+        trans.commit()?;
+
+        json(roll)
+    }
+
+    /// The `?seed=` to generate with, a random one is picked when it's `None`.
+    fn seed(pairs: &Pairs<'_>) -> Result<Option<u32>, ApiError> {
+        pairs
+            .get("seed")
+            .map(|seed| {
+                seed.parse::<u32>()
+                    .map_err(|_| ApiError::invalid("seed", seed))
+            })
+            .transpose()
+    }
+
+    pub fn roll_list(
+        ctx: &Context,
+        user: Option<&User>,
+        params: Params<'_>,
+        pairs: Pairs<'_>,
+        _: Payload,
+    ) -> Result<Response> {
+        let conn = ctx.pool.get()?;
+        let campaign = Db::campaign(&conn, params.find("campaign")?)?;
+
+        json(Db::rolls(
+            &conn,
+            campaign,
+            pairs.get("character"),
+            limit(&pairs)?,
+            gm(user),
+        )?)
+    }
+
+    /// Reads the weapon id `value`, legacy ids are upgraded to the current format.
+    fn weapon_id<C: AsConn>(conn: C, campaign: i64, value: &str) -> Result<Id> {
+        let conn = conn.as_conn();
+
+        let id = Id::try_from(value).map_err(|_| WeaponError::InvalidId)?;
+
+        if !id.check() {
+            return Err(WeaponError::InvalidId.into());
+        }
+
+        if id.is_legacy() {
+            return Ok(id.upgrade(&Db::legacy_parts(conn, campaign)?)?);
+        }
+
+        Ok(id)
+    }
+
+    pub fn weapon_build(
+        ctx: &Context,
+        user: Option<&User>,
+        params: Params<'_>,
+        _: Pairs<'_>,
+        payload: Payload,
+    ) -> Result<Response> {
+        let mut conn = ctx.pool.get()?;
+        let trans = conn.write()?;
+        let campaign = Db::campaign(&trans, params.find("campaign")?)?;
+
+        let id = weapon_id(&trans, campaign, payload.find("id")?)?;
+
+        let parts = Db::parts(&trans, campaign)?;
+        let curves = Db::curves(&trans, campaign)?;
+
+        let weapon = Weapon::from_id(&parts, &curves, id)?;
+
+        Db::show_weapon(&trans, campaign, weapon.id)?;
+
+        trans.commit()?;
+        ctx.events.notify();
+
+        json(Db::state(&conn, campaign, Some(weapon), gm(user))?)
+    }
+
+    pub fn weapon_generate(
+        ctx: &Context,
+        user: Option<&User>,
+        params: Params<'_>,
+        _: Pairs<'_>,
+        payload: Payload,
+    ) -> Result<Response> {
+        let mut conn = ctx.pool.get()?;
+        let trans = conn.write()?;
+        let campaign = Db::campaign(&trans, params.find("campaign")?)?;
+
+        let level = payload.value::<u8>("level")?;
+        let luck = check_luck(optional(&payload, "luck")?.unwrap_or_default())?;
+
+        let parts = Db::parts(&trans, campaign)?;
+        let curves = Db::curves(&trans, campaign)?;
+
+        let table = Db::drop_table(&trans, campaign)?;
+
+        let seed = optional(&payload, "seed")?.unwrap_or_else(rand::random);
+        let weapon = Weapon::generate(&parts, &curves, &table, level, luck, seed)?;
+
+        Db::show_weapon(&trans, campaign, weapon.id)?;
+
+        trans.commit()?;
+        ctx.events.notify();
+
+        json(Db::state(&conn, campaign, Some(weapon), gm(user))?)
+    }
+
+    /// Adds the weapon `id` to the library, as it looks with the current parts.
+    pub fn weapon_save(
+        ctx: &Context,
+        _: Option<&User>,
+        params: Params<'_>,
+        _: Pairs<'_>,
+        payload: Payload,
+    ) -> Result<Response> {
+        let mut conn = ctx.pool.get()?;
+        let trans = conn.write()?;
+        let campaign = Db::campaign(&trans, params.find("campaign")?)?;
+
+        let id = weapon_id(&trans, campaign, payload.find("id")?)?;
+        let tags = tags(&payload)?;
+
+        let parts = Db::parts(&trans, campaign)?;
+        let curves = Db::curves(&trans, campaign)?;
+
+        let weapon = Weapon::from_id(&parts, &curves, id)?;
+        let saved = Db::save_weapon(&trans, campaign, weapon, &tags)?;
+
+        trans.commit()?;
+
+        json(saved)
+    }
+
+    pub fn weapon_list(
+        ctx: &Context,
+        _: Option<&User>,
+        params: Params<'_>,
+        pairs: Pairs<'_>,
+        _: Payload,
+    ) -> Result<Response> {
+        let conn = ctx.pool.get()?;
+        let campaign = Db::campaign(&conn, params.find("campaign")?)?;
+
+        let level = |name: &str| {
+            pairs
+                .get(name)
+                .map(|level| {
+                    level
+                        .parse::<u8>()
+                        .map_err(|_| ApiError::invalid("level", level))
+                })
+                .transpose()
+        };
+
+        let filter = WeaponFilter {
+            rarity: variant(&pairs, "rarity")?,
+            company: variant(&pairs, "company")?,
+            typ: variant(&pairs, "type")?,
+            min_level: level("min_level")?,
+            max_level: level("max_level")?,
+            tag: pairs.get("tag"),
+            search: pairs.get("search"),
+            limit: limit(&pairs)?,
+        };
+
+        json(Db::saved_weapons(&conn, campaign, &filter)?)
+    }
+
+    pub fn weapon_tag(
+        ctx: &Context,
+        _: Option<&User>,
+        params: Params<'_>,
+        _: Pairs<'_>,
+        payload: Payload,
+    ) -> Result<Response> {
+        let mut conn = ctx.pool.get()?;
+        let trans = conn.write()?;
+        let campaign = Db::campaign(&trans, params.find("campaign")?)?;
+
+        let saved = Db::tag_weapon(&trans, campaign, saved_id(&params)?, &tags(&payload)?)?;
+
+        trans.commit()?;
+
+        json(saved)
+    }
+
+    pub fn weapon_remove(
+        ctx: &Context,
+        _: Option<&User>,
+        params: Params<'_>,
+        pairs: Pairs<'_>,
+        _: Payload,
+    ) -> Result<Response> {
+        let mut conn = ctx.pool.get()?;
+        let trans = conn.write()?;
+        let campaign = Db::campaign(&trans, params.find("campaign")?)?;
+
+        Db::remove_saved_weapon(&trans, campaign, saved_id(&params)?)?;
+
+        trans.commit()?;
+
+        let filter = WeaponFilter {
+            limit: limit(&pairs)?,
+            ..Default::default()
+        };
+
+        json(Db::saved_weapons(&conn, campaign, &filter)?)
+    }
+
+    /// The saved weapon `:id` refers to.
+    fn saved_id(params: &Params<'_>) -> Result<i64, ApiError> {
+        let id = params.find("id")?;
+
+        id.parse()
+            .map_err(|_| ApiError::invalid("saved weapon", id))
+    }
+
+    /// The body field `name`, `None` when it's missing or null.
+    fn optional<T: serde::de::DeserializeOwned>(
+        payload: &Payload,
+        name: &str,
+    ) -> Result<Option<T>, ApiError> {
+        match payload.value::<Option<T>>(name) {
+            Err(ApiError::MissingParameter(_)) => Ok(None),
+            value => value,
+        }
+    }
+
+    /// The `tags` body field, no tags when it's missing.
+    fn tags(payload: &Payload) -> Result<Vec<String>, ApiError> {
+        match payload.value::<Option<Vec<String>>>("tags") {
+            Err(ApiError::MissingParameter(_)) => Ok(Vec::new()),
+            tags => tags.map(Option::unwrap_or_default),
+        }
+    }
+
+    /// Reads `?name=` as one of the names `T` has in json.
+    fn variant<T: serde::de::DeserializeOwned>(
+        pairs: &Pairs<'_>,
+        name: &str,
+    ) -> Result<Option<T>, ApiError> {
+        pairs
+            .get(name)
+            .map(|value| parse_variant(name, value))
+            .transpose()
+    }
+
+    /// Like `variant`, but `?name=` is a comma separated list.
+    fn variants<T: serde::de::DeserializeOwned>(
+        pairs: &Pairs<'_>,
+        name: &str,
+    ) -> Result<Vec<T>, ApiError> {
+        match pairs.get(name) {
+            Some(values) => values
+                .split(',')
+                .map(|value| parse_variant(name, value))
+                .collect(),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn parse_variant<T: serde::de::DeserializeOwned>(
+        name: &str,
+        value: &str,
+    ) -> Result<T, ApiError> {
+        serde_json::from_value(serde_json::Value::String(value.to_string()))
+            .map_err(|_| ApiError::invalid(name, value))
+    }
+
+    /// Largest number of weapons that are generated at once.
+    static BATCH_LIMIT: usize = 100;
+
+    /// Generates `?count=` weapons as loot, without showing any of them.
+    pub fn weapon_generate_batch(
+        ctx: &Context,
+        _: Option<&User>,
+        params: Params<'_>,
+        pairs: Pairs<'_>,
+        _: Payload,
+    ) -> Result<Response> {
+        let conn = ctx.pool.get()?;
+        let campaign = Db::campaign(&conn, params.find("campaign")?)?;
+
+        let count = pairs.find("count")?;
+        let count = count
+            .parse::<usize>()
+            .ok()
+            .filter(|count| (1..=BATCH_LIMIT).contains(count))
+            .ok_or_else(|| ApiError::invalid("count", count))?;
+
+        let filter = LootFilter {
+            min_rarity: variant(&pairs, "min_rarity")?,
+            types: variants(&pairs, "types")?,
+            companies: variants(&pairs, "companies")?,
+            unique_parts: pairs
+                .get("unique_parts")
+                .map(|unique| {
+                    unique
+                        .parse::<bool>()
+                        .map_err(|_| ApiError::invalid("unique_parts", unique))
+                })
+                .transpose()?
+                .unwrap_or_default(),
+            luck: luck(&pairs)?,
+        };
+
+        let parts = Db::parts(&conn, campaign)?;
+        let curves = Db::curves(&conn, campaign)?;
+        let table = Db::drop_table(&conn, campaign)?;
+
+        let seed = seed(&pairs)?.unwrap_or_else(rand::random);
+        let weapons = Weapon::generate_batch(
+            &parts,
+            &curves,
+            &table,
+            level(&pairs)?,
+            count,
+            &filter,
+            seed,
+        )?;
+
+        json(weapons.into_iter().map(Weapon::display).collect::<Vec<_>>())
+    }
+
+    pub fn weapon_weight_list(
+        ctx: &Context,
+        _: Option<&User>,
+        params: Params<'_>,
+        _: Pairs<'_>,
+        _: Payload,
+    ) -> Result<Response> {
+        let conn = ctx.pool.get()?;
+        let campaign = Db::campaign(&conn, params.find("campaign")?)?;
+
+        json(Db::weapon_weights(&conn, campaign)?)
+    }
+
+    pub fn weapon_weight_set(
+        ctx: &Context,
+        _: Option<&User>,
+        params: Params<'_>,
+        _: Pairs<'_>,
+        payload: Payload,
+    ) -> Result<Response> {
+        let mut conn = ctx.pool.get()?;
+        let trans = conn.write()?;
+        let campaign = Db::campaign(&trans, params.find("campaign")?)?;
+
+        Db::set_weapon_weight(&trans, campaign, &payload.parse()?)?;
+
+        trans.commit()?;
+        ctx.events.notify();
+
+        json(Db::weapon_weights(&conn, campaign)?)
+    }
+
+    pub fn weapon_weight_remove(
+        ctx: &Context,
+        _: Option<&User>,
+        params: Params<'_>,
+        _: Pairs<'_>,
+        _: Payload,
+    ) -> Result<Response> {
+        let mut conn = ctx.pool.get()?;
+        let trans = conn.write()?;
+        let campaign = Db::campaign(&trans, params.find("campaign")?)?;
+
+        let typ = parse_variant("type", params.find("type")?)?;
+        let level = params.find("level")?;
+        let level = level
+            .parse::<u8>()
+            .map_err(|_| ApiError::invalid("level", level))?;
+
+        Db::remove_weapon_weight(&trans, campaign, typ, level)?;
+
+        trans.commit()?;
+        ctx.events.notify();
+
+        json(Db::weapon_weights(&conn, campaign)?)
+    }
+
+    pub fn rarity_weight_list(
+        ctx: &Context,
+        _: Option<&User>,
+        params: Params<'_>,
+        _: Pairs<'_>,
+        _: Payload,
+    ) -> Result<Response> {
+        let conn = ctx.pool.get()?;
+        let campaign = Db::campaign(&conn, params.find("campaign")?)?;
+
+        json(Db::rarity_weights(&conn, campaign)?)
+    }
+
+    pub fn rarity_weight_set(
+        ctx: &Context,
+        _: Option<&User>,
+        params: Params<'_>,
+        _: Pairs<'_>,
+        payload: Payload,
+    ) -> Result<Response> {
+        let mut conn = ctx.pool.get()?;
+        let trans = conn.write()?;
+        let campaign = Db::campaign(&trans, params.find("campaign")?)?;
+
+        Db::set_rarity_weight(&trans, campaign, &payload.parse()?)?;
+
+        trans.commit()?;
+        ctx.events.notify();
+
+        json(Db::rarity_weights(&conn, campaign)?)
+    }
+
+    pub fn rarity_weight_remove(
+        ctx: &Context,
+        _: Option<&User>,
+        params: Params<'_>,
+        _: Pairs<'_>,
+        _: Payload,
+    ) -> Result<Response> {
+        let mut conn = ctx.pool.get()?;
+        let trans = conn.write()?;
+        let campaign = Db::campaign(&trans, params.find("campaign")?)?;
+
+        let rarity = Rarity::try_from(params.find("rarity")?)?;
+        let level = params.find("level")?;
+        let level = level
+            .parse::<u8>()
+            .map_err(|_| ApiError::invalid("level", level))?;
+
+        Db::remove_rarity_weight(&trans, campaign, rarity, level)?;
+
+        trans.commit()?;
+        ctx.events.notify();
+
+        json(Db::rarity_weights(&conn, campaign)?)
+    }
+
+    /// Largest `?luck=` in either direction.
+    static LUCK_MAX: f64 = 10.0;
+
+    /// The `?luck=` to generate weapons with, positive luck makes rarer parts
+    /// more likely.
+    fn luck(pairs: &Pairs<'_>) -> Result<f64, ApiError> {
+        match pairs.get("luck") {
+            Some(luck) => luck
+                .parse::<f64>()
+                .map_err(|_| ApiError::invalid("luck", luck))
+                .and_then(check_luck),
+            None => Ok(0.0),
+        }
+    }
+
+    fn check_luck(luck: f64) -> Result<f64, ApiError> {
+        if !(-LUCK_MAX..=LUCK_MAX).contains(&luck) {
+            return Err(ApiError::invalid("luck", &luck.to_string()));
+        }
+
+        Ok(luck)
+    }
+
+    /// The `?level=` to generate weapons at.
+    fn level(pairs: &Pairs<'_>) -> Result<u8, ApiError> {
+        let level = pairs.find("level")?;
+
+        level
+            .parse::<u8>()
+            .map_err(|_| ApiError::InvalidValue(format!("invalid level `{}`", level)))
+    }
+
+    pub fn weapon_part_init(
+        ctx: &Context,
+        user: Option<&User>,
+        params: Params<'_>,
+        pairs: Pairs<'_>,
+        _: Payload,
+    ) -> Result<Response> {
+        let mut conn = ctx.pool.get()?;
+        let trans = conn.write()?;
+        let campaign = Db::campaign(&trans, params.find("campaign")?)?;
+        let since = since(&trans, campaign, &pairs)?;
+
+        Db::init_weapon_parts(&trans, campaign)?;
+
+        trans.commit()?;
+        ctx.events.notify();
+
+        respond(&conn, user, campaign, since)
+    }
+
+    pub fn weapon_part_new(
+        ctx: &Context,
+        user: Option<&User>,
+        params: Params<'_>,
+        pairs: Pairs<'_>,
+        payload: Payload,
+    ) -> Result<Response> {
+        let mut conn = ctx.pool.get()?;
+        let trans = conn.write()?;
+        let campaign = Db::campaign(&trans, params.find("campaign")?)?;
+        let since = since(&trans, campaign, &pairs)?;
+
+        let name = payload.find("name")?;
+        let details = payload.find("details")?;
+
+        let part = PartType::try_from(payload.find("part")?)?;
+        let rarity = Rarity::try_from(payload.find("rarity")?)?;
+        let company = Company::try_from(payload.find("company")?)?;
+
+        Db::add_weapon_part(&trans, campaign, name, details, part, rarity, company)?;
+
+        trans.commit()?;
+        ctx.events.notify();
+
+        respond(&conn, user, campaign, since)
+    }
+
+    pub fn weapon_part_remove(
+        ctx: &Context,
+        user: Option<&User>,
+        params: Params<'_>,
+        pairs: Pairs<'_>,
+        _: Payload,
+    ) -> Result<Response> {
+        let mut conn = ctx.pool.get()?;
+        let trans = conn.write()?;
+        let campaign = Db::campaign(&trans, params.find("campaign")?)?;
+        let since = since(&trans, campaign, &pairs)?;
+
+        Db::remove_weapon_part(&trans, campaign, part_id(&params)?)?;
+
+        trans.commit()?;
+        ctx.events.notify();
+
+        respond(&conn, user, campaign, since)
+    }
+
+    fn part_id(params: &Params<'_>) -> Result<u8, ApiError> {
+        let id = params.find("id")?;
+
+        id.parse().map_err(|_| ApiError::invalid("weapon part", id))
+    }
+
+    pub fn weapon_part_rename(
+        ctx: &Context,
+        user: Option<&User>,
+        params: Params<'_>,
+        pairs: Pairs<'_>,
+        payload: Payload,
+    ) -> Result<Response> {
+        let mut conn = ctx.pool.get()?;
+        let trans = conn.write()?;
+        let campaign = Db::campaign(&trans, params.find("campaign")?)?;
+        let since = since(&trans, campaign, &pairs)?;
+
+        let to = payload.find("name")?;
+        Db::rename_weapon_part(&trans, campaign, part_id(&params)?, to)?;
+
+        trans.commit()?;
+        ctx.events.notify();
+
+        respond(&conn, user, campaign, since)
+    }
+}
+
+mod models {
+    use std::{
+        borrow::Cow,
+        cmp::Ordering,
+        collections::{BTreeMap, HashMap, HashSet},
+        fmt, ops,
+    };
+
+    use anyhow::Result;
+    use rand::{distributions::WeightedIndex, prelude::*, Rng};
+    use rand_chacha::ChaCha8Rng;
+    use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+
+    use crate::{error::ApiError, utils, LEVEL_MAX};
+
+    pub struct Pairs<'p>(Vec<(Cow<'p, str>, Cow<'p, str>)>);
+
+    impl<'p> Pairs<'p> {
+        pub fn new(pairs: Vec<(Cow<'p, str>, Cow<'p, str>)>) -> Self {
+            Self(pairs)
+        }
+
+        pub fn get(&'p self, name: &str) -> Option<&'p str> {
+            self.0
+                .iter()
+                .find(|(k, _)| *k == name)
+                .map(|(_, v)| v.as_ref())
+        }
+
+        pub fn find(&'p self, name: &str) -> Result<&'p str, ApiError> {
+            self.get(name).ok_or_else(|| {
+                ApiError::MissingParameter(format!("missing `{}` url parameter", name))
+            })
+        }
+    }
+
+    pub struct Params<'p>(Vec<(&'p str, &'p str)>);
+
+    impl<'p> Params<'p> {
+        pub fn new(pairs: Vec<(&'p str, &'p str)>) -> Self {
+            Self(pairs)
+        }
+
+        pub fn find(&self, name: &str) -> Result<&'p str, ApiError> {
+            self.0
+                .iter()
+                .find(|(k, _)| *k == name)
+                .map(|(_, v)| *v)
+                .ok_or_else(|| {
+                    ApiError::MissingParameter(format!("missing `{}` path parameter", name))
+                })
+        }
+    }
+
+    /// A json object request body.
+    pub struct Payload(serde_json::Map<String, serde_json::Value>);
+
+    impl Payload {
+        pub fn new(body: &[u8]) -> Result<Self, ApiError> {
+            if body.iter().all(u8::is_ascii_whitespace) {
+                return Ok(Self(serde_json::Map::new()));
+            }
+
+            match serde_json::from_slice(body) {
+                Ok(serde_json::Value::Object(map)) => Ok(Self(map)),
+                Ok(_) => Err(ApiError::InvalidValue(
+                    "request body must be a json object".to_string(),
+                )),
+                Err(err) => Err(ApiError::InvalidValue(format!(
+                    "invalid json request body: {}",
+                    err
+                ))),
+            }
+        }
+
+        pub fn find(&self, name: &str) -> Result<&str, ApiError> {
+            match self.0.get(name) {
+                Some(serde_json::Value::String(value)) => Ok(value),
+                Some(_) => Err(ApiError::InvalidValue(format!(
+                    "`{}` body field must be a string",
+                    name
+                ))),
+                None => Err(ApiError::MissingParameter(format!(
+                    "missing `{}` body field",
+                    name
+                ))),
+            }
+        }
+
+        /// Like `find`, but a missing or null field is `None`.
+        pub fn get(&self, name: &str) -> Result<Option<&str>, ApiError> {
+            match self.0.get(name) {
+                None | Some(serde_json::Value::Null) => Ok(None),
+                Some(_) => self.find(name).map(Some),
+            }
+        }
+
+        /// Reads the body field `name` as any json value `T` can hold.
+        pub fn value<T: serde::de::DeserializeOwned>(&self, name: &str) -> Result<T, ApiError> {
+            let value = self.0.get(name).ok_or_else(|| {
+                ApiError::MissingParameter(format!("missing `{}` body field", name))
+            })?;
+
+            serde_json::from_value(value.clone()).map_err(|err| {
+                ApiError::InvalidValue(format!("invalid `{}` body field: {}", name, err))
+            })
+        }
+
+        /// Reads the whole body as `T`, fields `T` doesn't know are ignored.
+        pub fn parse<T: serde::de::DeserializeOwned>(&self) -> Result<T, ApiError> {
+            serde_json::from_value(serde_json::Value::Object(self.0.clone()))
+                .map_err(|err| ApiError::InvalidValue(format!("invalid request body: {}", err)))
+        }
+    }
+
+    #[derive(Clone, Copy, serde::Serialize)]
+    pub struct Id(#[serde(with = "id_serde")] [u8; 8]);
+
+    impl Id {
+        #[allow(dead_code)]
+        pub fn new(id: u64) -> Self {
+            Self(id.to_be_bytes())
+        }
+
+        /// Ids whose part bytes are positions in the (unordered) part table.
+        const VERSION_LEGACY: u8 = 0;
+        /// Ids whose part bytes are stable part ids.
+        const VERSION_STABLE: u8 = 1;
+
+        pub fn from(level: u8, typ: u8, body: u8, barrel: u8, magazine: u8, stock: u8) -> Self {
+            Self::with_version(
+                Self::VERSION_STABLE,
+                level,
+                typ,
+                body,
+                barrel,
+                magazine,
+                stock,
+            )
+        }
+
+        fn with_version(
+            version: u8,
+            level: u8,
+            typ: u8,
+            body: u8,
+            barrel: u8,
+            magazine: u8,
+            stock: u8,
+        ) -> Self {
+            let parity = level ^ typ ^ body ^ barrel ^ magazine ^ stock ^ version;
+
+            Self([level, typ, body, barrel, magazine, stock, version, parity])
+        }
+
+        pub fn check(&self) -> bool {
+            let [level, typ, body, barrel, magazine, stock, version, truth] = self.0;
+
+            let result = level ^ typ ^ body ^ barrel ^ magazine ^ stock ^ version;
+
+            truth == result
+        }
+
+        #[inline]
+        pub fn is_legacy(&self) -> bool {
+            self.version() == Self::VERSION_LEGACY
+        }
+
+        /// Converts a legacy id into a stable one, `legacy` maps the old part
+        /// positions to their part ids.
+        pub fn upgrade(&self, legacy: &[u8]) -> Result<Self, WeaponError> {
+            if !self.is_legacy() {
+                return Ok(*self);
+            }
+
+            let part = |position: u8| {
+                legacy
+                    .get(position as usize)
+                    .copied()
+                    .ok_or(WeaponError::MissingLegacyPart(position))
+            };
+
+            Ok(Self::from(
+                self.level(),
+                self.typ(),
+                part(self.body())?,
+                part(self.barrel())?,
+                part(self.magazine())?,
+                part(self.stock())?,
+            ))
+        }
+
+        #[inline]
+        pub fn level(&self) -> u8 {
+            self.0[0]
+        }
+
+        #[inline]
+        pub fn typ(&self) -> u8 {
+            self.0[1]
+        }
+    }
+
+    impl Id {
+        #[inline]
+        pub fn body(&self) -> u8 {
+            self.0[2]
+        }
+
+        #[inline]
+        pub fn barrel(&self) -> u8 {
+            self.0[3]
+        }
+
+        #[inline]
+        pub fn magazine(&self) -> u8 {
+            self.0[4]
+        }
+
+        #[inline]
+        pub fn stock(&self) -> u8 {
+            self.0[5]
+        }
+
+        #[inline]
+        pub fn version(&self) -> u8 {
+            self.0[6]
+        }
+    }
+
+    impl TryFrom<&str> for Id {
+        type Error = std::num::ParseIntError;
+
+        fn try_from(id: &str) -> Result<Self, Self::Error> {
+            u64::from_str_radix(id, 16).map(|id| Self(id.to_be_bytes()))
+        }
+    }
+
+    impl std::fmt::Display for Id {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "{:0>16x?}", u64::from_be_bytes(self.0))
+        }
+    }
+
+    mod id_serde {
         use serde::Serializer;
 
-        #[inline]
-        pub fn serialize<S>(id: &[u8; 8], s: S) -> Result<S::Ok, S::Error>
-        where
-            S: Serializer,
-        {
-            s.serialize_u64(u64::from_be_bytes(*id))
+        #[inline]
+        pub fn serialize<S>(id: &[u8; 8], s: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            s.serialize_u64(u64::from_be_bytes(*id))
+        }
+    }
+
+    #[derive(serde::Serialize)]
+    pub struct State {
+        pub templates: Vec<Template>,
+        pub stats: Vec<Stat>,
+        pub characters: Vec<Character>,
+        pub parts: Vec<Part>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub weapon: Option<WeaponDisplay>,
+        pub revision: i64,
+    }
+
+    /// Everything that changed after a given revision, entities are sent whole.
+    #[derive(serde::Serialize)]
+    pub struct Delta {
+        pub templates: Vec<Template>,
+        pub stats: Vec<Stat>,
+        pub characters: Vec<Character>,
+        pub parts: Vec<Part>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub weapon: Option<WeaponDisplay>,
+        /// Set when either weight table weapons are generated with changed.
+        pub weights: bool,
+        pub removed: Removed,
+        pub revision: i64,
+    }
+
+    #[derive(Default, serde::Serialize)]
+    pub struct Removed {
+        pub templates: Vec<String>,
+        pub stats: Vec<String>,
+        pub characters: Vec<String>,
+        pub parts: Vec<u8>,
+    }
+
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    pub enum Entity {
+        Template,
+        Character,
+        Stat,
+        Part,
+        Weapon,
+        Weight,
+    }
+
+    impl TryFrom<&str> for Entity {
+        type Error = ApiError;
+
+        fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
+            match value {
+                "template" => Ok(Entity::Template),
+                "character" => Ok(Entity::Character),
+                "stat" => Ok(Entity::Stat),
+                "part" => Ok(Entity::Part),
+                "weapon" => Ok(Entity::Weapon),
+                "weight" => Ok(Entity::Weight),
+                _ => Err(ApiError::invalid("entity", value)),
+            }
+        }
+    }
+
+    impl FromSql for Entity {
+        fn column_result(value: ValueRef) -> FromSqlResult<Self> {
+            String::column_result(value).and_then(|s| {
+                Entity::try_from(s.as_str()).map_err(|err| FromSqlError::Other(err.into()))
+            })
+        }
+    }
+
+    impl ToSql for Entity {
+        fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+            match self {
+                Entity::Template => Ok("template".into()),
+                Entity::Character => Ok("character".into()),
+                Entity::Stat => Ok("stat".into()),
+                Entity::Part => Ok("part".into()),
+                Entity::Weapon => Ok("weapon".into()),
+                Entity::Weight => Ok("weight".into()),
+            }
+        }
+    }
+
+    #[derive(serde::Serialize)]
+    pub struct Campaign {
+        pub name: String,
+    }
+
+    #[derive(serde::Serialize)]
+    pub struct Character {
+        pub name: String,
+        /// The player the character is assigned to.
+        pub owner: Option<String>,
+        pub template: String,
+        pub stats: Vec<CharacterStat>,
+        pub inventory: Vec<InventoryItem>,
+    }
+
+    /// A weapon a character carries.
+    #[derive(serde::Serialize)]
+    pub struct InventoryItem {
+        pub id: i64,
+        /// `None` once the parts of the weapon have been removed.
+        pub weapon: Option<WeaponDisplay>,
+        pub nickname: Option<String>,
+        pub equipped: bool,
+        pub notes: String,
+    }
+
+    /// A named set of stats, characters of it also get the stats of `parent`.
+    #[derive(serde::Serialize)]
+    pub struct Template {
+        pub name: String,
+        pub parent: Option<String>,
+    }
+
+    #[derive(serde::Serialize)]
+    pub struct User {
+        #[serde(skip)]
+        pub id: i64,
+        pub name: String,
+        pub role: Role,
+    }
+
+    #[derive(Clone, Copy, PartialEq, Eq, serde::Serialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum Role {
+        /// Edits the template, parts, curves and every character.
+        Gm,
+        /// Only changes stats on the characters assigned to them.
+        Player,
+    }
+
+    impl TryFrom<&str> for Role {
+        type Error = ApiError;
+
+        fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
+            match value {
+                "gm" => Ok(Role::Gm),
+                "player" => Ok(Role::Player),
+                _ => Err(ApiError::invalid("role", value)),
+            }
+        }
+    }
+
+    impl FromSql for Role {
+        fn column_result(value: ValueRef) -> FromSqlResult<Self> {
+            String::column_result(value).and_then(|s| {
+                Role::try_from(s.as_str()).map_err(|err| FromSqlError::Other(err.into()))
+            })
+        }
+    }
+
+    impl ToSql for Role {
+        fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+            match self {
+                Role::Gm => Ok("gm".into()),
+                Role::Player => Ok("player".into()),
+            }
+        }
+    }
+
+    /// A change that is kept in the history so it can be undone.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum Action {
+        IncrementStat,
+        DecrementStat,
+        ToggleStat,
+        SetStat,
+        AddCharacter,
+        RemoveCharacter,
+        AddStat,
+        RemoveStat,
+        RenameCharacter,
+        RenameStat,
+    }
+
+    impl TryFrom<&str> for Action {
+        type Error = ApiError;
+
+        fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
+            match value {
+                "increment_stat" => Ok(Action::IncrementStat),
+                "decrement_stat" => Ok(Action::DecrementStat),
+                "toggle_stat" => Ok(Action::ToggleStat),
+                "set_stat" => Ok(Action::SetStat),
+                "add_character" => Ok(Action::AddCharacter),
+                "remove_character" => Ok(Action::RemoveCharacter),
+                "add_stat" => Ok(Action::AddStat),
+                "remove_stat" => Ok(Action::RemoveStat),
+                "rename_character" => Ok(Action::RenameCharacter),
+                "rename_stat" => Ok(Action::RenameStat),
+                _ => Err(ApiError::invalid("action", value)),
+            }
+        }
+    }
+
+    impl FromSql for Action {
+        fn column_result(value: ValueRef) -> FromSqlResult<Self> {
+            String::column_result(value).and_then(|s| {
+                Action::try_from(s.as_str()).map_err(|err| FromSqlError::Other(err.into()))
+            })
+        }
+    }
+
+    impl ToSql for Action {
+        fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+            match self {
+                Action::IncrementStat => Ok("increment_stat".into()),
+                Action::DecrementStat => Ok("decrement_stat".into()),
+                Action::ToggleStat => Ok("toggle_stat".into()),
+                Action::SetStat => Ok("set_stat".into()),
+                Action::AddCharacter => Ok("add_character".into()),
+                Action::RemoveCharacter => Ok("remove_character".into()),
+                Action::AddStat => Ok("add_stat".into()),
+                Action::RemoveStat => Ok("remove_stat".into()),
+                Action::RenameCharacter => Ok("rename_character".into()),
+                Action::RenameStat => Ok("rename_stat".into()),
+            }
+        }
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum HistoryStatus {
+        Done,
+        Undone,
+        /// Undone and then followed by another change, it can't be redone.
+        Discarded,
+    }
+
+    impl TryFrom<&str> for HistoryStatus {
+        type Error = ApiError;
+
+        fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
+            match value {
+                "done" => Ok(HistoryStatus::Done),
+                "undone" => Ok(HistoryStatus::Undone),
+                "discarded" => Ok(HistoryStatus::Discarded),
+                _ => Err(ApiError::invalid("history status", value)),
+            }
+        }
+    }
+
+    impl FromSql for HistoryStatus {
+        fn column_result(value: ValueRef) -> FromSqlResult<Self> {
+            String::column_result(value).and_then(|s| {
+                HistoryStatus::try_from(s.as_str()).map_err(|err| FromSqlError::Other(err.into()))
+            })
+        }
+    }
+
+    impl ToSql for HistoryStatus {
+        fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+            match self {
+                HistoryStatus::Done => Ok("done".into()),
+                HistoryStatus::Undone => Ok("undone".into()),
+                HistoryStatus::Discarded => Ok("discarded".into()),
+            }
+        }
+    }
+
+    #[derive(serde::Serialize)]
+    pub struct HistoryEntry {
+        pub id: i64,
+        /// Unix time of the change.
+        pub time: i64,
+        pub actor: Option<String>,
+        pub action: Action,
+        pub character: Option<String>,
+        pub stat: Option<String>,
+        pub before: serde_json::Value,
+        pub after: serde_json::Value,
+        pub status: HistoryStatus,
+    }
+
+    /// A change as it's added to the history, `before` and `after` hold a stat
+    /// value, a [`CharacterSnapshot`] or a [`StatSnapshot`].
+    pub struct Change<'c> {
+        pub action: Action,
+        pub character: Option<&'c str>,
+        pub stat: Option<&'c str>,
+        pub before: serde_json::Value,
+        pub after: serde_json::Value,
+    }
+
+    /// Everything needed to bring back a removed character.
+    #[derive(Default, serde::Serialize, serde::Deserialize)]
+    pub struct CharacterSnapshot {
+        pub owner: Option<String>,
+        /// Missing from entries made before there were templates, those
+        /// characters had the base template.
+        #[serde(default)]
+        pub template: Option<String>,
+        pub stats: BTreeMap<String, StatValue>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub inventory: Vec<ItemSnapshot>,
+    }
+
+    /// An inventory item of a removed character, `weapon` is the weapon id.
+    #[derive(serde::Serialize, serde::Deserialize)]
+    pub struct ItemSnapshot {
+        pub weapon: String,
+        pub nickname: Option<String>,
+        pub equipped: bool,
+        pub notes: String,
+    }
+
+    /// Everything needed to bring back a removed template stat, `values` are
+    /// by character name.
+    #[derive(serde::Serialize, serde::Deserialize)]
+    pub struct StatSnapshot {
+        #[serde(flatten)]
+        pub typ: StatType,
+        #[serde(flatten)]
+        pub meta: StatMeta,
+        #[serde(default)]
+        pub template: Option<String>,
+        pub values: BTreeMap<String, StatValue>,
+    }
+
+    #[derive(Default)]
+    pub struct HistoryFilter<'f> {
+        pub actor: Option<&'f str>,
+        pub character: Option<&'f str>,
+        pub stat: Option<&'f str>,
+        pub action: Option<Action>,
+        /// Unix time of the oldest change.
+        pub since: Option<i64>,
+        /// Unix time the changes were made before.
+        pub until: Option<i64>,
+        pub limit: i64,
+    }
+
+    #[derive(serde::Serialize)]
+    pub struct CharacterStat {
+        pub name: String,
+        #[serde(flatten)]
+        pub typ: StatType,
+        pub value: StatValue,
+    }
+
+    #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum Company {
+        Arksys,
+        Dikarum,
+        Pecora,
+        Sisterhood,
+        Theia,
+        WestField,
+    }
+
+    impl Company {
+        fn to_lower_name(self) -> &'static str {
+            match self {
+                Company::Arksys => "arksys",
+                Company::Dikarum => "dikarum",
+                Company::Pecora => "pecora",
+                Company::Sisterhood => "sisterhood",
+                Company::Theia => "theia",
+                Company::WestField => "west_field",
+            }
+        }
+    }
+
+    impl TryFrom<&str> for Company {
+        type Error = ApiError;
+
+        fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
+            match value {
+                "arksys" => Ok(Company::Arksys),
+                "dikarum" => Ok(Company::Dikarum),
+                "pecora" => Ok(Company::Pecora),
+                "sisterhood" => Ok(Company::Sisterhood),
+                "theia" => Ok(Company::Theia),
+                "west_field" => Ok(Company::WestField),
+                _ => Err(ApiError::invalid("company", value)),
+            }
+        }
+    }
+
+    impl fmt::Display for Company {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Company::Arksys => write!(f, "Arksys Inc"),
+                Company::Dikarum => write!(f, "Dikarum & Sons"),
+                Company::Pecora => write!(f, "Pecora Group"),
+                Company::Sisterhood => write!(f, "Sisterhood of Blight"),
+                Company::Theia => write!(f, "Theia Manufacturing"),
+                Company::WestField => write!(f, "West Field Mining Munitions"),
+            }
+        }
+    }
+
+    impl FromSql for Company {
+        fn column_result(value: ValueRef) -> FromSqlResult<Self> {
+            String::column_result(value).and_then(|s| {
+                Company::try_from(s.as_str()).map_err(|err| FromSqlError::Other(err.into()))
+            })
+        }
+    }
+
+    impl ToSql for Company {
+        fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+            Ok(self.to_lower_name().into())
+        }
+    }
+
+    #[derive(Clone, serde::Serialize, serde::Deserialize)]
+    pub struct Part {
+        pub id: u8,
+        pub name: String,
+        pub details: String,
+        #[serde(rename = "type")]
+        pub typ: PartType,
+        pub rarity: Rarity,
+        pub company: Company,
+    }
+
+    #[derive(
+        Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+    )]
+    #[serde(rename_all = "snake_case")]
+    pub enum PartType {
+        Body,
+        Barrel,
+        Magazine,
+        Stock,
+    }
+
+    impl TryFrom<&str> for PartType {
+        type Error = ApiError;
+
+        fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
+            match value {
+                "body" => Ok(PartType::Body),
+                "barrel" => Ok(PartType::Barrel),
+                "magazine" => Ok(PartType::Magazine),
+                "stock" => Ok(PartType::Stock),
+                _ => Err(ApiError::invalid("part type", value)),
+            }
+        }
+    }
+
+    impl fmt::Display for PartType {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                PartType::Body => write!(f, "body"),
+                PartType::Barrel => write!(f, "barrel"),
+                PartType::Magazine => write!(f, "magazine"),
+                PartType::Stock => write!(f, "stock"),
+            }
+        }
+    }
+
+    impl FromSql for PartType {
+        fn column_result(value: ValueRef) -> FromSqlResult<Self> {
+            String::column_result(value).and_then(|s| {
+                PartType::try_from(s.as_str()).map_err(|err| FromSqlError::Other(err.into()))
+            })
+        }
+    }
+
+    impl ToSql for PartType {
+        fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+            match self {
+                PartType::Body => Ok("body".into()),
+                PartType::Barrel => Ok("barrel".into()),
+                PartType::Magazine => Ok("magazine".into()),
+                PartType::Stock => Ok("stock".into()),
+            }
+        }
+    }
+
+    #[derive(
+        Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+    )]
+    #[serde(rename_all = "snake_case")]
+    pub enum Rarity {
+        Common,
+        Uncommon,
+        Rare,
+        Epic,
+        Legendary,
+        Unique,
+    }
+
+    impl TryFrom<&str> for Rarity {
+        type Error = ApiError;
+
+        fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
+            match value {
+                "common" => Ok(Rarity::Common),
+                "uncommon" => Ok(Rarity::Uncommon),
+                "rare" => Ok(Rarity::Rare),
+                "epic" => Ok(Rarity::Epic),
+                "legendary" => Ok(Rarity::Legendary),
+                "unique" => Ok(Rarity::Unique),
+                _ => Err(ApiError::invalid("rarity", value)),
+            }
+        }
+    }
+
+    impl fmt::Display for Rarity {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Rarity::Common => write!(f, "common"),
+                Rarity::Uncommon => write!(f, "uncommon"),
+                Rarity::Rare => write!(f, "rare"),
+                Rarity::Epic => write!(f, "epic"),
+                Rarity::Legendary => write!(f, "legendary"),
+                Rarity::Unique => write!(f, "unique"),
+            }
+        }
+    }
+
+    impl ops::BitOr for Rarity {
+        type Output = Self;
+
+        fn bitor(self, rhs: Self) -> Self::Output {
+            match self.cmp(&rhs) {
+                Ordering::Less => rhs,
+                Ordering::Equal => self,
+                Ordering::Greater => self,
+            }
+        }
+    }
+
+    impl FromSql for Rarity {
+        fn column_result(value: ValueRef) -> FromSqlResult<Self> {
+            String::column_result(value).and_then(|s| {
+                Rarity::try_from(s.as_str()).map_err(|err| FromSqlError::Other(err.into()))
+            })
+        }
+    }
+
+    impl ToSql for Rarity {
+        fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+            match self {
+                Rarity::Common => Ok("common".into()),
+                Rarity::Uncommon => Ok("uncommon".into()),
+                Rarity::Rare => Ok("rare".into()),
+                Rarity::Epic => Ok("epic".into()),
+                Rarity::Legendary => Ok("legendary".into()),
+                Rarity::Unique => Ok("unique".into()),
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    pub enum WeaponError {
+        /// The id isn't hex or its parity byte doesn't match.
+        InvalidId,
+        InvalidLevel(u8),
+        InvalidType(u8),
+        MissingPart(u8),
+        MissingLegacyPart(u8),
+        MissingCurve(WeaponType),
+        NoParts(PartType),
+        /// None of the allowed weapon types can be generated.
+        NoTypes,
+        /// No weapon of at least this rarity came up.
+        RarityNotReached(Rarity),
+        /// A batch with unique parts asked for more weapons than there are
+        /// parts of a type to build them from.
+        NotEnoughParts {
+            typ: PartType,
+            available: usize,
+            count: usize,
+        },
+        /// A `weapon_curves` row with an unknown weapon or curve type.
+        InvalidCurve(String, String),
+    }
+
+    impl fmt::Display for WeaponError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                WeaponError::InvalidId => write!(f, "invalid weapon id"),
+                WeaponError::InvalidLevel(level) => {
+                    write!(f, "invalid level {}, the maximum is {}", level, LEVEL_MAX)
+                }
+                WeaponError::InvalidType(index) => write!(f, "unknown weapon type {}", index),
+                WeaponError::MissingPart(id) => write!(f, "missing weapon part {}", id),
+                WeaponError::MissingLegacyPart(position) => {
+                    write!(f, "missing legacy weapon part at position {}", position)
+                }
+                WeaponError::MissingCurve(typ) => write!(f, "missing weapon curve for {}", typ),
+                WeaponError::NoParts(typ) => write!(f, "no {} parts available", typ),
+                WeaponError::NoTypes => write!(f, "no weapon types available"),
+                WeaponError::RarityNotReached(rarity) => {
+                    write!(f, "no weapon of at least {} rarity came up", rarity)
+                }
+                WeaponError::NotEnoughParts {
+                    typ,
+                    available,
+                    count,
+                } => write!(
+                    f,
+                    "can't generate {} weapons with unique parts, {} {} parts can come up so at most {} can be generated",
+                    count, available, typ, available
+                ),
+                WeaponError::InvalidCurve(name, typ) => {
+                    write!(f, "invalid weapon curve `{}` of type `{}`", name, typ)
+                }
+            }
+        }
+    }
+
+    impl std::error::Error for WeaponError {}
+
+    impl From<WeaponError> for ApiError {
+        fn from(err: WeaponError) -> Self {
+            match err {
+                WeaponError::InvalidId | WeaponError::InvalidLevel(_) => {
+                    ApiError::InvalidValue(err.to_string())
+                }
+                _ => ApiError::Unprocessable(err.to_string()),
+            }
+        }
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    pub struct WeaponDisplay {
+        pub level: u8,
+        pub id: String,
+        pub name: String,
+        pub rarity: Rarity,
+        #[serde(rename = "type")]
+        pub typ: WeaponType,
+        pub company: Company,
+        pub barrel: Part,
+        pub body: Part,
+        pub magazine: Part,
+        pub stock: Part,
+        pub range: String,
+        pub damage: String,
+        pub details: Vec<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub seed: Option<u32>,
+    }
+
+    /// How often a weapon type is generated relative to the others, from
+    /// `level` up to the next level the type has a weight for.
+    #[derive(serde::Serialize, serde::Deserialize)]
+    pub struct WeaponWeight {
+        #[serde(rename = "type")]
+        pub typ: WeaponType,
+        #[serde(default)]
+        pub level: u8,
+        pub weight: u32,
+    }
+
+    /// How many times parts are rolled for a weapon before giving up on
+    /// reaching the minimum rarity.
+    static RARITY_ATTEMPTS: u32 = 1000;
+
+    /// How often parts of a rarity come up relative to the others, from `level`
+    /// up to the next level the rarity has a weight for.
+    #[derive(serde::Serialize, serde::Deserialize)]
+    pub struct RarityWeight {
+        pub rarity: Rarity,
+        #[serde(default)]
+        pub level: u8,
+        pub weight: u32,
+    }
+
+    /// The weights weapons are generated with.
+    pub struct DropTable {
+        pub types: Vec<WeaponWeight>,
+        pub rarities: Vec<RarityWeight>,
+    }
+
+    /// The weights of `weights` that hold at `level`, for each key the one with
+    /// the highest level at or below it. `band` gives the key and level of a
+    /// weight.
+    fn at_level<W, K: PartialEq>(
+        weights: &[W],
+        level: u8,
+        band: impl Fn(&W) -> (K, u8),
+    ) -> Vec<&W> {
+        weights
+            .iter()
+            .filter(|w| {
+                let (key, from) = band(w);
+
+                from <= level
+                    && !weights.iter().any(|o| {
+                        let (other, start) = band(o);
+
+                        other == key && start <= level && start > from
+                    })
+            })
+            .collect()
+    }
+
+    /// What generated loot is limited to, nothing is by default.
+    #[derive(Default)]
+    pub struct LootFilter {
+        pub min_rarity: Option<Rarity>,
+        /// The weapon types that may come up, all when empty.
+        pub types: Vec<WeaponType>,
+        /// The companies the weapon bodies may be made by, all when empty.
+        pub companies: Vec<Company>,
+        /// Whether a batch uses every part in one weapon at most.
+        pub unique_parts: bool,
+        /// Every step up in rarity is `2^luck` times as likely.
+        pub luck: f64,
+    }
+
+    /// A weapon in the library of a campaign.
+    #[derive(serde::Serialize)]
+    pub struct SavedWeapon {
+        pub id: i64,
+        /// Unix time the weapon was saved.
+        pub time: i64,
+        pub tags: Vec<String>,
+        pub weapon: WeaponDisplay,
+    }
+
+    #[derive(Default)]
+    pub struct WeaponFilter<'f> {
+        pub rarity: Option<Rarity>,
+        pub company: Option<Company>,
+        pub typ: Option<WeaponType>,
+        pub min_level: Option<u8>,
+        pub max_level: Option<u8>,
+        pub tag: Option<&'f str>,
+        /// Part of the name or of a tag.
+        pub search: Option<&'f str>,
+        pub limit: i64,
+    }
+
+    #[derive(serde::Serialize)]
+    pub struct Weapon {
+        pub level: u8,
+        pub id: Id,
+        pub rarity: Rarity,
+        #[serde(rename = "type")]
+        pub typ: WeaponType,
+        pub company: Company,
+        pub barrel: Part,
+        pub body: Part,
+        pub magazine: Part,
+        pub stock: Part,
+        pub damage: f32,
+        /// What the weapon was generated with, `None` when it was built from
+        /// its id.
+        pub seed: Option<u32>,
+    }
+
+    impl Weapon {
+        /// Rolls a random weapon, the same `seed` and parts give the same weapon.
+        pub fn generate(
+            parts: &[Part],
+            curves: &[WeaponCurve],
+            table: &DropTable,
+            level: u8,
+            luck: f64,
+            seed: u32,
+        ) -> Result<Self, WeaponError> {
+            let filter = LootFilter {
+                luck,
+                ..Default::default()
+            };
+
+            Self::generate_filtered(parts, curves, table, level, &filter, seed)
+        }
+
+        /// Rolls `count` weapons that match `filter`, the weapons get the
+        /// seeds counting up from `seed`.
+        pub fn generate_batch(
+            parts: &[Part],
+            curves: &[WeaponCurve],
+            table: &DropTable,
+            level: u8,
+            count: usize,
+            filter: &LootFilter,
+            seed: u32,
+        ) -> Result<Vec<Self>, WeaponError> {
+            use PartType::*;
+
+            if filter.unique_parts {
+                let rarities = at_level(&table.rarities, level, |w| (w.rarity, w.level))
+                    .into_iter()
+                    .filter(|w| w.weight > 0)
+                    .map(|w| w.rarity)
+                    .collect::<Vec<_>>();
+
+                for typ in [Barrel, Body, Magazine, Stock] {
+                    let available = parts
+                        .iter()
+                        .filter(|part| part.typ == typ && rarities.contains(&part.rarity))
+                        .filter(|part| {
+                            part.typ != Body
+                                || filter.companies.is_empty()
+                                || filter.companies.contains(&part.company)
+                        })
+                        .count();
+
+                    if available < count {
+                        return Err(WeaponError::NotEnoughParts {
+                            typ,
+                            available,
+                            count,
+                        });
+                    }
+                }
+            }
+
+            let mut parts = parts.to_vec();
+            let mut weapons = Vec::with_capacity(count);
+
+            for seed in (0..count).map(|i| seed.wrapping_add(i as u32)) {
+                let weapon = Self::generate_filtered(&parts, curves, table, level, filter, seed)?;
+
+                if filter.unique_parts {
+                    let used = [
+                        weapon.barrel.id,
+                        weapon.body.id,
+                        weapon.magazine.id,
+                        weapon.stock.id,
+                    ];
+
+                    parts.retain(|part| !used.contains(&part.id));
+                }
+
+                weapons.push(weapon);
+            }
+
+            Ok(weapons)
+        }
+
+        fn generate_filtered(
+            parts: &[Part],
+            curves: &[WeaponCurve],
+            table: &DropTable,
+            level: u8,
+            filter: &LootFilter,
+            seed: u32,
+        ) -> Result<Self, WeaponError> {
+            use PartType::*;
+
+            if level > LEVEL_MAX {
+                return Err(WeaponError::InvalidLevel(level));
+            }
+
+            let mut rng = ChaCha8Rng::seed_from_u64(seed.into());
+
+            let types = at_level(&table.types, level, |w| (w.typ, w.level))
+                .into_iter()
+                .filter(|w| filter.types.is_empty() || filter.types.contains(&w.typ))
+                .collect::<Vec<_>>();
+
+            let index = WeightedIndex::new(types.iter().map(|w| w.weight))
+                .map_err(|_| WeaponError::NoTypes)?;
+
+            let typ = types[index.sample(&mut rng)].typ;
+
+            let curve = curves
+                .iter()
+                .find(|c| c.typ == typ)
+                .ok_or(WeaponError::MissingCurve(typ))?;
+
+            // the company of a weapon is the one that made its body
+            let parts = parts
+                .iter()
+                .filter(|part| {
+                    part.typ != Body
+                        || filter.companies.is_empty()
+                        || filter.companies.contains(&part.company)
+                })
+                .cloned()
+                .collect::<Vec<_>>();
+
+            let rarities = at_level(&table.rarities, level, |w| (w.rarity, w.level))
+                .into_iter()
+                .map(|w| {
+                    let steps = w.rarity as i32 as f64;
+
+                    (w.rarity, w.weight as f64 * (filter.luck * steps).exp2())
+                })
+                .collect::<Vec<_>>();
+
+            let mut attempts = 0;
+            let (barrel, body, magazine, stock, rarity) = loop {
+                let barrel = Self::generate_part(&parts, &mut rng, &rarities, Barrel)
+                    .ok_or(WeaponError::NoParts(Barrel))?;
+                let body = Self::generate_part(&parts, &mut rng, &rarities, Body)
+                    .ok_or(WeaponError::NoParts(Body))?;
+                let magazine = Self::generate_part(&parts, &mut rng, &rarities, Magazine)
+                    .ok_or(WeaponError::NoParts(Magazine))?;
+                let stock = Self::generate_part(&parts, &mut rng, &rarities, Stock)
+                    .ok_or(WeaponError::NoParts(Stock))?;
+
+                let rarity = body.rarity | barrel.rarity | magazine.rarity | stock.rarity;
+
+                match filter.min_rarity {
+                    Some(min) if rarity < min => {
+                        attempts += 1;
+
+                        if attempts == RARITY_ATTEMPTS {
+                            return Err(WeaponError::RarityNotReached(min));
+                        }
+                    }
+                    _ => break (barrel, body, magazine, stock, rarity),
+                }
+            };
+
+            let id = Id::from(
+                level,
+                typ.index(),
+                body.id,
+                barrel.id,
+                magazine.id,
+                stock.id,
+            );
+
+            let damage = curve.curve.evaluate(utils::rescale(
+                level as f32,
+                0.0..(LEVEL_MAX as f32),
+                0.0..1.0,
+            ));
+
+            Ok(Self {
+                level,
+                id,
+                rarity,
+                typ,
+                company: body.company,
+                barrel,
+                body,
+                magazine,
+                stock,
+                damage,
+                seed: Some(seed),
+            })
+        }
+
+        /// Rolls the rarity from `rarities` first, then one of the parts of
+        /// that rarity, so how many parts a rarity has doesn't matter.
+        fn generate_part<R: Rng>(
+            parts: &[Part],
+            rng: &mut R,
+            rarities: &[(Rarity, f64)],
+            typ: PartType,
+        ) -> Option<Part> {
+            let rarities = rarities
+                .iter()
+                .filter(|(rarity, _)| parts.iter().any(|p| p.typ == typ && p.rarity == *rarity))
+                .collect::<Vec<_>>();
+
+            let index = WeightedIndex::new(rarities.iter().map(|(_, weight)| weight)).ok()?;
+            let rarity = rarities[index.sample(rng)].0;
+
+            let filtered = parts
+                .iter()
+                .filter(|p| p.typ == typ)
+                .filter(|p| p.rarity == rarity);
+
+            filtered.choose(rng).cloned()
+        }
+
+        pub fn from_id(
+            parts: &[Part],
+            curves: &[WeaponCurve],
+            id: Id,
+        ) -> Result<Self, WeaponError> {
+            let part = |id: u8| {
+                parts
+                    .iter()
+                    .find(|p| p.id == id)
+                    .cloned()
+                    .ok_or(WeaponError::MissingPart(id))
+            };
+
+            let level = id.level();
+            if level > LEVEL_MAX {
+                return Err(WeaponError::InvalidLevel(level));
+            }
+
+            let body = part(id.body())?;
+            let barrel = part(id.barrel())?;
+            let magazine = part(id.magazine())?;
+            let stock = part(id.stock())?;
+
+            let rarity = body.rarity | barrel.rarity | magazine.rarity | stock.rarity;
+
+            let typ = WeaponType::from_index(id.typ()).ok_or(WeaponError::InvalidType(id.typ()))?;
+
+            let curve = curves
+                .iter()
+                .find(|c| c.typ == typ)
+                .ok_or(WeaponError::MissingCurve(typ))?;
+
+            let damage = curve.curve.evaluate(utils::rescale(
+                level as f32,
+                0.0..(LEVEL_MAX as f32),
+                0.0..1.0,
+            ));
+
+            Ok(Self {
+                level,
+                id,
+                rarity,
+                typ,
+                company: body.company,
+                barrel,
+                body,
+                magazine,
+                stock,
+                damage,
+                seed: None,
+            })
+        }
+
+        pub fn name(&self) -> String {
+            format!("{} {}", self.barrel.name, self.body.name)
+        }
+
+        pub fn damage(&self) -> String {
+            let base = match self.typ {
+                WeaponType::Assault => 1.0,
+                WeaponType::Grenade => 1.0,
+                WeaponType::Pistol => 1.0,
+                WeaponType::Rocket => 1.0,
+                WeaponType::Shotgun => 1.0,
+                WeaponType::Sniper => 1.0,
+                WeaponType::Submachine => 1.0,
+            };
+
+            (base + (base * self.damage).round()).to_string()
+        }
+
+        pub fn range(&self) -> &str {
+            match self.typ {
+                WeaponType::Assault => "mid-far",
+                WeaponType::Grenade => "mid",
+                WeaponType::Pistol => "close-near",
+                WeaponType::Rocket => "mid-far",
+                WeaponType::Shotgun => "mid",
+                WeaponType::Sniper => "far",
+                WeaponType::Submachine => "close-mid",
+            }
+        }
+
+        pub fn details(&self) -> WeaponDetailsIter<'_> {
+            WeaponDetailsIter {
+                weapon: self,
+                index: 0,
+            }
+        }
+
+        pub fn display(self) -> WeaponDisplay {
+            WeaponDisplay {
+                level: self.level,
+                id: self.id.to_string(),
+                name: self.name(),
+                rarity: self.rarity,
+                typ: self.typ,
+                company: self.company,
+                barrel: self.barrel.clone(),
+                body: self.body.clone(),
+                magazine: self.magazine.clone(),
+                stock: self.stock.clone(),
+                range: self.range().to_string(),
+                damage: self.damage(),
+                details: self.details().collect(),
+                seed: self.seed,
+            }
         }
     }
 
-    #[derive(serde::Serialize)]
-    pub struct State {
-        pub stats: Vec<Stat>,
-        pub characters: Vec<Character>,
-        pub parts: Vec<Part>,
-        pub weapon: Option<WeaponDisplay>,
+    pub struct WeaponDetailsIter<'w> {
+        weapon: &'w Weapon,
+        index: u8,
     }
 
-    #[derive(serde::Serialize)]
-    pub struct Character {
-        pub name: String,
-        pub stats: Vec<CharacterStat>,
+    impl<'w> Iterator for WeaponDetailsIter<'w> {
+        type Item = String;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            loop {
+                match self.index {
+                    0 => {
+                        if !self.weapon.body.details.is_empty() {
+                            self.index += 1;
+
+                            return Some(self.weapon.body.details.clone());
+                        }
+                    }
+                    1 => {
+                        if !self.weapon.barrel.details.is_empty() {
+                            self.index += 1;
+
+                            return Some(self.weapon.barrel.details.clone());
+                        }
+                    }
+                    2 => {
+                        if !self.weapon.magazine.details.is_empty() {
+                            self.index += 1;
+
+                            return Some(self.weapon.magazine.details.clone());
+                        }
+                    }
+                    3 => {
+                        if !self.weapon.stock.details.is_empty() {
+                            self.index += 1;
+
+                            return Some(self.weapon.stock.details.clone());
+                        }
+                    }
+                    _ => return None,
+                }
+
+                self.index += 1;
+            }
+        }
     }
 
-    #[derive(serde::Serialize)]
-    pub struct CharacterStat {
-        pub name: String,
-        #[serde(rename = "type")]
-        pub typ: StatType,
-        pub value: i32,
+    pub struct WeaponCurve {
+        pub typ: WeaponType,
+        pub curve: Curve,
     }
 
-    #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize)]
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
     #[serde(rename_all = "snake_case")]
-    pub enum Company {
-        Arksys,
-        Dikarum,
-        Pecora,
-        Sisterhood,
-        Theia,
-        WestField,
+    pub enum WeaponType {
+        Assault,
+        Grenade,
+        Pistol,
+        Rocket,
+        Shotgun,
+        Sniper,
+        Submachine,
     }
 
-    impl Company {
-        fn to_lower_name(self) -> &'static str {
+    impl WeaponType {
+        fn index(&self) -> u8 {
             match self {
-                Company::Arksys => "arksys",
-                Company::Dikarum => "dikarum",
-                Company::Pecora => "pecora",
-                Company::Sisterhood => "sisterhood",
-                Company::Theia => "theia",
-                Company::WestField => "west_field",
+                WeaponType::Assault => 0,
+                WeaponType::Grenade => 1,
+                WeaponType::Pistol => 2,
+                WeaponType::Rocket => 3,
+                WeaponType::Shotgun => 4,
+                WeaponType::Sniper => 5,
+                WeaponType::Submachine => 6,
             }
         }
-    }
 
-    impl TryFrom<&str> for Company {
-        type Error = FromSqlError;
-
-        fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
-            match value {
-                "arksys" => Ok(Company::Arksys),
-                "dikarum" => Ok(Company::Dikarum),
-                "pecora" => Ok(Company::Pecora),
-                "sisterhood" => Ok(Company::Sisterhood),
-                "theia" => Ok(Company::Theia),
-                "west_field" => Ok(Company::WestField),
-                _ => Err(FromSqlError::InvalidType),
+        fn from_index(index: u8) -> Option<Self> {
+            match index {
+                0 => Some(WeaponType::Assault),
+                1 => Some(WeaponType::Grenade),
+                2 => Some(WeaponType::Pistol),
+                3 => Some(WeaponType::Rocket),
+                4 => Some(WeaponType::Shotgun),
+                5 => Some(WeaponType::Sniper),
+                6 => Some(WeaponType::Submachine),
+                _ => None,
             }
         }
     }
 
-    impl fmt::Display for Company {
+    impl fmt::Display for WeaponType {
         fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
             match self {
-                Company::Arksys => write!(f, "Arksys Inc"),
-                Company::Dikarum => write!(f, "Dikarum & Sons"),
-                Company::Pecora => write!(f, "Pecora Group"),
-                Company::Sisterhood => write!(f, "Sisterhood of Blight"),
-                Company::Theia => write!(f, "Theia Manufacturing"),
-                Company::WestField => write!(f, "West Field Mining Munitions"),
+                WeaponType::Assault => write!(f, "assault rifle"),
+                WeaponType::Grenade => write!(f, "grenade launcher"),
+                WeaponType::Pistol => write!(f, "pistol"),
+                WeaponType::Rocket => write!(f, "rocket launcher"),
+                WeaponType::Shotgun => write!(f, "shotgun"),
+                WeaponType::Sniper => write!(f, "sniper rifle"),
+                WeaponType::Submachine => write!(f, "submachine gun"),
+            }
+        }
+    }
+
+    impl TryFrom<&str> for WeaponType {
+        type Error = ApiError;
+
+        fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
+            match value {
+                "assault rifle" => Ok(WeaponType::Assault),
+                "grenade launcher" => Ok(WeaponType::Grenade),
+                "pistol" => Ok(WeaponType::Pistol),
+                "rocket launcher" => Ok(WeaponType::Rocket),
+                "shotgun" => Ok(WeaponType::Shotgun),
+                "sniper rifle" => Ok(WeaponType::Sniper),
+                "submachine gun" => Ok(WeaponType::Submachine),
+                _ => Err(ApiError::invalid("weapon type", value)),
             }
         }
     }
 
-    impl FromSql for Company {
+    impl FromSql for WeaponType {
         fn column_result(value: ValueRef) -> FromSqlResult<Self> {
-            String::column_result(value).and_then(|s| Company::try_from(s.as_str()))
+            String::column_result(value).and_then(|s| {
+                WeaponType::try_from(s.as_str()).map_err(|err| FromSqlError::Other(err.into()))
+            })
         }
     }
 
-    impl ToSql for Company {
-        fn to_sql(&self) -> rusqlite::Result<ToSqlOutput> {
-            Ok(self.to_lower_name().into())
+    impl ToSql for WeaponType {
+        fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+            Ok(self.to_string().into())
         }
     }
 
-    #[derive(Clone, serde::Serialize)]
-    pub struct Part {
+    #[derive(serde::Serialize)]
+    pub struct Stat {
         pub name: String,
-        pub details: String,
-        #[serde(rename = "type")]
-        pub typ: PartType,
-        pub rarity: Rarity,
-        pub company: Company,
+        pub template: String,
+        #[serde(flatten)]
+        pub typ: StatType,
+        #[serde(flatten)]
+        pub meta: StatMeta,
+    }
+
+    /// How a template stat is shown on stat sheets and what characters start
+    /// with, sent next to the stat type.
+    #[derive(Clone, Default, serde::Serialize, serde::Deserialize)]
+    #[serde(default)]
+    pub struct StatMeta {
+        /// Used instead of the default of the stat type.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub default: Option<StatValue>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub label: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub description: Option<String>,
+        /// Stats are sorted by this and then by when they were added.
+        pub order: i64,
+        /// Stats with the same category are shown together.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub category: Option<String>,
+        pub visibility: Visibility,
     }
 
-    #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize)]
+    impl StatMeta {
+        /// The value characters start with for a stat of `typ`.
+        pub fn default_value(&self, typ: &StatType) -> StatValue {
+            self.default.clone().unwrap_or_else(|| typ.default_value())
+        }
+    }
+
+    /// Who a stat is sent to.
+    #[derive(Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
     #[serde(rename_all = "snake_case")]
-    pub enum PartType {
-        Body,
-        Barrel,
-        Magazine,
-        Stock,
+    pub enum Visibility {
+        #[default]
+        Public,
+        /// Only the gm sees the stat, on the template and on characters.
+        Gm,
     }
 
-    impl TryFrom<&str> for PartType {
-        type Error = FromSqlError;
+    impl TryFrom<&str> for Visibility {
+        type Error = ApiError;
 
         fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
             match value {
-                "body" => Ok(PartType::Body),
-                "barrel" => Ok(PartType::Barrel),
-                "magazine" => Ok(PartType::Magazine),
-                "stock" => Ok(PartType::Stock),
-                _ => Err(FromSqlError::InvalidType),
+                "public" => Ok(Visibility::Public),
+                "gm" => Ok(Visibility::Gm),
+                _ => Err(ApiError::invalid("visibility", value)),
             }
         }
     }
 
-    impl fmt::Display for PartType {
-        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    impl FromSql for Visibility {
+        fn column_result(value: ValueRef) -> FromSqlResult<Self> {
+            String::column_result(value).and_then(|s| {
+                Visibility::try_from(s.as_str()).map_err(|err| FromSqlError::Other(err.into()))
+            })
+        }
+    }
+
+    impl ToSql for Visibility {
+        fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
             match self {
-                PartType::Body => write!(f, "body"),
-                PartType::Barrel => write!(f, "barrel"),
-                PartType::Magazine => write!(f, "magazine"),
-                PartType::Stock => write!(f, "stock"),
+                Visibility::Public => Ok("public".into()),
+                Visibility::Gm => Ok("gm".into()),
             }
         }
     }
 
-    impl FromSql for PartType {
-        fn column_result(value: ValueRef) -> FromSqlResult<Self> {
-            String::column_result(value).and_then(|s| PartType::try_from(s.as_str()))
-        }
+    /// The kind of value a stat holds, serialized with its `type` next to
+    /// the settings it has.
+    #[derive(Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+    #[serde(tag = "type", rename_all = "snake_case")]
+    pub enum StatType {
+        Number,
+        Boolean,
+        Text,
+        /// One of a fixed list of options.
+        Enum {
+            options: Vec<String>,
+        },
+        /// A number that's kept between `min` and `max`.
+        Bounded {
+            min: i32,
+            max: i32,
+        },
+        /// A dice expression like `2d6+1`.
+        Dice,
+        /// Computed from other stats by a [`Formula`], it can't be changed directly.
+        Formula {
+            formula: String,
+        },
     }
 
-    impl ToSql for PartType {
-        fn to_sql(&self) -> rusqlite::Result<ToSqlOutput> {
+    impl StatType {
+        /// Builds a stat type from the columns of a template row.
+        pub fn new(
+            kind: &str,
+            options: Option<Vec<String>>,
+            min: Option<i32>,
+            max: Option<i32>,
+            formula: Option<String>,
+        ) -> Result<Self, ApiError> {
+            let typ = match (kind, options, min, max, formula) {
+                ("number", ..) => StatType::Number,
+                ("boolean", ..) => StatType::Boolean,
+                ("text", ..) => StatType::Text,
+                ("enum", Some(options), ..) => StatType::Enum { options },
+                ("bounded", _, Some(min), Some(max), _) => StatType::Bounded { min, max },
+                ("dice", ..) => StatType::Dice,
+                ("formula", .., Some(formula)) => StatType::Formula { formula },
+                _ => return Err(ApiError::invalid("stat type", kind)),
+            };
+
+            typ.check()?;
+
+            Ok(typ)
+        }
+
+        pub fn kind(&self) -> &'static str {
             match self {
-                PartType::Body => Ok("body".into()),
-                PartType::Barrel => Ok("barrel".into()),
-                PartType::Magazine => Ok("magazine".into()),
-                PartType::Stock => Ok("stock".into()),
+                StatType::Number => "number",
+                StatType::Boolean => "boolean",
+                StatType::Text => "text",
+                StatType::Enum { .. } => "enum",
+                StatType::Bounded { .. } => "bounded",
+                StatType::Dice => "dice",
+                StatType::Formula { .. } => "formula",
+            }
+        }
+
+        /// Whether the stat holds a number that formulas can use.
+        pub fn is_numeric(&self) -> bool {
+            match self {
+                StatType::Number
+                | StatType::Boolean
+                | StatType::Bounded { .. }
+                | StatType::Formula { .. } => true,
+                StatType::Text | StatType::Enum { .. } | StatType::Dice => false,
+            }
+        }
+
+        pub fn options(&self) -> Option<&[String]> {
+            match self {
+                StatType::Enum { options } => Some(options),
+                _ => None,
+            }
+        }
+
+        pub fn bounds(&self) -> Option<(i32, i32)> {
+            match self {
+                StatType::Bounded { min, max } => Some((*min, *max)),
+                _ => None,
+            }
+        }
+
+        pub fn formula(&self) -> Option<&str> {
+            match self {
+                StatType::Formula { formula } => Some(formula),
+                _ => None,
+            }
+        }
+
+        /// Checks the settings of the stat type make sense.
+        pub fn check(&self) -> Result<(), ApiError> {
+            match self {
+                StatType::Enum { options } => {
+                    if options.is_empty() {
+                        return Err(ApiError::Unprocessable(
+                            "enum stats need at least one option".to_string(),
+                        ));
+                    }
+
+                    for (index, option) in options.iter().enumerate() {
+                        if options[..index].contains(option) {
+                            return Err(ApiError::Unprocessable(format!(
+                                "option `{}` is listed twice",
+                                option
+                            )));
+                        }
+                    }
+                }
+                StatType::Bounded { min, max } if min > max => {
+                    return Err(ApiError::Unprocessable(format!(
+                        "min {} is larger than max {}",
+                        min, max
+                    )));
+                }
+                StatType::Formula { formula } => {
+                    Formula::try_from(formula.as_str())?;
+                }
+                _ => {}
+            }
+
+            Ok(())
+        }
+
+        /// The value characters start out with.
+        pub fn default_value(&self) -> StatValue {
+            match self {
+                // formula values aren't stored, they're computed when read
+                StatType::Number | StatType::Boolean | StatType::Formula { .. } => {
+                    StatValue::Number(0)
+                }
+                StatType::Text => StatValue::Text(String::new()),
+                StatType::Enum { options } => {
+                    StatValue::Text(options.first().cloned().unwrap_or_default())
+                }
+                StatType::Bounded { min, max } => StatValue::Number(0.clamp(*min, *max)),
+                StatType::Dice => StatValue::Text("0".to_string()),
+            }
+        }
+
+        /// Checks `value` fits the stat type, dice are returned in their
+        /// normal form.
+        pub fn check_value(&self, value: StatValue) -> Result<StatValue, ApiError> {
+            match (self, value) {
+                (StatType::Formula { .. }, _) => Err(ApiError::Unprocessable(
+                    "formula stats are computed and can't be changed".to_string(),
+                )),
+                (StatType::Number, StatValue::Number(value)) => Ok(StatValue::Number(value)),
+                (StatType::Boolean, StatValue::Number(value @ (0 | 1))) => {
+                    Ok(StatValue::Number(value))
+                }
+                (StatType::Boolean, _) => {
+                    Err(ApiError::Unprocessable("expected 0 or 1".to_string()))
+                }
+                (StatType::Bounded { min, max }, StatValue::Number(value)) => {
+                    if (*min..=*max).contains(&value) {
+                        Ok(StatValue::Number(value))
+                    } else {
+                        Err(ApiError::Unprocessable(format!(
+                            "expected a number from {} to {}",
+                            min, max
+                        )))
+                    }
+                }
+                (StatType::Text, StatValue::Text(value)) => Ok(StatValue::Text(value)),
+                (StatType::Enum { options }, StatValue::Text(value)) => {
+                    if options.contains(&value) {
+                        Ok(StatValue::Text(value))
+                    } else {
+                        Err(ApiError::Unprocessable(format!(
+                            "expected one of {}",
+                            options.join(", ")
+                        )))
+                    }
+                }
+                (StatType::Dice, StatValue::Text(value)) => {
+                    Ok(StatValue::Text(Dice::try_from(value.as_str())?.to_string()))
+                }
+                (StatType::Number | StatType::Bounded { .. }, _) => {
+                    Err(ApiError::Unprocessable("expected a number".to_string()))
+                }
+                (StatType::Text | StatType::Enum { .. } | StatType::Dice, _) => {
+                    Err(ApiError::Unprocessable("expected text".to_string()))
+                }
+            }
+        }
+
+        /// Adds `by` to a number, bounded stats are clamped to their bounds.
+        pub fn step(&self, value: &StatValue, by: i32) -> Result<StatValue, ApiError> {
+            match (self, value) {
+                (StatType::Formula { .. }, _) => Err(ApiError::Unprocessable(
+                    "formula stats are computed and can't be changed".to_string(),
+                )),
+                (StatType::Number, StatValue::Number(value)) => {
+                    Ok(StatValue::Number(value.saturating_add(by)))
+                }
+                (StatType::Bounded { min, max }, StatValue::Number(value)) => Ok(
+                    StatValue::Number(value.saturating_add(by).clamp(*min, *max)),
+                ),
+                _ => Err(ApiError::Unprocessable(format!(
+                    "{} stats can't be incremented or decremented",
+                    self.kind()
+                ))),
+            }
+        }
+
+        pub fn toggle(&self, value: &StatValue) -> Result<StatValue, ApiError> {
+            match (self, value) {
+                (StatType::Formula { .. }, _) => Err(ApiError::Unprocessable(
+                    "formula stats are computed and can't be changed".to_string(),
+                )),
+                (StatType::Boolean, StatValue::Number(0)) => Ok(StatValue::Number(1)),
+                (StatType::Boolean, StatValue::Number(_)) => Ok(StatValue::Number(0)),
+                _ => Err(ApiError::Unprocessable(format!(
+                    "{} stats can't be toggled",
+                    self.kind()
+                ))),
             }
         }
     }
 
-    #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize)]
-    #[serde(rename_all = "snake_case")]
-    pub enum Rarity {
-        Common,
-        Uncommon,
-        Rare,
-        Epic,
-        Legendary,
-        Unique,
+    impl ToSql for StatType {
+        fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+            Ok(self.kind().into())
+        }
     }
 
-    impl TryFrom<&str> for Rarity {
-        type Error = FromSqlError;
+    /// Numbers back number, boolean and bounded stats, the others hold text.
+    #[derive(Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+    #[serde(untagged)]
+    pub enum StatValue {
+        Number(i32),
+        Text(String),
+    }
 
-        fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
-            match value {
-                "common" => Ok(Rarity::Common),
-                "uncommon" => Ok(Rarity::Uncommon),
-                "rare" => Ok(Rarity::Rare),
-                "epic" => Ok(Rarity::Epic),
-                "legendary" => Ok(Rarity::Legendary),
-                "unique" => Ok(Rarity::Unique),
-                _ => Err(FromSqlError::InvalidType),
+    impl StatValue {
+        pub fn number(&self) -> Option<i32> {
+            match self {
+                StatValue::Number(value) => Some(*value),
+                StatValue::Text(_) => None,
             }
         }
-    }
 
-    impl fmt::Display for Rarity {
-        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        pub fn text(&self) -> Option<&str> {
             match self {
-                Rarity::Common => write!(f, "common"),
-                Rarity::Uncommon => write!(f, "uncommon"),
-                Rarity::Rare => write!(f, "rare"),
-                Rarity::Epic => write!(f, "epic"),
-                Rarity::Legendary => write!(f, "legendary"),
-                Rarity::Unique => write!(f, "unique"),
+                StatValue::Number(_) => None,
+                StatValue::Text(value) => Some(value),
             }
         }
     }
 
-    impl ops::BitOr for Rarity {
-        type Output = Self;
-
-        fn bitor(self, rhs: Self) -> Self::Output {
-            match self.cmp(&rhs) {
-                Ordering::Less => rhs,
-                Ordering::Equal => self,
-                Ordering::Greater => self,
+    impl ToSql for StatValue {
+        fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+            match self {
+                StatValue::Number(value) => value.to_sql(),
+                StatValue::Text(value) => value.to_sql(),
             }
         }
     }
 
-    impl FromSql for Rarity {
+    impl FromSql for StatValue {
         fn column_result(value: ValueRef) -> FromSqlResult<Self> {
-            String::column_result(value).and_then(|s| Rarity::try_from(s.as_str()))
+            match value {
+                ValueRef::Integer(_) => i32::column_result(value).map(StatValue::Number),
+                _ => String::column_result(value).map(StatValue::Text),
+            }
         }
     }
 
-    impl ToSql for Rarity {
-        fn to_sql(&self) -> rusqlite::Result<ToSqlOutput> {
-            match self {
-                Rarity::Common => Ok("common".into()),
-                Rarity::Uncommon => Ok("uncommon".into()),
-                Rarity::Rare => Ok("rare".into()),
-                Rarity::Epic => Ok("epic".into()),
-                Rarity::Legendary => Ok("legendary".into()),
-                Rarity::Unique => Ok("unique".into()),
+    /// Largest number of dice a single term can roll.
+    static DICE_COUNT_MAX: u32 = 100;
+    /// Largest number of sides a die can have.
+    static DICE_SIDES_MAX: u32 = 1000;
+    /// Largest number of terms a dice expression can have.
+    static DICE_TERMS_MAX: usize = 100;
+    /// Most extra dice an exploding term rolls, so `100d2!` can't run forever.
+    static DICE_EXPLODE_MAX: u32 = 100;
+
+    /// A sum of dice, constants and stats like `2d20kh1 + @dex - 1`.
+    ///
+    /// Dice are written `NdM`, `N` defaults to 1. They can explode with `!`,
+    /// rolling another die for every die that shows its highest side, and
+    /// keep only their highest or lowest dice with `khK` or `klK`.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct Dice {
+        /// Terms with whether they are subtracted.
+        terms: Vec<(bool, DiceTerm)>,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub enum DiceTerm {
+        Roll {
+            count: u32,
+            sides: u32,
+            explode: bool,
+            keep: Option<Keep>,
+        },
+        Constant(u32),
+        /// A stat of the character that is rolled for, written `@name`.
+        Stat(String),
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Keep {
+        Highest(u32),
+        Lowest(u32),
+    }
+
+    /// What a stat referred to by a dice expression holds.
+    pub enum DiceStat {
+        /// A number, which is looked up again when rolling.
+        Number,
+        Dice(Dice),
+    }
+
+    /// How deep dice stats can refer to other dice stats.
+    static DICE_DEPTH_MAX: usize = 8;
+
+    impl Dice {
+        /// `value` with references to the stat `from` pointed at `to`, `None`
+        /// when it has none. Fails when dice can't refer to `to`.
+        pub fn rename(value: &str, from: &str, to: &str) -> Result<Option<String>, ApiError> {
+            let Ok(mut dice) = Dice::try_from(value) else {
+                return Ok(None);
+            };
+
+            let mut found = false;
+            for (_, term) in &mut dice.terms {
+                match term {
+                    DiceTerm::Stat(name) if name == from => {
+                        *name = to.to_string();
+                        found = true;
+                    }
+                    _ => {}
+                }
+            }
+
+            if !found {
+                return Ok(None);
+            }
+
+            // dice ignore whitespace, names with it would be read as another name
+            let renamed = dice.to_string();
+            if Dice::try_from(renamed.as_str()).ok() != Some(dice) {
+                return Err(ApiError::invalid("dice", &renamed));
             }
+
+            Ok(Some(renamed))
         }
-    }
 
-    #[derive(serde::Serialize)]
-    pub struct WeaponDisplay {
-        pub level: u8,
-        pub id: String,
-        pub name: String,
-        pub rarity: Rarity,
-        #[serde(rename = "type")]
-        pub typ: WeaponType,
-        pub company: Company,
-        pub barrel: Part,
-        pub body: Part,
-        pub magazine: Part,
-        pub stock: Part,
-        pub range: String,
-        pub damage: String,
-        pub details: Vec<String>,
+        /// Replaces references to dice stats with their expressions, `stat`
+        /// looks up what a referenced stat holds.
+        pub fn expand<F: FnMut(&str) -> Result<DiceStat, ApiError>>(
+            self,
+            stat: &mut F,
+        ) -> Result<Self, ApiError> {
+            self.expand_at(stat, 0)
+        }
+
+        fn expand_at<F: FnMut(&str) -> Result<DiceStat, ApiError>>(
+            self,
+            stat: &mut F,
+            depth: usize,
+        ) -> Result<Self, ApiError> {
+            let mut terms = Vec::new();
+
+            for (negative, term) in self.terms {
+                let DiceTerm::Stat(name) = &term else {
+                    terms.push((negative, term));
+                    continue;
+                };
+
+                match stat(name)? {
+                    DiceStat::Number => terms.push((negative, term)),
+                    DiceStat::Dice(_) if depth >= DICE_DEPTH_MAX => {
+                        return Err(ApiError::Unprocessable(format!(
+                            "dice stat `{}` refers to too many other dice stats",
+                            name
+                        )));
+                    }
+                    DiceStat::Dice(dice) => {
+                        for (inner, term) in dice.expand_at(stat, depth + 1)?.terms {
+                            terms.push((negative != inner, term));
+                        }
+                    }
+                }
+            }
+
+            if terms.len() > DICE_TERMS_MAX {
+                return Err(ApiError::Unprocessable(format!(
+                    "dice have more than {} terms",
+                    DICE_TERMS_MAX
+                )));
+            }
+
+            Ok(Self { terms })
+        }
+
+        /// Rolls every term with `rng`, `stat` looks up the values of the
+        /// stats the dice refer to.
+        pub fn roll<R: Rng, F: FnMut(&str) -> Result<i32, ApiError>>(
+            &self,
+            rng: &mut R,
+            mut stat: F,
+        ) -> Result<Roll, ApiError> {
+            let mut terms = Vec::new();
+            let mut total = 0i64;
+
+            for (negative, term) in &self.terms {
+                let mut dice = Vec::new();
+
+                let value = match term {
+                    DiceTerm::Roll {
+                        count,
+                        sides,
+                        explode,
+                        keep,
+                    } => {
+                        let mut rolls = 0;
+                        let mut extra = 0;
+                        while rolls < count + extra {
+                            let value = rng.gen_range(1..=*sides);
+                            let exploded = *explode && value == *sides && extra < DICE_EXPLODE_MAX;
+                            if exploded {
+                                extra += 1;
+                            }
+
+                            dice.push(Die {
+                                value,
+                                kept: true,
+                                exploded,
+                            });
+                            rolls += 1;
+                        }
+
+                        if let Some(keep) = keep {
+                            let mut order = (0..dice.len()).collect::<Vec<_>>();
+                            order.sort_by_key(|index| dice[*index].value);
+
+                            let dropped = match keep {
+                                Keep::Highest(kept) => {
+                                    order.truncate(dice.len().saturating_sub(*kept as usize));
+                                    order
+                                }
+                                Keep::Lowest(kept) => {
+                                    order.split_off((*kept as usize).min(order.len()))
+                                }
+                            };
+
+                            for index in dropped {
+                                dice[index].kept = false;
+                            }
+                        }
+
+                        dice.iter()
+                            .filter(|die| die.kept)
+                            .map(|die| die.value as i64)
+                            .sum()
+                    }
+                    DiceTerm::Constant(value) => *value as i64,
+                    DiceTerm::Stat(name) => stat(name)? as i64,
+                };
+
+                total += if *negative { -value } else { value };
+
+                terms.push(RolledTerm {
+                    negative: *negative,
+                    term: term.to_string(),
+                    dice,
+                    value,
+                });
+            }
+
+            let mut breakdown = String::new();
+            for (index, term) in terms.iter().enumerate() {
+                match (index, term.negative) {
+                    (0, false) => {}
+                    (0, true) => breakdown.push('-'),
+                    (_, false) => breakdown.push_str(" + "),
+                    (_, true) => breakdown.push_str(" - "),
+                }
+
+                breakdown.push_str(&term.to_string());
+            }
+            breakdown.push_str(&format!(" = {}", total));
+
+            Ok(Roll {
+                total,
+                terms,
+                breakdown,
+            })
+        }
     }
 
-    #[derive(serde::Serialize)]
-    pub struct Weapon {
-        pub level: u8,
-        pub id: Id,
-        pub rarity: Rarity,
-        #[serde(rename = "type")]
-        pub typ: WeaponType,
-        pub company: Company,
-        pub barrel: Part,
-        pub body: Part,
-        pub magazine: Part,
-        pub stock: Part,
-        pub damage: f32,
+    impl TryFrom<&str> for Dice {
+        type Error = ApiError;
+
+        fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
+            let expr = value
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .collect::<String>();
+
+            let (mut negative, mut rest) = match expr.strip_prefix('-') {
+                Some(rest) => (true, rest),
+                None => (false, expr.as_str()),
+            };
+
+            let mut terms = Vec::new();
+            loop {
+                let end = rest.find(['+', '-']).unwrap_or(rest.len());
+
+                let term = DiceTerm::try_from(&rest[..end])
+                    .map_err(|_| ApiError::invalid("dice", value))?;
+                terms.push((negative, term));
+
+                if terms.len() > DICE_TERMS_MAX {
+                    return Err(ApiError::invalid("dice", value));
+                }
+
+                if end == rest.len() {
+                    break;
+                }
+
+                negative = rest[end..].starts_with('-');
+                rest = &rest[end + 1..];
+            }
+
+            Ok(Self { terms })
+        }
     }
 
-    impl Weapon {
-        pub fn generate(parts: &[Part], curves: &[WeaponCurve], level: u8) -> Result<Self> {
-            use PartType::*;
+    impl TryFrom<&str> for DiceTerm {
+        type Error = ();
+
+        fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
+            if let Some(name) = value.strip_prefix('@') {
+                if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                    return Err(());
+                }
 
-            let mut rng = rand::thread_rng();
-
-            let typ = match rng.gen_range(0..10) {
-                0 | 1 => WeaponType::Pistol,
-                2 | 3 => WeaponType::Submachine,
-                4 | 5 => WeaponType::Shotgun,
-                6 | 7 => WeaponType::Assault,
-                8 => WeaponType::Grenade,
-                9 => WeaponType::Sniper,
-                10 => WeaponType::Rocket,
-                _ => unreachable!(),
+                return Ok(DiceTerm::Stat(name.to_string()));
+            }
+
+            let value = value.to_lowercase();
+
+            let Some((count, rest)) = value.split_once('d') else {
+                return value.parse().map(DiceTerm::Constant).map_err(|_| ());
             };
 
-            let curve = curves.iter().find(|c| c.typ == typ).context("Missing weapon curve")?;
+            let count = match count {
+                "" => 1,
+                count => count.parse().map_err(|_| ())?,
+            };
 
-            let (barrel_index, barrel) = Self::generate_part(parts, &mut rng, level, Barrel).context("Missing barrel")?;
-            let (body_index, body) = Self::generate_part(parts, &mut rng, level, Body).context("Missing body")?;
-            let (magazine_index, magazine) = Self::generate_part(parts, &mut rng, level, Magazine).context("Missing magazine")?;
-            let (stock_index, stock) = Self::generate_part(parts, &mut rng, level, Stock).context("Missing stock")?;
+            let end = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            let sides = rest[..end].parse().map_err(|_| ())?;
+            let mut rest = &rest[end..];
 
-            let rarity = body.rarity | barrel.rarity | magazine.rarity | stock.rarity;
+            let explode = match rest.strip_prefix('!') {
+                Some(after) => {
+                    rest = after;
+                    true
+                }
+                None => false,
+            };
 
-            let id = Id::from(
-                level,
-                typ.index(),
-                body_index as u8,
-                barrel_index as u8,
-                magazine_index as u8,
-                stock_index as u8,
-            );
+            let keep = match rest {
+                "" => None,
+                _ => {
+                    let (lowest, kept) = match (rest.strip_prefix("kh"), rest.strip_prefix("kl")) {
+                        (Some(kept), _) => (false, kept),
+                        (_, Some(kept)) => (true, kept),
+                        _ => return Err(()),
+                    };
+                    let kept = kept.parse::<u32>().map_err(|_| ())?;
+
+                    if !(1..=count).contains(&kept) {
+                        return Err(());
+                    }
 
-            let damage = curve.curve.evaluate(utils::rescale(
-                level as f32,
-                0.0..(LEVEL_MAX as f32),
-                0.0..1.0,
-            ));
+                    Some(if lowest {
+                        Keep::Lowest(kept)
+                    } else {
+                        Keep::Highest(kept)
+                    })
+                }
+            };
 
-            Ok(Self {
-                level,
-                id,
-                rarity,
-                typ,
-                company: body.company,
-                barrel,
-                body,
-                magazine,
-                stock,
-                damage,
+            // a die with a single side would explode forever
+            let sides_min = if explode { 2 } else { 1 };
+
+            if !(1..=DICE_COUNT_MAX).contains(&count)
+                || !(sides_min..=DICE_SIDES_MAX).contains(&sides)
+            {
+                return Err(());
+            }
+
+            Ok(DiceTerm::Roll {
+                count,
+                sides,
+                explode,
+                keep,
             })
         }
+    }
 
-        fn generate_part<R: Rng>(
-            parts: &[Part],
-            rng: &mut R,
-            level: u8,
-            typ: PartType,
-        ) -> Option<(usize, Part)> {
-            let filtered = parts
-                .iter()
-                .enumerate()
-                .filter(|(_, p)| p.typ == typ)
-                .filter(|(_, p)| Self::filter_rarity(p, level));
+    impl fmt::Display for Dice {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            for (index, (negative, term)) in self.terms.iter().enumerate() {
+                match (index, negative) {
+                    (_, true) => write!(f, "-")?,
+                    (0, false) => {}
+                    (_, false) => write!(f, "+")?,
+                }
+
+                write!(f, "{}", term)?;
+            }
 
-            filtered.choose(rng).map(|(id, part)| (id, part.clone()))
+            Ok(())
         }
+    }
 
-        fn filter_rarity(part: &Part, level: u8) -> bool {
-            use Rarity::*;
+    impl fmt::Display for DiceTerm {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                DiceTerm::Roll {
+                    count,
+                    sides,
+                    explode,
+                    keep,
+                } => {
+                    write!(f, "{}d{}", count, sides)?;
+
+                    if *explode {
+                        write!(f, "!")?;
+                    }
 
-            if level >= 20 {
-                true
-            } else if level >= 12 {
-                matches!(part.rarity, Common | Uncommon | Rare | Epic)
-            } else if level >= 8 {
-                matches!(part.rarity, Common | Uncommon | Rare)
-            } else {
-                matches!(part.rarity, Common | Uncommon)
+                    match keep {
+                        Some(Keep::Highest(kept)) => write!(f, "kh{}", kept),
+                        Some(Keep::Lowest(kept)) => write!(f, "kl{}", kept),
+                        None => Ok(()),
+                    }
+                }
+                DiceTerm::Constant(value) => write!(f, "{}", value),
+                DiceTerm::Stat(name) => write!(f, "@{}", name),
             }
         }
+    }
 
-        pub fn from_id(parts: &[Part], curves: &[WeaponCurve], id: Id) -> Option<Self> {
-            let level = id.level();
-            let body = parts[id.body() as usize].clone();
-            let barrel = parts[id.barrel() as usize].clone();
-            let magazine = parts[id.magazine() as usize].clone();
-            let stock = parts[id.stock() as usize].clone();
+    /// The outcome of rolling [`Dice`].
+    #[derive(serde::Serialize, serde::Deserialize)]
+    pub struct Roll {
+        pub total: i64,
+        pub terms: Vec<RolledTerm>,
+        /// The terms with their dice and values, like `2d20kh1 [14, (3)] + 2 = 16`.
+        pub breakdown: String,
+    }
 
-            let rarity = body.rarity | barrel.rarity | magazine.rarity | stock.rarity;
+    #[derive(serde::Serialize, serde::Deserialize)]
+    pub struct RolledTerm {
+        pub negative: bool,
+        pub term: String,
+        pub dice: Vec<Die>,
+        pub value: i64,
+    }
 
-            let typ = WeaponType::from_index(id.typ())?;
+    #[derive(serde::Serialize, serde::Deserialize)]
+    pub struct Die {
+        pub value: u32,
+        /// Whether the die counts towards the total, dice that weren't kept don't.
+        pub kept: bool,
+        /// Whether the die showed its highest side and rolled another die.
+        pub exploded: bool,
+    }
 
-            let curve = curves.iter().find(|c| c.typ == typ)?;
+    /// Dropped dice are in parentheses and exploded dice end in `!`.
+    impl fmt::Display for RolledTerm {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{}", self.term)?;
 
-            let damage = curve.curve.evaluate(utils::rescale(
-                level as f32,
-                0.0..(LEVEL_MAX as f32),
-                0.0..1.0,
-            ));
+            if self.term.starts_with('@') {
+                return write!(f, " ({})", self.value);
+            }
 
-            Some(Self {
-                level,
-                id,
-                rarity,
-                typ,
-                company: body.company,
-                barrel,
-                body,
-                magazine,
-                stock,
-                damage,
-            })
-        }
+            if self.dice.is_empty() {
+                return Ok(());
+            }
 
-        pub fn name(&self) -> String {
-            format!("{} {}", self.barrel.name, self.body.name)
+            write!(f, " [")?;
+            for (index, die) in self.dice.iter().enumerate() {
+                if index > 0 {
+                    write!(f, ", ")?;
+                }
+
+                let explode = if die.exploded { "!" } else { "" };
+                if die.kept {
+                    write!(f, "{}{}", die.value, explode)?;
+                } else {
+                    write!(f, "({}{})", die.value, explode)?;
+                }
+            }
+            write!(f, "]")
         }
+    }
 
-        pub fn damage(&self) -> String {
-            let base = match self.typ {
-                WeaponType::Assault => 1.0,
-                WeaponType::Grenade => 1.0,
-                WeaponType::Pistol => 1.0,
-                WeaponType::Rocket => 1.0,
-                WeaponType::Shotgun => 1.0,
-                WeaponType::Sniper => 1.0,
-                WeaponType::Submachine => 1.0,
-            };
+    /// A roll as it's kept in the roll history of a campaign.
+    #[derive(serde::Serialize)]
+    pub struct RollEntry {
+        pub id: i64,
+        /// Unix time of the roll.
+        pub time: i64,
+        pub actor: Option<String>,
+        pub character: Option<String>,
+        pub expr: String,
+        /// Rolling `expr` with the same seed and stats gives the same dice.
+        pub seed: u32,
+        #[serde(flatten)]
+        pub roll: Roll,
+    }
 
-            (base + (base * self.damage).round()).to_string()
-        }
+    /// Longest formula that is accepted.
+    static FORMULA_LEN_MAX: usize = 1000;
+    /// Deepest nesting of parentheses and operators a formula may have.
+    static FORMULA_DEPTH_MAX: usize = 64;
+
+    /// An expression over the stats of a character, like `10 + dex / 2`.
+    ///
+    /// Stats are referred to by name, names that aren't plain identifiers are
+    /// quoted with backticks. Besides arithmetic formulas have comparisons,
+    /// `&&`, `||` and `!` that treat anything but 0 as true, and the functions
+    /// `min`, `max`, `floor`, `ceil`, `round`, `abs` and `if(cond, then, else)`.
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Formula(Expr);
+
+    #[derive(Clone, Debug, PartialEq)]
+    enum Expr {
+        Number(f64),
+        Stat(String),
+        Neg(Box<Expr>),
+        Not(Box<Expr>),
+        Binary(BinaryOp, Box<Expr>, Box<Expr>),
+        Call(Function, Vec<Expr>),
+    }
 
-        pub fn range(&self) -> &str {
-            match self.typ {
-                WeaponType::Assault => "mid-far",
-                WeaponType::Grenade => "mid",
-                WeaponType::Pistol => "close-near",
-                WeaponType::Rocket => "mid-far",
-                WeaponType::Shotgun => "mid",
-                WeaponType::Sniper => "far",
-                WeaponType::Submachine => "close-mid",
-            }
-        }
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    enum BinaryOp {
+        Add,
+        Sub,
+        Mul,
+        Div,
+        Rem,
+        Lt,
+        Le,
+        Gt,
+        Ge,
+        Eq,
+        Ne,
+        And,
+        Or,
+    }
 
-        pub fn details(&self) -> WeaponDetailsIter<'_> {
-            WeaponDetailsIter {
-                weapon: self,
-                index: 0,
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    enum Function {
+        Min,
+        Max,
+        Floor,
+        Ceil,
+        Round,
+        Abs,
+        If,
+    }
+
+    impl Function {
+        fn arity(self) -> ops::RangeInclusive<usize> {
+            match self {
+                Function::Min | Function::Max => 1..=usize::MAX,
+                Function::Floor | Function::Ceil | Function::Round | Function::Abs => 1..=1,
+                Function::If => 3..=3,
             }
         }
+    }
 
-        pub fn display(self) -> WeaponDisplay {
-            WeaponDisplay {
-                level: self.level,
-                id: self.id.to_string(),
-                name: self.name(),
-                rarity: self.rarity,
-                typ: self.typ,
-                company: self.company,
-                barrel: self.barrel.clone(),
-                body: self.body.clone(),
-                magazine: self.magazine.clone(),
-                stock: self.stock.clone(),
-                range: self.range().to_string(),
-                damage: self.damage(),
-                details: self.details().collect(),
+    impl TryFrom<&str> for Function {
+        type Error = ApiError;
+
+        fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
+            match value {
+                "min" => Ok(Function::Min),
+                "max" => Ok(Function::Max),
+                "floor" => Ok(Function::Floor),
+                "ceil" => Ok(Function::Ceil),
+                "round" => Ok(Function::Round),
+                "abs" => Ok(Function::Abs),
+                "if" => Ok(Function::If),
+                _ => Err(ApiError::Unprocessable(format!(
+                    "unknown formula function `{}`",
+                    value
+                ))),
             }
         }
     }
 
-    pub struct WeaponDetailsIter<'w> {
-        weapon: &'w Weapon,
-        index: u8,
+    #[derive(Clone, Debug, PartialEq)]
+    enum Token {
+        Number(f64),
+        Name(String),
+        Op(&'static str),
+        Open,
+        Close,
+        Comma,
     }
 
-    impl<'w> Iterator for WeaponDetailsIter<'w> {
-        type Item = String;
-
-        fn next(&mut self) -> Option<Self::Item> {
-            loop {
-                match self.index {
-                    0 => {
-                        if !self.weapon.body.details.is_empty() {
-                            self.index += 1;
-
-                            return Some(self.weapon.body.details.clone());
-                        }
+    /// Operator tokens, longer operators come first so `<=` isn't read as `<`.
+    static FORMULA_OPS: &[&str] = &[
+        "&&", "||", "<=", ">=", "==", "!=", "<", ">", "+", "-", "*", "/", "%", "!",
+    ];
+
+    impl Formula {
+        /// `value` with references to the stat `from` pointed at `to` and
+        /// everything else kept as written, `None` when it has none. Fails when
+        /// the formula can't refer to `to`.
+        pub fn rename(value: &str, from: &str, to: &str) -> Result<Option<String>, ApiError> {
+            let bare = to.starts_with(|c: char| c.is_alphabetic() || c == '_')
+                && to.chars().all(|c| c.is_alphanumeric() || c == '_');
+
+            let mut renamed = String::new();
+            let mut found = false;
+            let mut rest = value;
+
+            while let Some(c) = rest.chars().next() {
+                let (len, name) = if c.is_alphabetic() || c == '_' {
+                    let len = rest
+                        .find(|c: char| !c.is_alphanumeric() && c != '_')
+                        .unwrap_or(rest.len());
+                    (len, Some(&rest[..len]))
+                } else if c == '`' {
+                    match rest[1..].find('`') {
+                        Some(len) => (len + 2, Some(&rest[1..len + 1])),
+                        None => (rest.len(), None),
                     }
-                    1 => {
-                        if !self.weapon.barrel.details.is_empty() {
-                            self.index += 1;
-
-                            return Some(self.weapon.barrel.details.clone());
+                } else if c.is_ascii_digit() || c == '.' {
+                    let len = rest
+                        .find(|c: char| !c.is_ascii_digit() && c != '.')
+                        .unwrap_or(rest.len());
+                    (len, None)
+                } else {
+                    (c.len_utf8(), None)
+                };
+
+                // names followed by `(` are functions
+                let call = rest[len..].trim_start().starts_with('(');
+
+                match name {
+                    Some(name) if name == from && !call => {
+                        found = true;
+
+                        if bare {
+                            renamed.push_str(to);
+                        } else {
+                            renamed.push_str(&format!("`{}`", to));
                         }
                     }
-                    2 => {
-                        if !self.weapon.magazine.details.is_empty() {
-                            self.index += 1;
+                    _ => renamed.push_str(&rest[..len]),
+                }
 
-                            return Some(self.weapon.magazine.details.clone());
+                rest = &rest[len..];
+            }
+
+            if !found {
+                return Ok(None);
+            }
+
+            Formula::try_from(renamed.as_str())?;
+
+            Ok(Some(renamed))
+        }
+
+        /// The names of the stats the formula reads.
+        pub fn references(&self) -> Vec<&str> {
+            fn walk<'e>(expr: &'e Expr, names: &mut Vec<&'e str>) {
+                match expr {
+                    Expr::Number(_) => {}
+                    Expr::Stat(name) => {
+                        if !names.contains(&name.as_str()) {
+                            names.push(name);
                         }
                     }
-                    3 => {
-                        if !self.weapon.stock.details.is_empty() {
-                            self.index += 1;
+                    Expr::Neg(expr) | Expr::Not(expr) => walk(expr, names),
+                    Expr::Binary(_, left, right) => {
+                        walk(left, names);
+                        walk(right, names);
+                    }
+                    Expr::Call(_, args) => args.iter().for_each(|arg| walk(arg, names)),
+                }
+            }
 
-                            return Some(self.weapon.stock.details.clone());
-                        }
+            let mut names = Vec::new();
+            walk(&self.0, &mut names);
+            names
+        }
+
+        /// Evaluates the formula with `stat` looking up the values of stats,
+        /// `None` when a stat has no value or the result isn't a finite number.
+        pub fn eval<F: FnMut(&str) -> Option<f64>>(&self, mut stat: F) -> Option<f64> {
+            Self::eval_expr(&self.0, &mut stat).filter(|value| value.is_finite())
+        }
+
+        fn eval_expr<F: FnMut(&str) -> Option<f64>>(expr: &Expr, stat: &mut F) -> Option<f64> {
+            let truth = |value: bool| if value { 1.0 } else { 0.0 };
+
+            let value = match expr {
+                Expr::Number(value) => *value,
+                Expr::Stat(name) => stat(name)?,
+                Expr::Neg(expr) => -Self::eval_expr(expr, stat)?,
+                Expr::Not(expr) => truth(Self::eval_expr(expr, stat)? == 0.0),
+                Expr::Binary(op, left, right) => {
+                    let left = Self::eval_expr(left, stat)?;
+                    let right = Self::eval_expr(right, stat)?;
+
+                    match op {
+                        BinaryOp::Add => left + right,
+                        BinaryOp::Sub => left - right,
+                        BinaryOp::Mul => left * right,
+                        BinaryOp::Div | BinaryOp::Rem if right == 0.0 => return None,
+                        BinaryOp::Div => left / right,
+                        BinaryOp::Rem => left % right,
+                        BinaryOp::Lt => truth(left < right),
+                        BinaryOp::Le => truth(left <= right),
+                        BinaryOp::Gt => truth(left > right),
+                        BinaryOp::Ge => truth(left >= right),
+                        BinaryOp::Eq => truth(left == right),
+                        BinaryOp::Ne => truth(left != right),
+                        BinaryOp::And => truth(left != 0.0 && right != 0.0),
+                        BinaryOp::Or => truth(left != 0.0 || right != 0.0),
+                    }
+                }
+                Expr::Call(Function::If, args) => {
+                    if Self::eval_expr(&args[0], stat)? != 0.0 {
+                        Self::eval_expr(&args[1], stat)?
+                    } else {
+                        Self::eval_expr(&args[2], stat)?
+                    }
+                }
+                Expr::Call(function, args) => {
+                    let args = args
+                        .iter()
+                        .map(|arg| Self::eval_expr(arg, stat))
+                        .collect::<Option<Vec<_>>>()?;
+
+                    match function {
+                        Function::Min => args.into_iter().fold(f64::INFINITY, f64::min),
+                        Function::Max => args.into_iter().fold(f64::NEG_INFINITY, f64::max),
+                        Function::Floor => args[0].floor(),
+                        Function::Ceil => args[0].ceil(),
+                        Function::Round => args[0].round(),
+                        Function::Abs => args[0].abs(),
+                        Function::If => unreachable!("if is evaluated lazily above"),
                     }
-                    _ => return None,
                 }
+            };
 
-                self.index += 1;
+            Some(value)
+        }
+
+        fn tokenize(value: &str) -> Result<Vec<Token>, String> {
+            let mut tokens = Vec::new();
+            let mut rest = value.trim_start();
+
+            while let Some(c) = rest.chars().next() {
+                let len = if c.is_ascii_digit() || c == '.' {
+                    let len = rest
+                        .find(|c: char| !c.is_ascii_digit() && c != '.')
+                        .unwrap_or(rest.len());
+                    let number = rest[..len]
+                        .parse()
+                        .map_err(|_| format!("invalid number `{}`", &rest[..len]))?;
+                    tokens.push(Token::Number(number));
+                    len
+                } else if c.is_alphabetic() || c == '_' {
+                    let len = rest
+                        .find(|c: char| !c.is_alphanumeric() && c != '_')
+                        .unwrap_or(rest.len());
+                    tokens.push(Token::Name(rest[..len].to_string()));
+                    len
+                } else if c == '`' {
+                    let len = rest[1..]
+                        .find('`')
+                        .ok_or_else(|| "unclosed `` ` ``".to_string())?;
+                    tokens.push(Token::Name(rest[1..len + 1].to_string()));
+                    len + 2
+                } else if c == '(' {
+                    tokens.push(Token::Open);
+                    1
+                } else if c == ')' {
+                    tokens.push(Token::Close);
+                    1
+                } else if c == ',' {
+                    tokens.push(Token::Comma);
+                    1
+                } else if let Some(op) = FORMULA_OPS.iter().find(|op| rest.starts_with(**op)) {
+                    tokens.push(Token::Op(op));
+                    op.len()
+                } else {
+                    return Err(format!("unexpected `{}`", c));
+                };
+
+                rest = rest[len..].trim_start();
             }
+
+            Ok(tokens)
         }
     }
 
-    pub struct WeaponCurve {
-        pub typ: WeaponType,
-        pub curve: Curve,
+    /// Recursive descent over the tokens of a formula, one method per
+    /// precedence level from loosest to tightest.
+    struct FormulaParser {
+        tokens: Vec<Token>,
+        position: usize,
+        depth: usize,
     }
 
-    #[derive(Clone, Copy, PartialEq, Eq, serde::Serialize)]
-    #[serde(rename_all = "snake_case")]
-    pub enum WeaponType {
-        Assault,
-        Grenade,
-        Pistol,
-        Rocket,
-        Shotgun,
-        Sniper,
-        Submachine,
-    }
+    impl FormulaParser {
+        fn peek(&self) -> Option<&Token> {
+            self.tokens.get(self.position)
+        }
 
-    impl WeaponType {
-        fn index(&self) -> u8 {
-            match self {
-                WeaponType::Assault => 0,
-                WeaponType::Grenade => 1,
-                WeaponType::Pistol => 2,
-                WeaponType::Rocket => 3,
-                WeaponType::Shotgun => 4,
-                WeaponType::Sniper => 5,
-                WeaponType::Submachine => 6,
+        fn next(&mut self) -> Option<Token> {
+            let token = self.tokens.get(self.position).cloned();
+            self.position += 1;
+            token
+        }
+
+        fn eat(&mut self, token: &Token) -> bool {
+            if self.peek() == Some(token) {
+                self.position += 1;
+                true
+            } else {
+                false
+            }
+        }
+
+        /// Parses left associative operators from `ops` with `operand` on both sides.
+        fn binary(
+            &mut self,
+            ops: &[(&str, BinaryOp)],
+            operand: fn(&mut Self) -> Result<Expr, String>,
+        ) -> Result<Expr, String> {
+            let mut expr = operand(self)?;
+
+            while let Some(Token::Op(op)) = self.peek() {
+                let Some((_, op)) = ops.iter().find(|(name, _)| name == op) else {
+                    break;
+                };
+                let op = *op;
+                self.position += 1;
+
+                expr = Expr::Binary(op, Box::new(expr), Box::new(operand(self)?));
+            }
+
+            Ok(expr)
+        }
+
+        fn or(&mut self) -> Result<Expr, String> {
+            self.depth += 1;
+            if self.depth > FORMULA_DEPTH_MAX {
+                return Err("formula is nested too deeply".to_string());
+            }
+
+            let expr = self.binary(&[("||", BinaryOp::Or)], Self::and);
+
+            self.depth -= 1;
+            expr
+        }
+
+        fn and(&mut self) -> Result<Expr, String> {
+            self.binary(&[("&&", BinaryOp::And)], Self::comparison)
+        }
+
+        fn comparison(&mut self) -> Result<Expr, String> {
+            self.binary(
+                &[
+                    ("<", BinaryOp::Lt),
+                    ("<=", BinaryOp::Le),
+                    (">", BinaryOp::Gt),
+                    (">=", BinaryOp::Ge),
+                    ("==", BinaryOp::Eq),
+                    ("!=", BinaryOp::Ne),
+                ],
+                Self::sum,
+            )
+        }
+
+        fn sum(&mut self) -> Result<Expr, String> {
+            self.binary(&[("+", BinaryOp::Add), ("-", BinaryOp::Sub)], Self::product)
+        }
+
+        fn product(&mut self) -> Result<Expr, String> {
+            self.binary(
+                &[
+                    ("*", BinaryOp::Mul),
+                    ("/", BinaryOp::Div),
+                    ("%", BinaryOp::Rem),
+                ],
+                Self::unary,
+            )
+        }
+
+        fn unary(&mut self) -> Result<Expr, String> {
+            if self.eat(&Token::Op("-")) {
+                return Ok(Expr::Neg(Box::new(self.unary()?)));
+            }
+
+            if self.eat(&Token::Op("!")) {
+                return Ok(Expr::Not(Box::new(self.unary()?)));
             }
+
+            self.primary()
         }
 
-        fn from_index(index: u8) -> Option<Self> {
-            match index {
-                0 => Some(WeaponType::Assault),
-                1 => Some(WeaponType::Grenade),
-                2 => Some(WeaponType::Pistol),
-                3 => Some(WeaponType::Rocket),
-                4 => Some(WeaponType::Shotgun),
-                5 => Some(WeaponType::Sniper),
-                6 => Some(WeaponType::Submachine),
-                _ => None,
+        fn primary(&mut self) -> Result<Expr, String> {
+            match self.next() {
+                Some(Token::Number(value)) => Ok(Expr::Number(value)),
+                Some(Token::Name(name)) if self.eat(&Token::Open) => {
+                    let function =
+                        Function::try_from(name.as_str()).map_err(|err| err.to_string())?;
+
+                    let mut args = Vec::new();
+                    if !self.eat(&Token::Close) {
+                        loop {
+                            args.push(self.or()?);
+
+                            if self.eat(&Token::Close) {
+                                break;
+                            }
+
+                            if !self.eat(&Token::Comma) {
+                                return Err(format!("expected `,` or `)` in `{}`", name));
+                            }
+                        }
+                    }
+
+                    if !function.arity().contains(&args.len()) {
+                        return Err(format!("wrong number of arguments for `{}`", name));
+                    }
+
+                    Ok(Expr::Call(function, args))
+                }
+                Some(Token::Name(name)) => Ok(Expr::Stat(name)),
+                Some(Token::Open) => {
+                    let expr = self.or()?;
+
+                    if !self.eat(&Token::Close) {
+                        return Err("expected `)`".to_string());
+                    }
+
+                    Ok(expr)
+                }
+                Some(token) => Err(format!("unexpected {}", token.describe())),
+                None => Err("unexpected end of formula".to_string()),
             }
         }
     }
 
-    impl fmt::Display for WeaponType {
-        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    impl Token {
+        fn describe(&self) -> String {
             match self {
-                WeaponType::Assault => write!(f, "assault rifle"),
-                WeaponType::Grenade => write!(f, "grenade launcher"),
-                WeaponType::Pistol => write!(f, "pistol"),
-                WeaponType::Rocket => write!(f, "rocket launcher"),
-                WeaponType::Shotgun => write!(f, "shotgun"),
-                WeaponType::Sniper => write!(f, "sniper rifle"),
-                WeaponType::Submachine => write!(f, "submachine gun"),
+                Token::Number(value) => format!("`{}`", value),
+                Token::Name(name) => format!("`{}`", name),
+                Token::Op(op) => format!("`{}`", op),
+                Token::Open => "`(`".to_string(),
+                Token::Close => "`)`".to_string(),
+                Token::Comma => "`,`".to_string(),
             }
         }
     }
 
-    impl TryFrom<&str> for WeaponType {
-        type Error = FromSqlError;
+    impl TryFrom<&str> for Formula {
+        type Error = ApiError;
 
         fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
-            match value {
-                "assault rifle" => Ok(WeaponType::Assault),
-                "grenade launcher" => Ok(WeaponType::Grenade),
-                "pistol" => Ok(WeaponType::Pistol),
-                "rocket launcher" => Ok(WeaponType::Rocket),
-                "shotgun" => Ok(WeaponType::Shotgun),
-                "sniper rifle" => Ok(WeaponType::Sniper),
-                "submachine gun" => Ok(WeaponType::Submachine),
-                _ => Err(FromSqlError::InvalidType),
+            let invalid = |reason: String| {
+                ApiError::Unprocessable(format!("invalid formula `{}`: {}", value, reason))
+            };
+
+            if value.len() > FORMULA_LEN_MAX {
+                return Err(invalid(format!(
+                    "longer than {} characters",
+                    FORMULA_LEN_MAX
+                )));
+            }
+
+            let mut parser = FormulaParser {
+                tokens: Formula::tokenize(value).map_err(invalid)?,
+                position: 0,
+                depth: 0,
+            };
+
+            let expr = parser.or().map_err(invalid)?;
+
+            if let Some(token) = parser.peek() {
+                return Err(invalid(format!("unexpected {}", token.describe())));
             }
+
+            Ok(Formula(expr))
         }
     }
 
-    impl FromSql for WeaponType {
-        fn column_result(value: ValueRef) -> FromSqlResult<Self> {
-            String::column_result(value).and_then(|s| WeaponType::try_from(s.as_str()))
+    /// Fills in the values of the formula stats of a character from its other
+    /// stats. Formulas that can't be evaluated, like ones dividing by zero,
+    /// are 0 and results are rounded down.
+    pub fn evaluate_formulas(stats: &mut [CharacterStat]) {
+        let formulas = stats
+            .iter()
+            .filter_map(|stat| match &stat.typ {
+                StatType::Formula { formula } => {
+                    Some((stat.name.clone(), Formula::try_from(formula.as_str()).ok()))
+                }
+                _ => None,
+            })
+            .collect::<HashMap<_, _>>();
+
+        if formulas.is_empty() {
+            return;
         }
-    }
 
-    impl ToSql for WeaponType {
-        fn to_sql(&self) -> rusqlite::Result<ToSqlOutput> {
-            Ok(self.to_string().into())
+        let mut values = stats
+            .iter()
+            .filter(|stat| !formulas.contains_key(&stat.name))
+            .filter_map(|stat| Some((stat.name.clone(), Some(stat.value.number()? as f64))))
+            .collect::<HashMap<_, _>>();
+
+        fn value(
+            name: &str,
+            formulas: &HashMap<String, Option<Formula>>,
+            values: &mut HashMap<String, Option<f64>>,
+            visiting: &mut Vec<String>,
+        ) -> Option<f64> {
+            if let Some(value) = values.get(name) {
+                return *value;
+            }
+
+            // cycles are rejected when formulas are added, this only keeps a
+            // broken template from recursing forever
+            if visiting.iter().any(|visited| visited == name) {
+                return None;
+            }
+
+            let formula = formulas.get(name)?.as_ref()?;
+
+            // other formulas see the value rounded down, just like the player does
+            visiting.push(name.to_string());
+            let result = formula
+                .eval(|stat| value(stat, formulas, values, visiting))
+                .map(f64::floor);
+            visiting.pop();
+
+            values.insert(name.to_string(), result);
+            result
         }
-    }
 
-    #[derive(serde::Serialize)]
-    pub struct Stat {
-        pub name: String,
-        #[serde(rename = "type")]
-        pub typ: StatType,
-    }
+        for stat in stats.iter_mut() {
+            if formulas.contains_key(&stat.name) {
+                let result = value(&stat.name, &formulas, &mut values, &mut Vec::new());
 
-    #[derive(serde::Serialize)]
-    #[serde(rename_all = "snake_case")]
-    pub enum StatType {
-        Number,
-        Boolean,
+                stat.value = StatValue::Number(result.map_or(0, |value| value as i32));
+            }
+        }
     }
 
-    impl FromSql for StatType {
-        fn column_result(value: ValueRef) -> FromSqlResult<Self> {
-            String::column_result(value).and_then(|s| match s.as_str() {
-                "number" => Ok(StatType::Number),
-                "boolean" => Ok(StatType::Boolean),
-                _ => Err(FromSqlError::InvalidType),
-            })
+    /// Finds a formula in `formulas` that depends on itself, returning the
+    /// stats that lead back to it.
+    pub fn formula_cycle<'f>(formulas: &HashMap<&'f str, Vec<&'f str>>) -> Option<Vec<&'f str>> {
+        fn visit<'f>(
+            name: &'f str,
+            formulas: &HashMap<&'f str, Vec<&'f str>>,
+            path: &mut Vec<&'f str>,
+            done: &mut HashSet<&'f str>,
+        ) -> bool {
+            if let Some(start) = path.iter().position(|visited| *visited == name) {
+                path.drain(..start);
+                path.push(name);
+                return true;
+            }
+
+            if done.contains(name) {
+                return false;
+            }
+
+            path.push(name);
+            for reference in formulas.get(name).into_iter().flatten() {
+                if visit(reference, formulas, path, done) {
+                    return true;
+                }
+            }
+            path.pop();
+
+            done.insert(name);
+            false
         }
-    }
 
-    impl ToSql for StatType {
-        fn to_sql(&self) -> rusqlite::Result<ToSqlOutput> {
-            match self {
-                StatType::Number => Ok("number".into()),
-                StatType::Boolean => Ok("boolean".into()),
+        let mut done = HashSet::new();
+
+        for name in formulas.keys() {
+            let mut path = Vec::new();
+            if visit(name, formulas, &mut path, &mut done) {
+                return Some(path);
             }
         }
+
+        None
     }
 
     #[derive(Clone, Copy)]
@@ -1513,6 +8984,191 @@ mod models {
             c2 * c * self.a + 3.0 * c2 * t * self.b + 3.0 * c * t2 * self.c + t2 * t * self.d
         }
     }
+
+    #[cfg(test)]
+    mod tests {
+        use std::collections::HashMap;
+
+        use rand::{rngs::mock::StepRng, SeedableRng as _};
+        use rand_chacha::ChaCha8Rng;
+
+        use super::{formula_cycle, Dice, Formula, Roll, DICE_EXPLODE_MAX};
+
+        fn roll(dice: &str, seed: u64) -> Roll {
+            let stats = |name: &str| match name {
+                "dex" => Ok(3),
+                _ => panic!("unknown stat `{}`", name),
+            };
+
+            Dice::try_from(dice)
+                .unwrap()
+                .roll(&mut ChaCha8Rng::seed_from_u64(seed), stats)
+                .unwrap()
+        }
+
+        fn eval(formula: &str) -> f64 {
+            let stats = |name: &str| match name {
+                "dex" => Some(7.0),
+                "str" => Some(12.0),
+                _ => None,
+            };
+
+            Formula::try_from(formula).unwrap().eval(stats).unwrap()
+        }
+
+        #[test]
+        fn formula_precedence() {
+            assert_eq!(eval("2 + 3 * 4"), 14.0);
+            assert_eq!(eval("(2 + 3) * 4"), 20.0);
+            assert_eq!(eval("10 - 4 - 3"), 3.0);
+            assert_eq!(eval("10 + dex / 2"), 13.5);
+            assert_eq!(eval("str % 5 * 2"), 4.0);
+            assert_eq!(eval("-2 * 3"), -6.0);
+            assert_eq!(eval("2 * -dex + 1"), -13.0);
+            assert_eq!(eval("--2"), 2.0);
+            assert_eq!(eval("-(1 + 2) * 2"), -6.0);
+            assert_eq!(eval("max(dex, str) - min(1, 2, -3)"), 15.0);
+            assert_eq!(eval("floor(dex / 2) + ceil(dex / 2)"), 7.0);
+        }
+
+        #[test]
+        fn formula_comparisons() {
+            assert_eq!(eval("1 < 2"), 1.0);
+            assert_eq!(eval("2 <= 1"), 0.0);
+            assert_eq!(eval("str >= 12 && dex > 7"), 0.0);
+            assert_eq!(eval("str == 12 || dex != 7"), 1.0);
+            assert_eq!(eval("1 + 1 == 2"), 1.0);
+            assert_eq!(eval("!0"), 1.0);
+            assert_eq!(eval("!dex"), 0.0);
+            assert_eq!(eval("!(dex < str)"), 0.0);
+            assert_eq!(eval("!1 + 1"), 1.0);
+            assert_eq!(eval("if(str > 10, 1, 2)"), 1.0);
+            assert_eq!(eval("if(0, 1 / 0, 2)"), 2.0);
+        }
+
+        #[test]
+        fn malformed_formulas_are_rejected() {
+            for formula in [
+                "",
+                "1 +",
+                "(1 + 2",
+                "1 + 2)",
+                "2 $ 3",
+                "dex str",
+                "`dex",
+                "1..2",
+                "min()",
+                "floor(1, 2)",
+                "if(1, 2)",
+                "sqrt(4)",
+                "max(1 2)",
+            ] {
+                assert!(Formula::try_from(formula).is_err(), "{}", formula);
+            }
+
+            let nested = format!("{}1{}", "(".repeat(100), ")".repeat(100));
+            assert!(Formula::try_from(nested.as_str()).is_err());
+        }
+
+        #[test]
+        fn formula_cycles_are_found() {
+            let formulas = HashMap::from([("armor", vec!["dex"]), ("carry", vec!["carry"])]);
+            assert_eq!(formula_cycle(&formulas), Some(vec!["carry", "carry"]));
+
+            let formulas = HashMap::from([
+                ("a", vec!["b"]),
+                ("b", vec!["dex", "c"]),
+                ("c", vec!["a"]),
+                ("d", vec!["a"]),
+            ]);
+            let path = formula_cycle(&formulas).unwrap();
+            assert_eq!(path.len(), 4, "{:?}", path);
+            assert_eq!(path.first(), path.last(), "{:?}", path);
+            assert!(!path.contains(&"d"), "{:?}", path);
+            for step in path.windows(2) {
+                assert!(formulas[step[0]].contains(&step[1]), "{:?}", path);
+            }
+
+            let formulas = HashMap::from([("a", vec!["b", "c"]), ("b", vec!["c"])]);
+            assert_eq!(formula_cycle(&formulas), None);
+        }
+
+        #[test]
+        fn dice_are_parsed() {
+            for (dice, parsed) in [
+                ("2d20kh1 + @dex - 1", "2d20kh1+@dex-1"),
+                ("d6", "1d6"),
+                ("-1D8!", "-1d8!"),
+                ("4d6 kl 3", "4d6kl3"),
+                ("3", "3"),
+            ] {
+                assert_eq!(Dice::try_from(dice).unwrap().to_string(), parsed);
+            }
+
+            for dice in [
+                "", "d", "0d6", "101d6", "1d0", "1d1001", "1d1!", "2d6kh3", "2d6kh0", "1d6x",
+                "1d6+", "@", "@dex!", "1d6 ++ 2",
+            ] {
+                assert!(Dice::try_from(dice).is_err(), "{}", dice);
+            }
+        }
+
+        #[test]
+        fn dice_keep_highest_and_lowest() {
+            for seed in 0..20 {
+                for (dice, kept) in [("4d6kh3", 3), ("4d6kl1", 1)] {
+                    let roll = roll(dice, seed);
+                    let dice = &roll.terms[0].dice;
+
+                    let mut values = dice.iter().map(|die| die.value).collect::<Vec<_>>();
+                    values.sort();
+                    if kept == 3 {
+                        values.drain(..1);
+                    } else {
+                        values.truncate(1);
+                    }
+
+                    assert_eq!(dice.iter().filter(|die| die.kept).count(), kept);
+                    assert_eq!(roll.total, values.iter().sum::<u32>() as i64);
+                }
+            }
+        }
+
+        #[test]
+        fn exploding_dice_are_capped() {
+            // always rolls the middle of the range, the highest side of a d2
+            let mut rng = StepRng::new(0x8000_0000, 0);
+
+            let roll = Dice::try_from("1d2!")
+                .unwrap()
+                .roll(&mut rng, |_| Ok(0))
+                .unwrap();
+            let dice = &roll.terms[0].dice;
+
+            assert_eq!(dice.len() as u32, DICE_EXPLODE_MAX + 1);
+            assert!(dice.iter().all(|die| die.value == 2));
+            assert_eq!(
+                dice.iter().filter(|die| die.exploded).count() as u32,
+                DICE_EXPLODE_MAX
+            );
+            assert_eq!(roll.total, 2 * (DICE_EXPLODE_MAX as i64 + 1));
+        }
+
+        #[test]
+        fn seeded_rolls_reproduce() {
+            let first = roll("2d20kh1 + 3d4! + @dex - 1", 1);
+
+            assert_eq!(
+                first.breakdown,
+                roll("2d20kh1 + 3d4! + @dex - 1", 1).breakdown
+            );
+            assert_eq!(
+                first.breakdown,
+                "2d20kh1 [9, (2)] + 3d4! [3, 4!, 2, 1] + @dex (3) - 1 = 21"
+            );
+            assert_eq!(first.total, 21);
+        }
+    }
 }
 
 mod utils {
@@ -1531,3 +9187,774 @@ mod utils {
         (((value - old_min) * (new_max - new_min)) / (old_max - old_min)) + new_min
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead as _, BufReader, Write as _},
+        net::{TcpListener, TcpStream},
+        path::PathBuf,
+        sync::Arc,
+        time::Duration,
+    };
+
+    use oxhttp::model::{HeaderName, Method, Request, Status, Url};
+    use path_tree::PathTree;
+    use rusqlite::Connection;
+    use serde_json::Value;
+
+    use crate::{
+        auth,
+        events::Events,
+        handle, migrations,
+        models::{
+            Action, HistoryFilter, HistoryStatus, Rarity, Role, StatMeta, StatType, StatValue,
+            Visibility, Weapon, WeaponType,
+        },
+        pool::Pool,
+        router, Context, Db, Route, LEVEL_MAX, POOL_SIZE,
+    };
+
+    /// A migrated database with the default campaign's parts and a logged in gm.
+    struct Server {
+        path: PathBuf,
+        router: PathTree<Vec<(Method, Route)>>,
+        ctx: Context,
+        token: String,
+    }
+
+    impl Server {
+        fn new(name: &str) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("lumen-{}-{}.db", name, std::process::id()));
+
+            let mut conn = Connection::open(&path).unwrap();
+            migrations::run(&mut conn).unwrap();
+            Db::add_user(&conn, "gm", "password", Role::Gm).unwrap();
+            Db::init_weapon_parts(&conn, 1).unwrap();
+            let (token, _) = Db::login(&conn, "gm", "password").unwrap();
+
+            Self {
+                ctx: Context {
+                    pool: Arc::new(Pool::new(&path, POOL_SIZE)),
+                    events: Arc::new(Events::new()),
+                },
+                router: router().unwrap(),
+                path,
+                token,
+            }
+        }
+
+        fn execute(&self, sql: &str) {
+            self.ctx.pool.get().unwrap().execute_batch(sql).unwrap();
+        }
+
+        /// Adds the user `name` and sends the following requests as them.
+        fn login(&mut self, name: &str, role: Role) {
+            let conn = self.ctx.pool.get().unwrap();
+            Db::add_user(&*conn, name, "password", role).unwrap();
+            self.token = Db::login(&*conn, name, "password").unwrap().0;
+        }
+
+        fn request(&self, method: Method, path: &str) -> (Status, Value) {
+            self.send(method, path, "")
+        }
+
+        fn send(&self, method: Method, path: &str, body: &str) -> (Status, Value) {
+            let url = Url::parse(&format!("http://localhost{}", path)).unwrap();
+            let cookie = format!("{}={}", auth::SESSION_COOKIE, self.token);
+
+            let mut request = Request::builder(method, url)
+                .with_header("cookie".parse::<HeaderName>().unwrap(), cookie)
+                .unwrap()
+                .with_body(body.to_owned());
+
+            let response = handle(&self.router, &self.ctx, &mut request);
+            let status = response.status();
+            let body = response.into_body().to_string().unwrap();
+
+            (status, serde_json::from_str(&body).unwrap())
+        }
+    }
+
+    impl Drop for Server {
+        fn drop(&mut self) {
+            for suffix in ["", "-wal", "-shm"] {
+                let mut path = self.path.clone().into_os_string();
+                path.push(suffix);
+                let _ = std::fs::remove_file(path);
+            }
+        }
+    }
+
+    #[test]
+    fn concurrent_writes_are_serialized() {
+        const THREADS: i32 = 8;
+        const INCREMENTS: i32 = 25;
+
+        let server = Server::new("concurrent-writes");
+
+        {
+            let conn = server.ctx.pool.get().unwrap();
+            Db::add_character(&*conn, 1, "Ash", None, None).unwrap();
+            Db::add_stat(
+                &*conn,
+                1,
+                "hp",
+                None,
+                StatType::Number,
+                StatMeta::default(),
+                None,
+            )
+            .unwrap();
+        }
+
+        std::thread::scope(|scope| {
+            for _ in 0..THREADS {
+                scope.spawn(|| {
+                    for _ in 0..INCREMENTS {
+                        let mut conn = server.ctx.pool.get().unwrap();
+                        let trans = conn.write().unwrap();
+                        Db::increment_stat(&trans, 1, "Ash", "hp", None).unwrap();
+                        trans.commit().unwrap();
+                    }
+                });
+            }
+        });
+
+        let conn = server.ctx.pool.get().unwrap();
+        let (_, value) = Db::stat_value(&*conn, 1, "Ash", "hp").unwrap();
+
+        assert!(value == StatValue::Number(THREADS * INCREMENTS));
+    }
+
+    #[test]
+    fn bounded_stats_are_clamped() {
+        let server = Server::new("bounded-stats");
+        let conn = server.ctx.pool.get().unwrap();
+
+        Db::add_character(&*conn, 1, "Ash", None, None).unwrap();
+        Db::add_stat(
+            &*conn,
+            1,
+            "ammo",
+            None,
+            StatType::Bounded { min: 0, max: 3 },
+            StatMeta::default(),
+            None,
+        )
+        .unwrap();
+        Db::add_stat(
+            &*conn,
+            1,
+            "hp",
+            None,
+            StatType::Number,
+            StatMeta::default(),
+            None,
+        )
+        .unwrap();
+
+        let ammo = || Db::stat_value(&*conn, 1, "Ash", "ammo").unwrap().1;
+
+        for _ in 0..5 {
+            Db::increment_stat(&*conn, 1, "Ash", "ammo", None).unwrap();
+        }
+        assert!(ammo() == StatValue::Number(3));
+
+        for _ in 0..5 {
+            Db::decrement_stat(&*conn, 1, "Ash", "ammo", None).unwrap();
+        }
+        assert!(ammo() == StatValue::Number(0));
+
+        let (status, body) = server.request(
+            "PATCH".parse().unwrap(),
+            "/api/campaigns/default/character/Ash/hp/toggle",
+        );
+        assert_error(status, &body);
+    }
+
+    #[test]
+    fn events_stream_on_one_connection() {
+        let server = Server::new("events");
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        drop(listener);
+
+        let ctx = Context {
+            pool: Arc::clone(&server.ctx.pool),
+            events: Arc::clone(&server.ctx.events),
+        };
+        let routes = router().unwrap();
+        std::thread::spawn(move || {
+            oxhttp::Server::new(move |request| handle(&routes, &ctx, request)).listen(address)
+        });
+
+        let mut stream = (0..50)
+            .find_map(|_| {
+                TcpStream::connect(address)
+                    .map_err(|_| std::thread::sleep(Duration::from_millis(20)))
+                    .ok()
+            })
+            .unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(10)))
+            .unwrap();
+        write!(
+            stream,
+            "GET /api/campaigns/default/events HTTP/1.1\r\nHost: localhost\r\nCookie: {}={}\r\n\r\n",
+            auth::SESSION_COOKIE,
+            server.token
+        )
+        .unwrap();
+
+        let mut lines = BufReader::new(stream).lines();
+        let mut next_event = || {
+            lines
+                .by_ref()
+                .map(Result::unwrap)
+                .find_map(|line| line.strip_prefix("event: ").map(str::to_owned))
+                .unwrap()
+        };
+
+        assert_eq!(next_event(), "state");
+
+        // the buffer holds something else before each event
+        for name in ["Ash", "Brock", "Misty"] {
+            Db::add_character(&*server.ctx.pool.get().unwrap(), 1, name, None, None).unwrap();
+            server.ctx.events.notify();
+
+            assert_eq!(next_event(), "delta");
+        }
+    }
+
+    #[test]
+    fn generation_is_reproducible() {
+        let server = Server::new("reproducible");
+        let conn = server.ctx.pool.get().unwrap();
+
+        let parts = Db::parts(&*conn, 1).unwrap();
+        let curves = Db::curves(&*conn, 1).unwrap();
+        let table = Db::drop_table(&*conn, 1).unwrap();
+
+        // the catalog as another connection reads it back
+        let fresh = Connection::open(&server.path).unwrap();
+        let fresh_parts = Db::parts(&fresh, 1).unwrap();
+        let fresh_curves = Db::curves(&fresh, 1).unwrap();
+        let fresh_table = Db::drop_table(&fresh, 1).unwrap();
+
+        for level in [0, 1, 12, 20, LEVEL_MAX] {
+            for luck in [-2.0, 0.0, 2.0] {
+                for seed in 0..1000 {
+                    let first = Weapon::generate(&parts, &curves, &table, level, luck, seed);
+                    let second = Weapon::generate(&parts, &curves, &table, level, luck, seed);
+                    let reloaded = Weapon::generate(
+                        &fresh_parts,
+                        &fresh_curves,
+                        &fresh_table,
+                        level,
+                        luck,
+                        seed,
+                    );
+
+                    let first = first.unwrap().id.to_string();
+                    assert_eq!(first, second.unwrap().id.to_string(), "seed {}", seed);
+                    assert_eq!(first, reloaded.unwrap().id.to_string(), "seed {}", seed);
+                }
+            }
+        }
+
+        let golden = Weapon::generate(&parts, &curves, &table, 12, 0.0, 42).unwrap();
+        assert_eq!(golden.id.to_string(), "0c0104012710013e");
+    }
+
+    #[test]
+    fn rockets_need_their_level_band() {
+        let server = Server::new("rocket-band");
+        let conn = server.ctx.pool.get().unwrap();
+
+        let parts = Db::parts(&*conn, 1).unwrap();
+        let curves = Db::curves(&*conn, 1).unwrap();
+        let table = Db::drop_table(&*conn, 1).unwrap();
+        let rockets = |level| {
+            (0..200)
+                .map(|seed| Weapon::generate(&parts, &curves, &table, level, 0.0, seed).unwrap())
+                .filter(|weapon| weapon.typ == WeaponType::Rocket)
+                .count()
+        };
+
+        for level in [0, 12, 19] {
+            assert_eq!(rockets(level), 0, "rocket at level {}", level);
+        }
+        assert!(rockets(20) > 0);
+        assert!(rockets(LEVEL_MAX) > 0);
+    }
+
+    #[test]
+    fn luck_favours_rarer_parts() {
+        let server = Server::new("luck");
+        let conn = server.ctx.pool.get().unwrap();
+
+        let parts = Db::parts(&*conn, 1).unwrap();
+        let curves = Db::curves(&*conn, 1).unwrap();
+        let table = Db::drop_table(&*conn, 1).unwrap();
+        let rare = |luck| {
+            (0..300)
+                .map(|seed| Weapon::generate(&parts, &curves, &table, 20, luck, seed).unwrap())
+                .flat_map(|weapon| [weapon.barrel, weapon.body, weapon.magazine, weapon.stock])
+                .filter(|part| part.rarity >= Rarity::Rare)
+                .count()
+        };
+
+        let plain = rare(0.0);
+        let lucky = rare(1.0);
+        assert!(
+            lucky > plain,
+            "{} rare parts with luck, {} without",
+            lucky,
+            plain
+        );
+    }
+
+    #[test]
+    fn weight_changes_are_sent() {
+        let server = Server::new("weight-changes");
+
+        let (status, body) = server.request(Method::GET, "/api/campaigns/default/state");
+        assert_eq!(status, Status::OK, "{}", body);
+        let revision = body["revision"].as_i64().unwrap();
+
+        let (status, body) = server.send(
+            Method::POST,
+            "/api/campaigns/default/weapon/weights",
+            r#"{"type": "rocket", "level": 10, "weight": 2}"#,
+        );
+        assert_eq!(status, Status::OK, "{}", body);
+
+        let (status, body) = server.request(
+            Method::GET,
+            &format!("/api/campaigns/default/state?since={}", revision),
+        );
+        assert_eq!(status, Status::OK, "{}", body);
+        assert_eq!(body["weights"], true, "{}", body);
+        let revision = body["revision"].as_i64().unwrap();
+
+        let (status, body) = server.request(
+            Method::DELETE,
+            "/api/campaigns/default/weapon/rarities/unique/20",
+        );
+        assert_eq!(status, Status::OK, "{}", body);
+
+        let (status, body) = server.request(
+            Method::GET,
+            &format!("/api/campaigns/default/state?since={}", revision),
+        );
+        assert_eq!(status, Status::OK, "{}", body);
+        assert_eq!(body["weights"], true, "{}", body);
+        let revision = body["revision"].as_i64().unwrap();
+
+        let (status, body) = server.request(
+            Method::GET,
+            &format!("/api/campaigns/default/state?since={}", revision),
+        );
+        assert_eq!(status, Status::OK, "{}", body);
+        assert_eq!(body["weights"], false, "{}", body);
+    }
+
+    #[test]
+    fn unique_part_batches_are_limited() {
+        let server = Server::new("unique-parts");
+
+        // magazines only come in common, the rarest part type at level 1
+        let limit = server
+            .ctx
+            .pool
+            .get()
+            .unwrap()
+            .query_row(
+                "SELECT COUNT(*) FROM weapon_parts WHERE campaign = 1 AND type = 'magazine'",
+                [],
+                |row| row.get::<_, usize>(0),
+            )
+            .unwrap();
+        let batch = |count| {
+            server.request(
+                Method::GET,
+                &format!(
+                    "/api/campaigns/default/weapon/generate/batch?count={}&level=1&unique_parts=true&seed=3",
+                    count
+                ),
+            )
+        };
+
+        let (status, body) = batch(limit);
+        assert_eq!(status, Status::OK, "{}", body);
+        let weapons = body.as_array().unwrap();
+        assert_eq!(weapons.len(), limit);
+        let mut magazines = weapons
+            .iter()
+            .map(|weapon| weapon["magazine"]["id"].as_u64().unwrap())
+            .collect::<Vec<_>>();
+        magazines.sort();
+        magazines.dedup();
+        assert_eq!(magazines.len(), limit);
+
+        let (status, body) = batch(limit + 1);
+        assert_eq!(status, Status::UNPROCESSABLE_CONTENT, "{}", body);
+        let message = body["error"]["message"].as_str().unwrap();
+        assert!(
+            message.contains(&format!("at most {}", limit)),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn part_ids_are_never_reused() {
+        let server = Server::new("part-ids");
+        let part = |name: &str| {
+            format!(
+                r#"{{"name": "{}", "details": "", "part": "barrel", "rarity": "common", "company": "arksys"}}"#,
+                name
+            )
+        };
+        let id = |name: &str| {
+            server
+                .ctx
+                .pool
+                .get()
+                .unwrap()
+                .query_row(
+                    "SELECT id FROM weapon_parts WHERE campaign = 1 AND name = ?",
+                    [name],
+                    |row| row.get::<_, i64>(0),
+                )
+                .unwrap()
+        };
+
+        let (status, body) = server.send(
+            Method::POST,
+            "/api/campaigns/default/weapon/part",
+            &part("first"),
+        );
+        assert_eq!(status, Status::OK, "{}", body);
+        let first = id("first");
+
+        let (status, body) = server.request(
+            Method::DELETE,
+            &format!("/api/campaigns/default/weapon/part/{}", first),
+        );
+        assert_eq!(status, Status::OK, "{}", body);
+
+        let (status, body) = server.send(
+            Method::POST,
+            "/api/campaigns/default/weapon/part",
+            &part("second"),
+        );
+        assert_eq!(status, Status::OK, "{}", body);
+        assert_eq!(id("second"), first + 1);
+
+        server.execute("UPDATE campaigns SET part_seq = 255 WHERE id = 1");
+        let (status, body) = server.send(
+            Method::POST,
+            "/api/campaigns/default/weapon/part",
+            &part("third"),
+        );
+        assert_eq!(status, Status::CONFLICT, "{}", body);
+    }
+
+    #[test]
+    fn shown_weapon_stays_in_full_state() {
+        let server = Server::new("shown-weapon");
+
+        {
+            let conn = server.ctx.pool.get().unwrap();
+            Db::add_character(&*conn, 1, "Ash", None, None).unwrap();
+            Db::add_stat(
+                &*conn,
+                1,
+                "hp",
+                None,
+                StatType::Number,
+                StatMeta::default(),
+                None,
+            )
+            .unwrap();
+        }
+
+        let (status, body) = server.send(
+            Method::POST,
+            "/api/campaigns/default/weapon/generate",
+            r#"{"level": 1, "seed": 1}"#,
+        );
+        assert_eq!(status, Status::OK, "{}", body);
+        let id = body["weapon"]["id"].clone();
+
+        let (status, body) = server.request(
+            "PATCH".parse().unwrap(),
+            "/api/campaigns/default/character/Ash/hp/increment",
+        );
+        assert_eq!(status, Status::OK, "{}", body);
+        assert_eq!(body["weapon"]["id"], id);
+
+        let (status, body) = server.request(Method::GET, "/api/campaigns/default/state");
+        assert_eq!(status, Status::OK, "{}", body);
+        assert_eq!(body["weapon"]["id"], id);
+    }
+
+    #[test]
+    fn inventories_follow_part_changes() {
+        let server = Server::new("inventory-parts");
+        let conn = server.ctx.pool.get().unwrap();
+
+        Db::add_character(&*conn, 1, "Ash", None, None).unwrap();
+        Db::add_character(&*conn, 1, "Brock", None, None).unwrap();
+
+        let weapon = Weapon::generate(
+            &Db::parts(&*conn, 1).unwrap(),
+            &Db::curves(&*conn, 1).unwrap(),
+            &Db::drop_table(&*conn, 1).unwrap(),
+            1,
+            0.0,
+            1,
+        )
+        .unwrap();
+
+        let (status, body) = server.send(
+            Method::POST,
+            "/api/campaigns/default/inventory/Ash",
+            &format!(r#"{{"id": "{}"}}"#, weapon.id),
+        );
+        assert_eq!(status, Status::OK, "{}", body);
+        let revision = body["revision"].as_i64().unwrap();
+
+        Db::rename_weapon_part(&*conn, 1, weapon.body.id, "Renamed").unwrap();
+
+        let (status, body) = server.request(
+            Method::GET,
+            &format!("/api/campaigns/default/state?since={}", revision),
+        );
+        assert_eq!(status, Status::OK, "{}", body);
+        let characters = body["characters"].as_array().unwrap();
+        assert_eq!(characters.len(), 1, "{}", body);
+        assert_eq!(characters[0]["name"], "Ash");
+        assert_eq!(
+            characters[0]["inventory"][0]["weapon"]["body"]["name"],
+            "Renamed"
+        );
+        let revision = body["revision"].as_i64().unwrap();
+
+        Db::remove_weapon_part(&*conn, 1, weapon.body.id).unwrap();
+
+        let (status, body) = server.request(
+            Method::GET,
+            &format!("/api/campaigns/default/state?since={}", revision),
+        );
+        assert_eq!(status, Status::OK, "{}", body);
+        assert_eq!(body["characters"][0]["name"], "Ash", "{}", body);
+        assert_eq!(body["characters"][0]["inventory"][0]["weapon"], Value::Null);
+    }
+
+    #[test]
+    fn undo_and_redo_replay_history() {
+        let server = Server::new("undo-redo");
+        let conn = server.ctx.pool.get().unwrap();
+        let gm = Some("gm");
+
+        let hp = |stat| match Db::stat_value(&*conn, 1, "Ash", stat) {
+            Ok((_, StatValue::Number(value))) => Some(value),
+            _ => None,
+        };
+        let history = || {
+            let filter = HistoryFilter {
+                limit: 100,
+                ..HistoryFilter::default()
+            };
+
+            Db::history(&*conn, 1, &filter, true)
+                .unwrap()
+                .into_iter()
+                .map(|entry| (entry.action, entry.status))
+                .collect::<Vec<_>>()
+        };
+
+        Db::add_character(&*conn, 1, "Ash", None, gm).unwrap();
+        Db::add_stat(
+            &*conn,
+            1,
+            "hp",
+            None,
+            StatType::Number,
+            StatMeta::default(),
+            gm,
+        )
+        .unwrap();
+        Db::increment_stat(&*conn, 1, "Ash", "hp", gm).unwrap();
+        assert_eq!(hp("hp"), Some(1));
+
+        Db::undo(&*conn, 1, 1).unwrap();
+        assert_eq!(hp("hp"), Some(0));
+        assert_eq!(history()[0], (Action::IncrementStat, HistoryStatus::Undone));
+
+        Db::redo(&*conn, 1, 1).unwrap();
+        assert_eq!(hp("hp"), Some(1));
+        assert_eq!(history()[0], (Action::IncrementStat, HistoryStatus::Done));
+        assert!(Db::redo(&*conn, 1, 1).is_err());
+
+        // the removed character's snapshot follows the rename of its stat
+        Db::remove_character(&*conn, 1, "Ash", gm).unwrap();
+        Db::rename_stat(&*conn, 1, "hp", "health", gm).unwrap();
+        let filter = HistoryFilter {
+            action: Some(Action::RemoveCharacter),
+            limit: 1,
+            ..HistoryFilter::default()
+        };
+        let removed = Db::history(&*conn, 1, &filter, true).unwrap();
+        assert_eq!(removed[0].before["stats"]["health"], 1);
+
+        Db::undo(&*conn, 1, 2).unwrap();
+        assert_eq!(hp("hp"), Some(1));
+        assert_eq!(
+            history()[..2],
+            [
+                (Action::RenameStat, HistoryStatus::Undone),
+                (Action::RemoveCharacter, HistoryStatus::Undone),
+            ]
+        );
+
+        Db::redo(&*conn, 1, 2).unwrap();
+        assert_eq!(hp("hp"), None);
+        assert_eq!(hp("health"), None);
+        assert!(history()
+            .iter()
+            .all(|(_, status)| *status == HistoryStatus::Done));
+
+        // a change after an undo discards what was undone
+        Db::undo(&*conn, 1, 1).unwrap();
+        Db::add_character(&*conn, 1, "Bo", None, gm).unwrap();
+        assert_eq!(history()[1], (Action::RenameStat, HistoryStatus::Discarded));
+        assert!(Db::redo(&*conn, 1, 1).is_err());
+    }
+
+    #[test]
+    fn gm_only_stats_are_hidden_from_players() {
+        let mut server = Server::new("hidden-stats");
+
+        {
+            let conn = server.ctx.pool.get().unwrap();
+            Db::add_character(&*conn, 1, "Ash", None, None).unwrap();
+            for (stat, visibility) in [("hp", Visibility::Public), ("secret", Visibility::Gm)] {
+                let meta = StatMeta {
+                    visibility,
+                    ..StatMeta::default()
+                };
+                Db::add_stat(&*conn, 1, stat, None, StatType::Number, meta, None).unwrap();
+                Db::increment_stat(&*conn, 1, "Ash", stat, Some("gm")).unwrap();
+            }
+        }
+
+        let (status, body) = server.send(
+            Method::POST,
+            "/api/campaigns/default/roll",
+            r#"{"expr": "1d20+@secret", "character": "Ash", "seed": 1}"#,
+        );
+        assert_eq!(status, Status::OK, "{}", body);
+
+        server.login("player", Role::Player);
+        Db::set_owner(&*server.ctx.pool.get().unwrap(), 1, "Ash", Some("player")).unwrap();
+
+        let (status, body) = server.request(Method::GET, "/api/campaigns/default/history");
+        assert_eq!(status, Status::OK, "{}", body);
+        let entries = body.as_array().unwrap();
+        assert!(
+            entries.iter().any(|entry| entry["stat"] == "hp"),
+            "{}",
+            body
+        );
+        assert!(!body.to_string().contains("secret"), "{}", body);
+
+        let (status, body) =
+            server.request(Method::GET, "/api/campaigns/default/history?stat=secret");
+        assert_eq!(status, Status::OK, "{}", body);
+        assert_eq!(body, Value::Array(Vec::new()));
+
+        let (status, body) = server.request(Method::GET, "/api/campaigns/default/rolls");
+        assert_eq!(status, Status::OK, "{}", body);
+        assert_eq!(body, Value::Array(Vec::new()));
+
+        let (status, body) = server.send(
+            Method::POST,
+            "/api/campaigns/default/roll",
+            r#"{"expr": "1d20+@secret", "character": "Ash", "seed": 1}"#,
+        );
+        assert_eq!(status, Status::NOT_FOUND, "{}", body);
+
+        let (status, body) = server.request(Method::GET, "/api/campaigns/default/state");
+        assert_eq!(status, Status::OK, "{}", body);
+        let revision = body["revision"].as_i64().unwrap();
+
+        Db::remove_stat(&*server.ctx.pool.get().unwrap(), 1, "secret", Some("gm")).unwrap();
+
+        let (status, body) = server.request(
+            Method::GET,
+            &format!("/api/campaigns/default/state?since={}", revision),
+        );
+        assert_eq!(status, Status::OK, "{}", body);
+        assert_eq!(body["removed"]["stats"], Value::Array(Vec::new()));
+
+        let (status, body) = server.request(Method::GET, "/api/campaigns/default/history");
+        assert_eq!(status, Status::OK, "{}", body);
+        assert!(!body.to_string().contains("secret"), "{}", body);
+    }
+
+    fn assert_error(status: Status, body: &Value) {
+        assert!(
+            status == Status::BAD_REQUEST || status == Status::UNPROCESSABLE_CONTENT,
+            "{} {}",
+            status,
+            body
+        );
+        assert!(body["error"]["message"].is_string(), "{}", body);
+    }
+
+    #[test]
+    fn missing_part_is_rejected() {
+        let server = Server::new("missing-part");
+
+        for _ in 0..2 {
+            let id = crate::models::Id::from(1, 0, 250, 251, 252, 253);
+            let (status, body) = server.send(
+                Method::POST,
+                "/api/campaigns/default/weapon/build",
+                &format!(r#"{{"id": "{}"}}"#, id),
+            );
+            assert_error(status, &body);
+
+            let (status, body) = server.send(
+                Method::POST,
+                "/api/campaigns/default/weapon/generate",
+                r#"{"level": 1, "seed": 1}"#,
+            );
+            assert_eq!(status, Status::OK, "{}", body);
+        }
+    }
+
+    #[test]
+    fn corrupt_curve_is_rejected() {
+        let server = Server::new("corrupt-curve");
+        server.execute("UPDATE weapon_curves SET type = 'sine' WHERE name = 'pistol'");
+
+        for _ in 0..2 {
+            let (status, body) = server.send(
+                Method::POST,
+                "/api/campaigns/default/weapon/generate",
+                r#"{"level": 1, "seed": 1}"#,
+            );
+            assert_error(status, &body);
+
+            let (status, body) = server.request(Method::GET, "/api/campaigns/default/history");
+            assert_eq!(status, Status::OK, "{}", body);
+        }
+    }
+}